@@ -1,28 +1,124 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
-use core::panic;
 use eframe::egui;
-use std::process::Command;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use iracing_overlay::hub::{TelemetryHub, HUB_ADDRESS};
+use iracing_overlay::registry::{Overlays, SettingsChange, Widget, Widgets, WindowsConfig};
+use iracing_overlay::supervisor::OverlaySupervisor;
+use iracing_overlay::telemetry::{self, TelemetrySampler, TelemetrySnapshot, Variable};
+use iracing_overlay::util::{overlay_dir, overlay_path, toggle};
+use iracing_overlay::widgets;
+
+/// How often the overlay processes are checked on when nothing else needs a repaint.
+const SUPERVISOR_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long the settings have to be left alone before edits are saved, so
+/// dragging a value doesn't write the file every frame.
+const CONFIG_SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// How wide the preview on an overlay's settings page starts.
+const PREVIEW_WIDTH: f32 = 300.;
+
+/// Sample `variables` for overlays drawn by the main app, recording every
+/// sample and repainting when one changes while `shown`.
+fn start_sampler(
+    ctx: egui::Context,
+    variables: &[Variable],
+    recorder: impl Fn(&TelemetrySnapshot) + Send + 'static,
+    shown: Arc<AtomicBool>,
+) -> TelemetrySampler {
+    TelemetrySampler::start(variables, telemetry::overlay_connector(variables), move |snapshot, changed| {
+        // History is recorded whether or not anything is showing it
+        recorder(snapshot);
+        if changed && shown.load(Ordering::Relaxed) {
+            ctx.request_repaint();
+        }
+    })
+}
+
+/// The overlays when they're drawn by the main app, sharing one telemetry
+/// sampler. Only kept while they run in this app, as otherwise their
+/// recorders would be doing the same work as the overlay programs.
+struct HostedOverlays {
+    widgets: Widgets,
+    telemetry: TelemetrySampler,
+    /// Whether any of the overlays are on screen, so new telemetry needs a repaint
+    shown: Arc<AtomicBool>,
+}
 
-use crate::util::{
-    toggle, HomeConfig, LaspLapTimeConfig, Overlays, SpeedConfig, SpeedUnits, WindowProcesses,
-    WindowsConfig,
-};
+impl HostedOverlays {
+    fn start(ctx: egui::Context, config: &WindowsConfig) -> Self {
+        let widgets = Widgets::new(config);
+        let shown = Arc::new(AtomicBool::new(true));
+        let telemetry = start_sampler(ctx, &Widgets::variables(), widgets.recorder(), shown.clone());
+        Self { widgets, telemetry, shown }
+    }
+}
+
+/// The overlay on the open settings page, drawn on its own while the overlays
+/// run as separate programs, and dropped when the page is left.
+struct PreviewedOverlay {
+    widget: Widget,
+    telemetry: TelemetrySampler,
+}
+
+impl PreviewedOverlay {
+    fn start(ctx: egui::Context, overlay: Overlays, config: &WindowsConfig) -> Option<Self> {
+        let widget = Widget::new(overlay, config)?;
+        let recorder = widget.recorder();
+        let record = move |snapshot: &TelemetrySnapshot| {
+            if let Some(recorder) = &recorder {
+                recorder(snapshot);
+            }
+        };
+        let telemetry = start_sampler(ctx, &widget.variables(), record, Arc::new(AtomicBool::new(true)));
+        Some(Self { widget, telemetry })
+    }
+}
 
 pub struct MainApp {
     menu_option: Overlays,
     config: WindowsConfig,
-    windows: WindowProcesses,
+    supervisor: OverlaySupervisor,
+    /// Started when the overlays are first drawn in this app, and kept running
+    /// so their history isn't lost until they're switched back to separate programs
+    hosted: Option<HostedOverlays>,
+    /// Only while an overlay's settings page is open and there's no `hosted`
+    preview: Option<PreviewedOverlay>,
+    /// When the settings were last edited, while there are edits still to save
+    last_edit: Option<Instant>,
+    /// The overlay folder as typed in, which may not be finished yet
+    overlay_dir_text: String,
+    /// Why the last overlay failed to launch, until it's dismissed
+    launch_error: Option<String>,
+    _hub: Option<TelemetryHub>,
 }
 
 impl MainApp {
     pub fn new() -> Self {
         let config: WindowsConfig = confy::load("iracing-overlays", None).unwrap_or_default();
-        let windows: WindowProcesses = WindowProcesses::default();
+        // Binding fails if another copy of the app is already running a hub,
+        // in which case the overlays will use that one
+        let hub = TelemetryHub::start(HUB_ADDRESS).ok();
+        let overlay_dir_text = config
+            .overlay_dir
+            .as_ref()
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_default();
         Self {
             menu_option: Overlays::Home,
             config,
-            windows,
+            supervisor: OverlaySupervisor::new(),
+            hosted: None,
+            preview: None,
+            last_edit: None,
+            overlay_dir_text,
+            launch_error: None,
+            _hub: hub,
         }
     }
     fn render_top_panel(&mut self, ctx: &egui::Context) {
@@ -30,25 +126,35 @@ impl MainApp {
             ui.add_space(5.0);
             ui.vertical_centered(|ui| ui.heading("iRacing Overlays"));
             ui.add_space(5.0);
+            if let Some(error) = &self.launch_error {
+                let mut dismissed = false;
+                ui.horizontal(|ui| {
+                    ui.colored_label(egui::Color32::RED, error);
+                    dismissed = ui.button("Dismiss").clicked();
+                });
+                if dismissed {
+                    self.launch_error = None;
+                }
+                ui.add_space(5.0);
+            }
         });
     }
 
     fn render_left_side_panel(&mut self, ctx: &egui::Context) {
         egui::SidePanel::left("left").show(ctx, |ui| {
             egui::ScrollArea::vertical().show(ui, |ui| {
-                let home_btn = ui.add(egui::Button::new("Home"));
-                if home_btn.clicked() {
-                    self.menu_option = Overlays::Home;
+                let pages = std::iter::once(Overlays::Home).chain(Overlays::WINDOWS.iter().copied());
+                for page in pages {
+                    if ui.add(egui::Button::new(page.title())).clicked() {
+                        self.menu_option = page;
+                    }
                 }
 
-                let speed_btn = ui.add(egui::Button::new("Speed"));
-                if speed_btn.clicked() {
-                    self.menu_option = Overlays::Speed;
-                }
+                ui.separator();
 
-                let lastlapbtn = ui.add(egui::Button::new("Last Lap Time"));
-                if lastlapbtn.clicked() {
-                    self.menu_option = Overlays::LastLapTime;
+                let log_btn = ui.add(egui::Button::new("Log"));
+                if log_btn.clicked() {
+                    self.menu_option = Overlays::Log;
                 }
             });
         });
@@ -57,8 +163,8 @@ impl MainApp {
     fn render_main(&mut self, ctx: &egui::Context) {
         egui::CentralPanel::default().show(ctx, |ui| match self.menu_option {
             Overlays::Home => self.render_home_options(ui),
-            Overlays::Speed => self.render_speed_options(ui),
-            Overlays::LastLapTime => self.render_lastlaptime_options(ui),
+            Overlays::Log => self.render_log(ui),
+            overlay => self.render_overlay_options(overlay, ui),
         });
     }
 
@@ -80,163 +186,249 @@ impl MainApp {
 
         ui.horizontal(|ui| {
             ui.label("Font size");
-            ui.add(egui::DragValue::new(&mut self.config.home_config.font_size).range(6.0..=40.0).speed(0.5));
+            let font_size = egui::DragValue::new(&mut self.config.home_config.font_size).range(6.0..=40.0).speed(0.5);
+            if ui.add(font_size).changed() {
+                self.config_edited();
+            }
         });
 
         ui.horizontal(|ui| {
             ui.label("Pick background colour");
-            ui.color_edit_button_srgba(&mut self.config.home_config.bg_colour);
+            if ui.color_edit_button_srgba(&mut self.config.home_config.bg_colour).changed() {
+                self.config_edited();
+            }
         });
 
         ui.horizontal(|ui| {
             ui.label("Pick font colour");
-            ui.color_edit_button_srgba(&mut self.config.home_config.font_colour);
+            if ui.color_edit_button_srgba(&mut self.config.home_config.font_colour).changed() {
+                self.config_edited();
+            }
         });
-    }
-
-    fn render_speed_options(&mut self, ui: &mut egui::Ui) {
-        ui.label("Speed");
 
         ui.horizontal(|ui| {
-            ui.label(format!("Show window: {}", self.config.speed_config.display));
-            let display_toggle = ui.add(toggle(&mut self.config.speed_config.display));
-            if display_toggle.clicked() {
-                self.save_config();
-                self.manage_speed_overlay();
+            ui.label("Overlay folder");
+            let default_dir = overlay_dir(None).map(|dir| dir.display().to_string()).unwrap_or_default();
+            let dir_edit = ui.add(egui::TextEdit::singleline(&mut self.overlay_dir_text).hint_text(default_dir));
+            if dir_edit.changed() {
+                let text = self.overlay_dir_text.trim();
+                self.config.overlay_dir = (!text.is_empty()).then(|| PathBuf::from(text));
+                self.config_edited();
             }
         });
 
         ui.horizontal(|ui| {
-            ui.label("Font size");
-            ui.add(egui::DragValue::new(&mut self.config.speed_config.font_size).range(6.0..=40.0).speed(0.5));
+            ui.label("Run overlays in this app");
+            let single_process_toggle = ui.add(toggle(&mut self.config.home_config.single_process));
+            if single_process_toggle.clicked() {
+                self.save_config();
+                self.manage_overlays();
+            }
         });
 
-        let mut units_enum = self.config.speed_config.units.clone();
         ui.horizontal(|ui| {
-            ui.label("Units:");
-            let unit_select_ms = ui.radio_value(
-                &mut units_enum,
-                SpeedUnits::Metrespersecond,
-                "metres per second",
-            );
-            let unit_select_mph =
-                ui.radio_value(&mut units_enum, SpeedUnits::Milesperhour, "miles per hour");
-            let unit_select_kph = ui.radio_value(
-                &mut units_enum,
-                SpeedUnits::Kilometresperhour,
-                "kilometres per hour",
-            );
-            if unit_select_ms.clicked() {
-                self.config.speed_config.units = SpeedUnits::Metrespersecond;
-            }
-            if unit_select_mph.clicked() {
-                self.config.speed_config.units = SpeedUnits::Milesperhour;
-            }
-            if unit_select_kph.clicked() {
-                self.config.speed_config.units = SpeedUnits::Kilometresperhour;
+            ui.label("Restart crashed overlays");
+            if ui.add(toggle(&mut self.config.home_config.auto_restart)).changed() {
+                self.config_edited();
             }
         });
 
         ui.horizontal(|ui| {
-            ui.label("Pick background colour");
-            ui.color_edit_button_srgba(&mut self.config.speed_config.overlay_bgcolour);
+            ui.label("Restarts before giving up");
+            let restart_limit = egui::DragValue::new(&mut self.config.home_config.restart_limit).range(1..=20);
+            if ui.add(restart_limit).changed() {
+                self.config_edited();
+            }
         });
+    }
 
-        ui.horizontal(|ui| {
-            ui.label("Pick font colour");
-            ui.color_edit_button_srgba(&mut self.config.speed_config.overlay_fontcolour);
-        });
+    /// Start or stop each overlay to match its "Show window" setting, and
+    /// switch the setting off for overlays that have stopped by themselves.
+    fn manage_overlays(&mut self) {
+        let restart_limit = if self.config.home_config.auto_restart {
+            self.config.home_config.restart_limit
+        } else {
+            0
+        };
+        let exited = self.supervisor.poll(restart_limit);
+        for (overlay, _) in &exited {
+            if let Some(display) = self.config.display_mut(*overlay) {
+                *display = false;
+            }
+        }
+        if !exited.is_empty() {
+            self.save_config();
+        }
+
+        for &overlay in Overlays::WINDOWS {
+            let Some(name) = overlay.binary() else {
+                continue;
+            };
+            // Nothing to start when the overlays are drawn in this app instead
+            let display = self.config.display_mut(overlay).is_some_and(|display| *display)
+                && !self.config.home_config.single_process;
+            let running = self.supervisor.is_running(overlay);
+            if display && !running {
+                if let Err(error) = self.launch_overlay(overlay, name) {
+                    self.supervisor.log().push(format!("[{}] {}", name, error));
+                    self.launch_error = Some(error);
+                    // Switch it back off rather than trying again every frame
+                    if let Some(display) = self.config.display_mut(overlay) {
+                        *display = false;
+                    }
+                }
+            } else if !display && running {
+                self.supervisor.stop(overlay);
+            }
+        }
     }
 
-    fn manage_speed_overlay(&mut self) {
-        if self.config.speed_config.display && self.windows.speed.is_none() {
-            self.windows.speed = Some(
-                Command::new(r"C:\Program Files (x86)\iRacing Overlays\speed")
-                    .spawn()
-                    .expect("Failed to launch speed overlay"),
+    /// Draw the overlays that are switched on as windows of this app, when
+    /// they aren't being run as separate programs.
+    fn show_hosted_overlays(&mut self, ctx: &egui::Context) {
+        let previewing = Overlays::WINDOWS.contains(&self.menu_option);
+        if !previewing || self.config.home_config.single_process {
+            self.preview = None;
+        }
+        if !self.config.home_config.single_process {
+            self.hosted = None;
+            return;
+        }
+        let displayed = Overlays::WINDOWS
+            .iter()
+            .any(|&overlay| self.config.display_mut(overlay).is_some_and(|display| *display));
+        let hosted = self
+            .hosted
+            .get_or_insert_with(|| HostedOverlays::start(ctx.clone(), &self.config));
+        hosted.shown.store(previewing || displayed, Ordering::Relaxed);
+        let snapshot = hosted.telemetry.latest();
+        let mut closed = false;
+        for &overlay in Overlays::WINDOWS {
+            if !self.config.display_mut(overlay).is_some_and(|display| *display) {
+                continue;
+            }
+            let config = &self.config;
+            let close_requested = ctx.show_viewport_immediate(
+                egui::ViewportId::from_hash_of(overlay.title()),
+                widgets::viewport(overlay.title()),
+                |ctx, _class| {
+                    widgets::overlay_window(ctx, |ui| hosted.widgets.show(overlay, ui, config, &snapshot));
+                    ctx.input(|i| i.viewport().close_requested())
+                },
             );
-        } else if !self.config.speed_config.display && self.windows.speed.is_some() {
-            let Some(ref mut child) = self.windows.speed else {
-                panic!("Oops")
-            };
-            let _ = child.kill();
-            self.windows.speed = None;
+            // Closing the window switches the overlay off, as with the separate programs
+            if close_requested {
+                if let Some(display) = self.config.display_mut(overlay) {
+                    *display = false;
+                }
+                closed = true;
+            }
+        }
+        if closed {
+            self.save_config();
         }
     }
 
-    fn render_lastlaptime_options(&mut self, ui: &mut egui::Ui) {
-        ui.label("Last lap time");
+    /// Start the overlay called `name`, or say why it couldn't be started.
+    fn launch_overlay(&mut self, overlay: Overlays, name: &str) -> Result<(), String> {
+        let path = overlay_path(&overlay_dir(self.config.overlay_dir.as_deref())?, name);
+        if !path.is_file() {
+            return Err(format!("Can't find the {} overlay at {}", name, path.display()));
+        }
+        self.supervisor
+            .start(overlay, name, &path)
+            .map_err(|e| format!("Failed to launch {}: {}", path.display(), e))
+    }
 
+    fn render_log(&mut self, ui: &mut egui::Ui) {
         ui.horizontal(|ui| {
-          ui.label(format!(
-              "Show window: {}",
-              self.config.lastlaptime_config.display
-          ));
-          let display_toggle = ui.add(toggle(&mut self.config.lastlaptime_config.display));
-
-          if display_toggle.clicked() {
-              self.save_config();
-              self.manage_lastlaptime_overlay();
-          }
+            ui.label("Log");
+            if ui.button("Clear").clicked() {
+                self.supervisor.log().clear();
+            }
         });
 
-        ui.horizontal(|ui| {
-            ui.label("Font size");
-            ui.add(egui::DragValue::new(&mut self.config.lastlaptime_config.font_size).range(6.0..=40.0).speed(0.5));
+        egui::ScrollArea::vertical().stick_to_bottom(true).show(ui, |ui| {
+            for line in self.supervisor.log().lines() {
+                ui.monospace(line);
+            }
         });
+    }
 
-        ui.horizontal(|ui| {
-            ui.label("Pick background colour");
-            ui.color_edit_button_srgba(&mut self.config.lastlaptime_config.overlay_bgcolour);
-        });
+    fn render_overlay_options(&mut self, overlay: Overlays, ui: &mut egui::Ui) {
+        egui::SidePanel::right("preview")
+            .default_width(PREVIEW_WIDTH)
+            .show_inside(ui, |ui| {
+                ui.label("Preview");
+                self.show_preview(overlay, ui);
+            });
 
-        ui.horizontal(|ui| {
-            ui.label("Pick font colour");
-            ui.color_edit_button_srgba(&mut self.config.lastlaptime_config.overlay_fontcolour);
-        });
+        ui.label(overlay.title());
+
+        match self.config.settings_ui(overlay, ui) {
+            SettingsChange::Switched => {
+                self.save_config();
+                self.manage_overlays();
+            }
+            SettingsChange::Edited => self.config_edited(),
+            SettingsChange::Unchanged => {}
+        }
     }
 
-    fn manage_lastlaptime_overlay(&mut self) {
-        if self.config.lastlaptime_config.display && self.windows.lastlaptime.is_none() {
-            self.windows.lastlaptime = Some(
-                Command::new(r"C:\Program Files (x86)\iRacing Overlays\lastlaptime")
-                    .spawn()
-                    .expect("Failed to launch Last Lap Time overlay"),
-            );
-        } else if !self.config.lastlaptime_config.display && self.windows.lastlaptime.is_some() {
-            let Some(ref mut child) = self.windows.lastlaptime else {
-                panic!("Oops")
-            };
-            let _ = child.kill();
-            self.windows.lastlaptime = None;
+    /// Draw `overlay` with the hosted overlays if they're running, or else on
+    /// its own, starting it if it isn't the one already previewed.
+    fn show_preview(&mut self, overlay: Overlays, ui: &mut egui::Ui) {
+        if self.config.home_config.single_process {
+            let hosted = self
+                .hosted
+                .get_or_insert_with(|| HostedOverlays::start(ui.ctx().clone(), &self.config));
+            hosted.widgets.show(overlay, ui, &self.config, &hosted.telemetry.latest());
+            return;
+        }
+        if self.preview.as_ref().map(|preview| preview.widget.overlay()) != Some(overlay) {
+            self.preview = PreviewedOverlay::start(ui.ctx().clone(), overlay, &self.config);
+        }
+        if let Some(preview) = &self.preview {
+            preview.widget.show(ui, &self.config, &preview.telemetry.latest());
         }
     }
 
-    fn save_config(&self) {
-        let _ = confy::store(
-            "iracing-overlays",
-            None,
-            WindowsConfig {
-                home_config: HomeConfig {
-                    font_size: self.config.home_config.font_size,
-                    bg_colour: self.config.home_config.bg_colour,
-                    font_colour: self.config.home_config.font_colour,
-                },
-                speed_config: SpeedConfig {
-                    display: self.config.speed_config.display,
-                    font_size: self.config.speed_config.font_size,
-                    units: self.config.speed_config.units.clone(),
-                    overlay_bgcolour: self.config.speed_config.overlay_bgcolour,
-                    overlay_fontcolour: self.config.speed_config.overlay_fontcolour,
-                },
-                lastlaptime_config: LaspLapTimeConfig {
-                    display: self.config.lastlaptime_config.display,
-                    font_size: self.config.lastlaptime_config.font_size,
-                    overlay_bgcolour: self.config.lastlaptime_config.overlay_bgcolour,
-                    overlay_fontcolour: self.config.lastlaptime_config.overlay_fontcolour,
-                },
-            },
-        );
+    /// Apply the settings to the overlays drawn here.
+    fn reconfigure_drawn(&self) {
+        if let Some(hosted) = &self.hosted {
+            hosted.widgets.reconfigure(&self.config);
+        }
+        if let Some(preview) = &self.preview {
+            preview.widget.reconfigure(&self.config);
+        }
+    }
+
+    /// Save the settings for the overlays to pick up, and apply them to the
+    /// ones drawn here.
+    fn save_config(&mut self) {
+        self.last_edit = None;
+        let _ = confy::store("iracing-overlays", None, &self.config);
+        self.reconfigure_drawn();
+    }
+
+    /// Apply an edited setting to the overlays drawn here straight away, and
+    /// save it once the settings have been left alone for a moment.
+    fn config_edited(&mut self) {
+        self.last_edit = Some(Instant::now());
+        self.reconfigure_drawn();
+    }
+
+    /// Save any edits that have been left alone for `CONFIG_SAVE_DELAY`.
+    fn save_edited_config(&mut self, ctx: &egui::Context) {
+        let Some(last_edit) = self.last_edit else {
+            return;
+        };
+        let waited = last_edit.elapsed();
+        if waited >= CONFIG_SAVE_DELAY {
+            self.save_config();
+        } else {
+            ctx.request_repaint_after(CONFIG_SAVE_DELAY - waited);
+        }
     }
 }
 
@@ -260,27 +452,29 @@ impl eframe::App for MainApp {
         .into();
         ctx.set_style(style);
 
-        let mut visuals = egui::Visuals::default();
-        visuals.panel_fill = self.config.home_config.bg_colour;
-        visuals.override_text_color = Some(self.config.home_config.font_colour);
-        ctx.set_visuals(visuals);
+        ctx.set_visuals(egui::Visuals {
+            panel_fill: self.config.home_config.bg_colour,
+            override_text_color: Some(self.config.home_config.font_colour),
+            ..Default::default()
+        });
 
-        ctx.request_repaint();
         self.render_top_panel(ctx);
         self.render_left_side_panel(ctx);
         self.render_footer(ctx);
         self.render_main(ctx);
 
-        self.manage_speed_overlay();
-        self.manage_lastlaptime_overlay();
+        self.save_edited_config(ctx);
+        self.manage_overlays();
+        self.show_hosted_overlays(ctx);
+        // Telemetry repaints as it arrives, but the overlay processes still
+        // need checking on without it
+        ctx.request_repaint_after(SUPERVISOR_POLL_INTERVAL);
     }
 
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
-        if let Some(ref mut child) = self.windows.speed {
-            let _ = child.kill();
-        }
-        if let Some(ref mut child) = self.windows.lastlaptime {
-            let _ = child.kill();
+        if self.last_edit.is_some() {
+            self.save_config();
         }
+        self.supervisor.stop_all();
     }
 }
@@ -0,0 +1,162 @@
+//! Picking up the changes the main app makes to the config file, so overlays
+//! running as their own programs don't need restarting to see them.
+
+use crate::util::WindowsConfig;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the config file is looked at for changes.
+pub const CONFIG_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Notices when the config file's contents change.
+pub struct ConfigWatcher {
+    path: Option<PathBuf>,
+    /// The file as it was last read
+    contents: Option<String>,
+    /// The file's modified time and length when it was last read, so it's
+    /// only read again once it's been written to
+    stamp: Option<(SystemTime, u64)>,
+    last_check: Instant,
+    interval: Duration,
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+impl ConfigWatcher {
+    /// Watch the config file the main app saves to.
+    pub fn new() -> Self {
+        Self::for_path(confy::get_configuration_file_path("iracing-overlays", None).ok())
+    }
+
+    pub fn for_path(path: Option<PathBuf>) -> Self {
+        let stamp = path.as_deref().and_then(stamp);
+        let contents = path.as_ref().and_then(|path| std::fs::read_to_string(path).ok());
+        Self {
+            path,
+            contents,
+            stamp,
+            last_check: Instant::now(),
+            interval: CONFIG_CHECK_INTERVAL,
+        }
+    }
+
+    /// The new config, if the file has changed since it was last read. The
+    /// file is looked at no more than once every `CONFIG_CHECK_INTERVAL`. A
+    /// file that doesn't parse, such as one still being written, is tried
+    /// again next time.
+    pub fn reload(&mut self) -> Option<WindowsConfig> {
+        if self.last_check.elapsed() < self.interval {
+            return None;
+        }
+        self.last_check = Instant::now();
+        let path = self.path.as_deref()?;
+        let stamp = stamp(path);
+        if stamp.is_some() && stamp == self.stamp {
+            return None;
+        }
+        let contents = std::fs::read_to_string(path).ok()?;
+        // Saving the same settings again changes the stamp but not the contents
+        if self.contents.as_ref() == Some(&contents) {
+            self.stamp = stamp;
+            return None;
+        }
+        let config = toml::from_str(&contents).ok()?;
+        self.contents = Some(contents);
+        self.stamp = stamp;
+        Some(config)
+    }
+}
+
+impl Default for ConfigWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("config-watch-{}-{}.toml", std::process::id(), name))
+    }
+
+    /// A watcher that looks at the file every time it's asked.
+    fn eager_watcher(path: &Path) -> ConfigWatcher {
+        let mut watcher = ConfigWatcher::for_path(Some(path.to_path_buf()));
+        watcher.interval = Duration::ZERO;
+        watcher
+    }
+
+    #[test]
+    fn picks_up_changes() {
+        let path = config_file("changes");
+        let mut config = WindowsConfig::default();
+        confy::store_path(&path, &config).unwrap();
+        let mut watcher = eager_watcher(&path);
+        assert!(watcher.reload().is_none());
+
+        config.speed_config.font_size = 30.;
+        confy::store_path(&path, &config).unwrap();
+        let reloaded = watcher.reload().unwrap();
+        assert_eq!(reloaded.speed_config.font_size, 30.);
+        // Only once per change
+        assert!(watcher.reload().is_none());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn waits_out_a_half_written_file() {
+        let path = config_file("half-written");
+        let mut config = WindowsConfig::default();
+        confy::store_path(&path, &config).unwrap();
+        let mut watcher = eager_watcher(&path);
+
+        std::fs::write(&path, "[speed_config\nfont_si").unwrap();
+        assert!(watcher.reload().is_none());
+
+        config.fuel_config.margin_laps = 2.;
+        confy::store_path(&path, &config).unwrap();
+        assert_eq!(watcher.reload().unwrap().fuel_config.margin_laps, 2.);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn checks_at_most_once_an_interval() {
+        let path = config_file("interval");
+        let mut config = WindowsConfig::default();
+        confy::store_path(&path, &config).unwrap();
+        let mut watcher = ConfigWatcher::for_path(Some(path.clone()));
+        watcher.interval = Duration::from_millis(200);
+
+        config.speed_config.font_size = 30.;
+        confy::store_path(&path, &config).unwrap();
+        assert!(watcher.reload().is_none());
+        std::thread::sleep(Duration::from_millis(250));
+        assert_eq!(watcher.reload().unwrap().speed_config.font_size, 30.);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn resaving_the_same_settings_is_not_a_change() {
+        let path = config_file("resave");
+        let config = WindowsConfig::default();
+        confy::store_path(&path, &config).unwrap();
+        let mut watcher = eager_watcher(&path);
+        std::thread::sleep(Duration::from_millis(10));
+        confy::store_path(&path, &config).unwrap();
+        assert!(watcher.reload().is_none());
+        assert!(watcher.reload().is_none());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn missing_file_never_changes() {
+        let mut watcher = eager_watcher(&config_file("missing"));
+        assert!(watcher.reload().is_none());
+        assert!(ConfigWatcher::for_path(None).reload().is_none());
+    }
+}
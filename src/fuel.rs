@@ -0,0 +1,7 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
+
+use iracing_overlay::widgets::{self, fuel::FuelWidget};
+
+fn main() -> Result<(), eframe::Error> {
+    widgets::run_standalone::<FuelWidget>()
+}
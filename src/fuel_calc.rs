@@ -0,0 +1,294 @@
+use crate::session::{DriverInfo, Session};
+use crate::session_flags::SessionFlags;
+use crate::telemetry::{IRacingTelemetry, Variable, VariableType};
+
+/// The telemetry needed to track fuel use.
+pub const VARIABLES: &[Variable] = &[
+    Variable::new("SessionNum", VariableType::Int),
+    Variable::new("SessionTime", VariableType::Double),
+    Variable::new("SessionTimeRemain", VariableType::Double),
+    Variable::new("SessionFlags", VariableType::Bits),
+    Variable::new("Lap", VariableType::Int),
+    Variable::new("LapCompleted", VariableType::Int),
+    Variable::new("LapDistPct", VariableType::Float),
+    Variable::new("FuelLevel", VariableType::Float),
+    Variable::new("OnPitRoad", VariableType::Bool),
+];
+
+/// The parts of a telemetry sample the fuel calculation uses.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FuelSample {
+    pub lap: i32,
+    pub session_time: f64,
+    pub fuel_level: f32,
+    pub on_pit_road: bool,
+    pub yellow: bool,
+}
+
+impl FuelSample {
+    pub fn from_telemetry(telemetry: &IRacingTelemetry) -> Option<Self> {
+        Some(Self {
+            lap: telemetry.int("Lap")?,
+            session_time: telemetry.double("SessionTime")?,
+            fuel_level: telemetry.float("FuelLevel")?,
+            on_pit_road: telemetry.bool("OnPitRoad").unwrap_or_default(),
+            yellow: SessionFlags::from_telemetry(telemetry).unwrap_or_default().yellow(),
+        })
+    }
+}
+
+struct LapStart {
+    lap: i32,
+    session_time: f64,
+    fuel_level: f32,
+    /// Set when part of the lap was in the pit lane or under yellow, or not seen at all.
+    ignore: bool,
+}
+
+/// A completed lap that counts towards the averages.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FuelLap {
+    pub fuel_used: f32,
+    pub lap_time: f32,
+}
+
+/// Tracks fuel used per lap from the fuel level at each lap crossing.
+#[derive(Default)]
+pub struct FuelCalculator {
+    current: Option<LapStart>,
+    laps: Vec<FuelLap>,
+}
+
+impl FuelCalculator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, sample: FuelSample) {
+        let Some(current) = &mut self.current else {
+            // We've joined part way through a lap, so can't use it
+            self.current = Some(LapStart {
+                lap: sample.lap,
+                session_time: sample.session_time,
+                fuel_level: sample.fuel_level,
+                ignore: true,
+            });
+            return;
+        };
+        current.ignore |= sample.on_pit_road || sample.yellow;
+        if sample.lap == current.lap {
+            return;
+        }
+        let fuel_used = current.fuel_level - sample.fuel_level;
+        // Skipping laps or going backwards means a reset or a new session, and
+        // a fuel level that went up means a refuel
+        let next_lap = sample.lap == current.lap + 1;
+        if next_lap && !current.ignore && fuel_used > 0. {
+            self.laps.push(FuelLap {
+                fuel_used,
+                lap_time: (sample.session_time - current.session_time) as f32,
+            });
+        }
+        self.current = Some(LapStart {
+            lap: sample.lap,
+            session_time: sample.session_time,
+            fuel_level: sample.fuel_level,
+            ignore: !next_lap || sample.on_pit_road || sample.yellow,
+        });
+    }
+
+    pub fn laps(&self) -> &[FuelLap] {
+        &self.laps
+    }
+
+    pub fn last(&self) -> Option<f32> {
+        self.laps.last().map(|lap| lap.fuel_used)
+    }
+
+    pub fn average(&self) -> Option<f32> {
+        if self.laps.is_empty() {
+            return None;
+        }
+        Some(self.laps.iter().map(|lap| lap.fuel_used).sum::<f32>() / self.laps.len() as f32)
+    }
+
+    pub fn max(&self) -> Option<f32> {
+        self.laps.iter().map(|lap| lap.fuel_used).reduce(f32::max)
+    }
+
+    pub fn average_lap_time(&self) -> Option<f32> {
+        if self.laps.is_empty() {
+            return None;
+        }
+        Some(self.laps.iter().map(|lap| lap.lap_time).sum::<f32>() / self.laps.len() as f32)
+    }
+}
+
+/// Laps still to drive until the chequered flag, including what's left of the
+/// current one. Whichever of the lap and time limits comes first ends the race,
+/// and a time-limited race runs on to finish the lap the clock runs out on.
+pub fn laps_to_finish(
+    session: &Session,
+    laps_completed: i32,
+    lap_dist_pct: f32,
+    time_remaining: f64,
+    lap_time: Option<f32>,
+) -> Option<f32> {
+    let by_laps = session
+        .laps()
+        .map(|laps| (laps as f32 - laps_completed as f32 - lap_dist_pct).max(0.));
+    let by_time = session.time_limit().zip(lap_time).map(|(_, lap_time)| {
+        let laps = time_remaining.max(0.) as f32 / lap_time + lap_dist_pct;
+        laps.ceil() - lap_dist_pct
+    });
+    match (by_laps, by_time) {
+        (Some(by_laps), Some(by_time)) => Some(by_laps.min(by_time)),
+        (by_laps, by_time) => by_laps.or(by_time),
+    }
+}
+
+/// What the overlay shows. Fuel is in litres, and anything that can't be worked out yet is `None`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FuelEstimate {
+    pub average: Option<f32>,
+    pub last: Option<f32>,
+    pub max: Option<f32>,
+    /// Laps the fuel in the tank will last at the average usage.
+    pub laps_remaining: Option<f32>,
+    /// Fuel needed to finish the race, including the safety margin.
+    pub to_finish: Option<f32>,
+    /// Fuel to add at the next stop, limited by what fits in the tank.
+    pub to_add: Option<f32>,
+}
+
+impl FuelEstimate {
+    pub fn new(
+        calculator: &FuelCalculator,
+        telemetry: &IRacingTelemetry,
+        session: Option<&Session>,
+        driver_info: Option<&DriverInfo>,
+        margin_laps: f32,
+    ) -> Self {
+        let average = calculator.average();
+        let fuel_level = telemetry.float("FuelLevel");
+        let laps_remaining = average
+            .zip(fuel_level)
+            .filter(|(average, _)| *average > 0.)
+            .map(|(average, fuel_level)| fuel_level / average);
+        let laps_to_finish = session.and_then(|session| {
+            laps_to_finish(
+                session,
+                telemetry.int("LapCompleted")?.max(0),
+                telemetry.float("LapDistPct")?.max(0.),
+                telemetry.double("SessionTimeRemain")?,
+                calculator.average_lap_time(),
+            )
+        });
+        let to_finish = average
+            .zip(laps_to_finish)
+            .map(|(average, laps)| (laps + margin_laps) * average);
+        let to_add = to_finish.zip(fuel_level).map(|(to_finish, fuel_level)| {
+            let needed = (to_finish - fuel_level).max(0.);
+            match driver_info {
+                Some(info) if info.driver_car_fuel_max_ltr > 0. => {
+                    // Cars can be restricted to a fraction of their tank
+                    let max_pct = if info.driver_car_max_fuel_pct > 0. { info.driver_car_max_fuel_pct } else { 1. };
+                    needed.min((info.driver_car_fuel_max_ltr * max_pct - fuel_level).max(0.))
+                }
+                _ => needed,
+            }
+        });
+        Self {
+            average,
+            last: calculator.last(),
+            max: calculator.max(),
+            laps_remaining,
+            to_finish,
+            to_add,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(lap: i32, fuel_level: f32) -> FuelSample {
+        FuelSample {
+            lap,
+            session_time: lap as f64 * 90.,
+            fuel_level,
+            on_pit_road: false,
+            yellow: false,
+        }
+    }
+
+    fn session(laps: &str, time: &str) -> Session {
+        Session {
+            session_laps: laps.to_string(),
+            session_time: time.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn averages_complete_laps_only() {
+        let mut calculator = FuelCalculator::new();
+        for (lap, fuel_level) in [(1, 50.), (2, 47.), (3, 44.5), (4, 41.5)] {
+            calculator.update(sample(lap, fuel_level));
+        }
+        // The first lap was joined part way through
+        assert_eq!(calculator.laps().len(), 2);
+        assert_eq!(calculator.last(), Some(3.));
+        assert_eq!(calculator.max(), Some(3.));
+        assert_eq!(calculator.average(), Some(2.75));
+        assert_eq!(calculator.average_lap_time(), Some(90.));
+    }
+
+    #[test]
+    fn ignores_pit_and_yellow_laps() {
+        let mut calculator = FuelCalculator::new();
+        calculator.update(sample(1, 50.));
+        calculator.update(sample(2, 47.));
+        calculator.update(FuelSample { yellow: true, ..sample(2, 46.) });
+        calculator.update(sample(3, 45.));
+        calculator.update(FuelSample { on_pit_road: true, ..sample(3, 44.) });
+        // Refuelled in the pits
+        calculator.update(sample(4, 80.));
+        calculator.update(sample(5, 77.));
+        let used: Vec<f32> = calculator.laps().iter().map(|lap| lap.fuel_used).collect();
+        assert_eq!(used, vec![3.]);
+    }
+
+    #[test]
+    fn ignores_refuels_and_resets() {
+        let mut calculator = FuelCalculator::new();
+        calculator.update(sample(1, 50.));
+        calculator.update(sample(2, 47.));
+        calculator.update(sample(3, 60.));
+        calculator.update(sample(1, 60.));
+        calculator.update(sample(2, 57.));
+        assert!(calculator.laps().is_empty());
+    }
+
+    #[test]
+    fn laps_to_finish_a_lap_limited_race() {
+        let laps = laps_to_finish(&session("20", "unlimited"), 15, 0.25, 600., None);
+        assert_eq!(laps, Some(4.75));
+    }
+
+    #[test]
+    fn laps_to_finish_a_time_limited_race() {
+        // 200 seconds at 90 per lap ends during the third lap from here, which is then completed
+        let laps = laps_to_finish(&session("unlimited", "1800.0000 sec"), 10, 0.5, 200., Some(90.));
+        assert_eq!(laps, Some(2.5));
+        // Without a lap time there's no way of knowing
+        assert_eq!(laps_to_finish(&session("unlimited", "1800.0000 sec"), 10, 0.5, 200., None), None);
+    }
+
+    #[test]
+    fn laps_to_finish_takes_the_first_limit() {
+        let laps = laps_to_finish(&session("12", "3600.0000 sec"), 10, 0., 1000., Some(90.));
+        assert_eq!(laps, Some(2.));
+    }
+}
@@ -0,0 +1,233 @@
+use crate::session::SessionDetails;
+use crate::telemetry::{IRacingTelemetry, Variable, VariableType};
+
+/// The telemetry needed to work out gaps between cars on track.
+pub const VARIABLES: &[Variable] = &[
+    Variable::new("PlayerCarIdx", VariableType::Int),
+    Variable::new("CarIdxLap", VariableType::IntArray),
+    Variable::new("CarIdxLapDistPct", VariableType::FloatArray),
+    Variable::new("CarIdxEstTime", VariableType::FloatArray),
+    Variable::new("CarIdxOnPitRoad", VariableType::BoolArray),
+];
+
+/// Where a car is on track, from the `CarIdx*` telemetry arrays.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CarPosition {
+    pub car_idx: usize,
+    pub lap: i32,
+    pub lap_dist_pct: f32,
+    pub est_time: f32,
+    pub on_pit_road: bool,
+}
+
+impl CarPosition {
+    /// Read every car that's currently in the world from the telemetry.
+    pub fn all(telemetry: &IRacingTelemetry) -> Vec<CarPosition> {
+        let (Some(laps), Some(pcts)) = (
+            telemetry.int_array("CarIdxLap"),
+            telemetry.float_array("CarIdxLapDistPct"),
+        ) else {
+            return Vec::new();
+        };
+        let est_times = telemetry.float_array("CarIdxEstTime").unwrap_or_default();
+        let on_pit_road = telemetry.bool_array("CarIdxOnPitRoad").unwrap_or_default();
+        pcts.iter()
+            .enumerate()
+            // Cars not in the world have a negative distance
+            .filter(|(_, pct)| **pct >= 0.)
+            .map(|(car_idx, pct)| CarPosition {
+                car_idx,
+                lap: laps.get(car_idx).copied().unwrap_or_default(),
+                lap_dist_pct: *pct,
+                est_time: est_times.get(car_idx).copied().unwrap_or_default(),
+                on_pit_road: on_pit_road.get(car_idx).copied().unwrap_or_default(),
+            })
+            .collect()
+    }
+}
+
+/// How far a car is ahead of or behind the player on track.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gap {
+    pub car_idx: usize,
+    /// Seconds, positive when the car is ahead on track.
+    pub seconds: f32,
+    /// Whole laps the car is ahead of (positive) or behind (negative) the player in the race.
+    pub laps: i32,
+    pub on_pit_road: bool,
+}
+
+/// Wrap a fraction of a lap into the half lap either side of zero.
+fn wrap_half_lap(delta: f32) -> f32 {
+    delta - delta.round()
+}
+
+/// Work out the gap from `player` to every other car, using each car's estimated
+/// time around the lap where iRacing provides it, and its distance otherwise.
+pub fn gaps(player: &CarPosition, cars: &[CarPosition], lap_time: f32) -> Vec<Gap> {
+    cars.iter()
+        .filter(|car| car.car_idx != player.car_idx)
+        .map(|car| {
+            let on_track = wrap_half_lap(car.lap_dist_pct - player.lap_dist_pct);
+            let seconds = if car.est_time > 0. && player.est_time > 0. && lap_time > 0. {
+                let seconds = car.est_time - player.est_time;
+                seconds - (seconds / lap_time).round() * lap_time
+            } else {
+                on_track * lap_time
+            };
+            let race = (car.lap as f32 + car.lap_dist_pct) - (player.lap as f32 + player.lap_dist_pct);
+            Gap {
+                car_idx: car.car_idx,
+                seconds,
+                laps: (race - on_track).round() as i32,
+                on_pit_road: car.on_pit_road,
+            }
+        })
+        .collect()
+}
+
+/// The `count` closest cars ahead of and behind the player, ordered from
+/// furthest ahead to furthest behind.
+pub fn closest(gaps: &[Gap], count: usize) -> (Vec<Gap>, Vec<Gap>) {
+    let mut ahead: Vec<Gap> = gaps.iter().filter(|gap| gap.seconds >= 0.).copied().collect();
+    let mut behind: Vec<Gap> = gaps.iter().filter(|gap| gap.seconds < 0.).copied().collect();
+    ahead.sort_by(|a, b| a.seconds.total_cmp(&b.seconds));
+    behind.sort_by(|a, b| b.seconds.total_cmp(&a.seconds));
+    ahead.truncate(count);
+    behind.truncate(count);
+    ahead.reverse();
+    (ahead, behind)
+}
+
+/// iRacing's estimate of a lap for `car_idx`'s class, or for the player's car if there isn't one.
+pub fn est_lap_time(session: &SessionDetails, car_idx: usize) -> f32 {
+    session
+        .driver(car_idx)
+        .map(|driver| driver.car_class_est_lap_time)
+        .filter(|lap_time| *lap_time > 0.)
+        .unwrap_or(session.driver_info.driver_car_est_lap_time)
+}
+
+/// The gaps for the relative overlay, leaving out the pace car and spectators.
+pub fn relative(telemetry: &IRacingTelemetry, session: &SessionDetails, count: usize) -> Option<(Vec<Gap>, Vec<Gap>)> {
+    let player_idx = usize::try_from(telemetry.int("PlayerCarIdx")?).ok()?;
+    let cars: Vec<CarPosition> = CarPosition::all(telemetry)
+        .into_iter()
+        .filter(|car| {
+            car.car_idx == player_idx || session.driver(car.car_idx).is_some_and(|d| d.is_racing())
+        })
+        .collect();
+    let player = cars.iter().find(|car| car.car_idx == player_idx)?;
+    Some(closest(&gaps(player, &cars, est_lap_time(session, player_idx)), count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::{IRacingLogging, ScriptedSource, TelemetryValue};
+    use crate::test_support::car;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    const SESSION: &str = "---
+DriverInfo:
+ DriverCarIdx: 0
+ DriverCarEstLapTime: 100.0000
+ Drivers:
+ - CarIdx: 0
+   UserName: Player
+ - CarIdx: 1
+   UserName: Ahead
+ - CarIdx: 2
+   UserName: Behind
+ - CarIdx: 3
+   UserName: Pace Car
+   CarIsPaceCar: 1
+...
+";
+
+    fn frame(laps: Vec<i32>, pcts: Vec<f32>) -> HashMap<&'static str, TelemetryValue> {
+        let est_times = pcts.iter().map(|pct| pct * 100.).collect();
+        HashMap::from([
+            ("PlayerCarIdx", TelemetryValue::Int(0)),
+            ("CarIdxLap", TelemetryValue::IntVec(laps)),
+            ("CarIdxLapDistPct", TelemetryValue::FloatVec(pcts)),
+            ("CarIdxEstTime", TelemetryValue::FloatVec(est_times)),
+            ("CarIdxOnPitRoad", TelemetryValue::BoolVec(vec![false; 4])),
+        ])
+    }
+
+    #[test]
+    fn gap_to_car_on_the_same_lap() {
+        let gaps = gaps(&car(0, 3, 0.50), &[car(1, 3, 0.52), car(2, 3, 0.45)], 100.);
+        assert!((gaps[0].seconds - 2.).abs() < 1e-3);
+        assert!((gaps[1].seconds + 5.).abs() < 1e-3);
+        assert_eq!(gaps[0].laps, 0);
+        assert_eq!(gaps[1].laps, 0);
+    }
+
+    #[test]
+    fn gap_wraps_across_the_start_finish_line() {
+        // Just ahead across the line, and just behind before it
+        let gaps = gaps(&car(0, 3, 0.98), &[car(1, 4, 0.01), car(2, 2, 0.97)], 100.);
+        assert!((gaps[0].seconds - 3.).abs() < 1e-3);
+        assert_eq!(gaps[0].laps, 0);
+        assert!((gaps[1].seconds + 1.).abs() < 1e-3);
+        assert_eq!(gaps[1].laps, -1);
+    }
+
+    #[test]
+    fn lapping_and_lapped_cars() {
+        let gaps = gaps(&car(0, 5, 0.20), &[car(1, 6, 0.25), car(2, 4, 0.10)], 100.);
+        assert_eq!(gaps[0].laps, 1);
+        assert!((gaps[0].seconds - 5.).abs() < 1e-3);
+        assert_eq!(gaps[1].laps, -1);
+        assert!((gaps[1].seconds + 10.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn falls_back_to_distance_without_estimated_times() {
+        let mut ahead = car(1, 1, 0.30);
+        ahead.est_time = 0.;
+        let gaps = gaps(&car(0, 1, 0.25), &[ahead], 80.);
+        assert!((gaps[0].seconds - 4.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn closest_orders_cars_ahead_then_behind() {
+        let all = gaps(
+            &car(0, 1, 0.5),
+            &[car(1, 1, 0.6), car(2, 1, 0.55), car(3, 1, 0.4), car(4, 1, 0.45), car(5, 1, 0.9)],
+            100.,
+        );
+        let (ahead, behind) = closest(&all, 2);
+        let ahead: Vec<usize> = ahead.iter().map(|gap| gap.car_idx).collect();
+        let behind: Vec<usize> = behind.iter().map(|gap| gap.car_idx).collect();
+        assert_eq!(ahead, vec![1, 2]);
+        assert_eq!(behind, vec![4, 3]);
+    }
+
+    #[test]
+    fn relative_from_a_scripted_stream() {
+        let frames = vec![
+            frame(vec![2, 2, 1, 0], vec![0.10, 0.15, 0.95, 0.50]),
+            frame(vec![2, 2, 2, 0], vec![0.11, 0.16, 0.01, 0.50]),
+        ];
+        let source = ScriptedSource::new(frames).with_session_info(SESSION);
+        let mut logging = IRacingLogging::with_source(Box::new(source), VARIABLES);
+
+        assert!(logging.read_telemetry(Duration::ZERO));
+        let session = logging.session.clone().unwrap();
+        let (ahead, behind) = relative(&logging.telemetry, &session, 3).unwrap();
+        // The pace car is left out
+        assert_eq!(ahead.len(), 1);
+        assert_eq!(behind.len(), 1);
+        assert!((ahead[0].seconds - 5.).abs() < 1e-3);
+        assert!((behind[0].seconds + 15.).abs() < 1e-3);
+
+        assert!(logging.read_telemetry(Duration::ZERO));
+        let (_, behind) = relative(&logging.telemetry, &session, 3).unwrap();
+        assert!((behind[0].seconds + 10.).abs() < 1e-3);
+        assert_eq!(behind[0].laps, 0);
+    }
+}
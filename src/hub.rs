@@ -0,0 +1,444 @@
+use crate::telemetry::{
+    open_default_source, ConnectionState, IRacingLogging, Sampled, TelemetrySource,
+    TelemetryValue,
+};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Where the main app publishes telemetry for the overlays to pick up.
+pub const HUB_ADDRESS: &str = "127.0.0.1:47761";
+
+// Every frame is a little-endian u32 length, then the session info header,
+// then one tagged value per subscribed variable, in the order the client
+// asked for them. The session info itself is only sent when it changes.
+// A frame with a length of zero is a heartbeat, sent when there's no new
+// sample so that closed connections are still noticed, and so overlays know
+// the hub is still there while the sim isn't.
+const SESSION_NONE: u8 = 0;
+const SESSION_VERSION: u8 = 1;
+const SESSION_VERSION_AND_INFO: u8 = 2;
+
+const TAG_MISSING: u8 = 0;
+const TAG_CHAR: u8 = 1;
+const TAG_BOOL: u8 = 2;
+const TAG_INT: u8 = 3;
+const TAG_BITS: u8 = 4;
+const TAG_FLOAT: u8 = 5;
+const TAG_DOUBLE: u8 = 6;
+const TAG_INT_VEC: u8 = 7;
+const TAG_BITS_VEC: u8 = 8;
+const TAG_FLOAT_VEC: u8 = 9;
+const TAG_BOOL_VEC: u8 = 10;
+
+fn encode_value(value: Option<&TelemetryValue>, out: &mut Vec<u8>) {
+    match value {
+        None => out.push(TAG_MISSING),
+        Some(TelemetryValue::Char(c)) => out.extend([TAG_CHAR, *c]),
+        Some(TelemetryValue::Bool(b)) => out.extend([TAG_BOOL, *b as u8]),
+        Some(TelemetryValue::Int(i)) => {
+            out.push(TAG_INT);
+            out.extend(i.to_le_bytes());
+        }
+        Some(TelemetryValue::Bits(b)) => {
+            out.push(TAG_BITS);
+            out.extend(b.to_le_bytes());
+        }
+        Some(TelemetryValue::Float(f)) => {
+            out.push(TAG_FLOAT);
+            out.extend(f.to_le_bytes());
+        }
+        Some(TelemetryValue::Double(d)) => {
+            out.push(TAG_DOUBLE);
+            out.extend(d.to_le_bytes());
+        }
+        Some(TelemetryValue::IntVec(v)) => {
+            out.push(TAG_INT_VEC);
+            out.extend((v.len() as u32).to_le_bytes());
+            v.iter().for_each(|i| out.extend(i.to_le_bytes()));
+        }
+        Some(TelemetryValue::BitsVec(v)) => {
+            out.push(TAG_BITS_VEC);
+            out.extend((v.len() as u32).to_le_bytes());
+            v.iter().for_each(|b| out.extend(b.to_le_bytes()));
+        }
+        Some(TelemetryValue::FloatVec(v)) => {
+            out.push(TAG_FLOAT_VEC);
+            out.extend((v.len() as u32).to_le_bytes());
+            v.iter().for_each(|f| out.extend(f.to_le_bytes()));
+        }
+        Some(TelemetryValue::BoolVec(v)) => {
+            out.push(TAG_BOOL_VEC);
+            out.extend((v.len() as u32).to_le_bytes());
+            v.iter().for_each(|b| out.push(*b as u8));
+        }
+    }
+}
+
+fn take<const N: usize>(bytes: &mut &[u8]) -> Option<[u8; N]> {
+    let (head, rest) = bytes.split_first_chunk::<N>()?;
+    *bytes = rest;
+    Some(*head)
+}
+
+fn take_vec<T, const N: usize>(bytes: &mut &[u8], convert: fn([u8; N]) -> T) -> Option<Vec<T>> {
+    let count = u32::from_le_bytes(take(bytes)?) as usize;
+    (0..count).map(|_| take(bytes).map(convert)).collect()
+}
+
+/// Returns `Some(None)` for a variable the hub couldn't find, and `None` if the frame is malformed.
+fn decode_value(bytes: &mut &[u8]) -> Option<Option<TelemetryValue>> {
+    let [tag] = take(bytes)?;
+    let value = match tag {
+        TAG_MISSING => return Some(None),
+        TAG_CHAR => TelemetryValue::Char(take::<1>(bytes)?[0]),
+        TAG_BOOL => TelemetryValue::Bool(take::<1>(bytes)?[0] > 0),
+        TAG_INT => TelemetryValue::Int(i32::from_le_bytes(take(bytes)?)),
+        TAG_BITS => TelemetryValue::Bits(u32::from_le_bytes(take(bytes)?)),
+        TAG_FLOAT => TelemetryValue::Float(f32::from_le_bytes(take(bytes)?)),
+        TAG_DOUBLE => TelemetryValue::Double(f64::from_le_bytes(take(bytes)?)),
+        TAG_INT_VEC => TelemetryValue::IntVec(take_vec(bytes, i32::from_le_bytes)?),
+        TAG_BITS_VEC => TelemetryValue::BitsVec(take_vec(bytes, u32::from_le_bytes)?),
+        TAG_FLOAT_VEC => TelemetryValue::FloatVec(take_vec(bytes, f32::from_le_bytes)?),
+        TAG_BOOL_VEC => TelemetryValue::BoolVec(take_vec(bytes, |[b]: [u8; 1]| b > 0)?),
+        _ => return None,
+    };
+    Some(Some(value))
+}
+
+struct HubClient {
+    stream: TcpStream,
+    names: Vec<&'static str>,
+    session_info_version: Option<i32>,
+}
+
+/// Reads telemetry once in the main app and publishes it to every overlay.
+pub struct TelemetryHub {
+    address: SocketAddr,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TelemetryHub {
+    pub fn start(address: &str) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+        let running = Arc::new(AtomicBool::new(true));
+        let thread = {
+            let running = running.clone();
+            thread::spawn(move || Self::run(listener, running))
+        };
+        Ok(Self {
+            address,
+            running,
+            thread: Some(thread),
+        })
+    }
+
+    /// The address the hub is listening on.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    fn run(listener: TcpListener, running: Arc<AtomicBool>) {
+        let mut logging = IRacingLogging::with_connector(Box::new(open_default_source), &[]);
+        let subscribed = Self::accept(listener, running.clone());
+        let mut clients: Vec<HubClient> = Vec::new();
+        let mut frame = Vec::new();
+        while running.load(Ordering::Relaxed) {
+            clients.extend(subscribed.try_iter());
+            let Some(source) = logging.poll(Duration::from_millis(16)) else {
+                clients.retain_mut(|client| client.stream.write_all(&[0; 4]).is_ok());
+                if logging.state() == ConnectionState::Disconnected {
+                    // Don't spin while waiting to retry the connection
+                    thread::sleep(Duration::from_millis(16));
+                }
+                continue;
+            };
+            let session_info_version = source.session_info_version();
+            let mut session_info = None;
+            clients.retain_mut(|client| {
+                frame.clear();
+                frame.extend([0; 4]);
+                match session_info_version {
+                    None => frame.push(SESSION_NONE),
+                    Some(version) if client.session_info_version == Some(version) => {
+                        frame.push(SESSION_VERSION);
+                        frame.extend(version.to_le_bytes());
+                    }
+                    Some(version) => {
+                        let info = session_info
+                            .get_or_insert_with(|| source.session_info().unwrap_or_default());
+                        frame.push(SESSION_VERSION_AND_INFO);
+                        frame.extend(version.to_le_bytes());
+                        frame.extend((info.len() as u32).to_le_bytes());
+                        frame.extend(info.as_bytes());
+                        client.session_info_version = Some(version);
+                    }
+                }
+                for name in &client.names {
+                    encode_value(source.get(name).as_ref(), &mut frame);
+                }
+                let length = (frame.len() - 4) as u32;
+                frame[..4].copy_from_slice(&length.to_le_bytes());
+                client.stream.write_all(&frame).is_ok()
+            });
+        }
+    }
+
+    /// Accept new clients on their own thread, so a slow one never holds up sampling.
+    fn accept(listener: TcpListener, running: Arc<AtomicBool>) -> Receiver<HubClient> {
+        let (subscribed, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            while running.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let subscribed = subscribed.clone();
+                        thread::spawn(move || {
+                            if let Some(client) = Self::subscribe(stream) {
+                                let _ = subscribed.send(client);
+                            }
+                        });
+                    }
+                    Err(..) => thread::sleep(Duration::from_millis(16)),
+                }
+            }
+        });
+        receiver
+    }
+
+    /// Read the comma separated list of variables a new client wants.
+    fn subscribe(stream: TcpStream) -> Option<HubClient> {
+        stream.set_nonblocking(false).ok()?;
+        stream.set_read_timeout(Some(Duration::from_millis(500))).ok()?;
+        stream.set_write_timeout(Some(Duration::from_millis(50))).ok()?;
+        stream.set_nodelay(true).ok()?;
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line).ok()?;
+        let names = line
+            .trim()
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .map(intern)
+            .collect();
+        Some(HubClient {
+            stream,
+            names,
+            session_info_version: None,
+        })
+    }
+}
+
+impl Drop for TelemetryHub {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Telemetry sources are looked up by `&'static str`, so names read off the
+/// socket are leaked, once per distinct name overlays ever ask for.
+fn intern(name: &str) -> &'static str {
+    static NAMES: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+    let mut names = NAMES.get_or_init(Default::default).lock().unwrap();
+    if let Some(interned) = names.get(name) {
+        return interned;
+    }
+    let interned: &'static str = Box::leak(name.to_string().into_boxed_str());
+    names.insert(interned);
+    interned
+}
+
+/// Telemetry received from the main app's `TelemetryHub`.
+pub struct HubSource {
+    stream: TcpStream,
+    names: Vec<&'static str>,
+    buffer: Vec<u8>,
+    current: HashMap<&'static str, TelemetryValue>,
+    session_info_version: Option<i32>,
+    session_info: Option<String>,
+}
+
+impl HubSource {
+    pub fn connect(address: &str, names: &[&'static str]) -> Result<Self, Box<dyn Error>> {
+        let address: SocketAddr = address.parse()?;
+        let mut stream = TcpStream::connect_timeout(&address, Duration::from_millis(200))?;
+        stream.set_nodelay(true)?;
+        stream.write_all(format!("{}\n", names.join(",")).as_bytes())?;
+        Ok(Self {
+            stream,
+            names: names.to_vec(),
+            buffer: Vec::new(),
+            current: HashMap::new(),
+            session_info_version: None,
+            session_info: None,
+        })
+    }
+
+    /// Read the session info header from the front of a frame.
+    fn read_session_info(&mut self, bytes: &mut &[u8]) -> Result<(), Box<dyn Error>> {
+        let malformed = "Malformed telemetry frame";
+        let [flags] = take(bytes).ok_or(malformed)?;
+        if flags == SESSION_NONE {
+            self.session_info_version = None;
+            return Ok(());
+        }
+        self.session_info_version = Some(i32::from_le_bytes(take(bytes).ok_or(malformed)?));
+        if flags == SESSION_VERSION_AND_INFO {
+            let length = u32::from_le_bytes(take(bytes).ok_or(malformed)?) as usize;
+            let info = bytes.get(..length).ok_or(malformed)?;
+            self.session_info = Some(String::from_utf8(info.to_vec())?);
+            *bytes = &bytes[length..];
+        }
+        Ok(())
+    }
+
+    /// Decode the newest complete frame in the buffer, dropping the values from
+    /// any older ones.
+    fn take_latest_frame(&mut self) -> Result<bool, Box<dyn Error>> {
+        let buffer = std::mem::take(&mut self.buffer);
+        let mut latest = None;
+        let mut start = 0;
+        while let Some(header) = buffer.get(start..start + 4) {
+            let length = u32::from_le_bytes(header.try_into()?) as usize;
+            if length == 0 {
+                // A heartbeat, not a sample
+                start += 4;
+                continue;
+            }
+            let Some(mut bytes) = buffer.get(start + 4..start + 4 + length) else {
+                break;
+            };
+            // Session info is only sent once, so it can't be skipped with the rest
+            self.read_session_info(&mut bytes)?;
+            latest = Some(bytes);
+            start += 4 + length;
+        }
+        self.buffer = buffer[start..].to_vec();
+        let Some(mut bytes) = latest else {
+            return Ok(false);
+        };
+        let mut current = HashMap::with_capacity(self.names.len());
+        for name in &self.names {
+            if let Some(value) = decode_value(&mut bytes).ok_or("Malformed telemetry frame")? {
+                current.insert(*name, value);
+            }
+        }
+        self.current = current;
+        Ok(true)
+    }
+}
+
+impl TelemetrySource for HubSource {
+    fn sample(&mut self, timeout: Duration) -> Result<Sampled, Box<dyn Error>> {
+        let deadline = Instant::now() + timeout;
+        let mut chunk = [0; 4096];
+        // Anything from the hub, even a heartbeat, shows it's still there
+        let mut heard = false;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            // A zero timeout means block forever, so always wait at least a little
+            self.stream
+                .set_read_timeout(Some(remaining.max(Duration::from_millis(1))))?;
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err("Telemetry hub closed the connection".into()),
+                Ok(n) => {
+                    heard = true;
+                    self.buffer.extend_from_slice(&chunk[..n]);
+                }
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => (),
+                Err(e) => return Err(Box::new(e)),
+            }
+            if self.take_latest_frame()? {
+                return Ok(Sampled::New);
+            }
+            if Instant::now() >= deadline {
+                if heard {
+                    return Ok(Sampled::Idle);
+                }
+                return Err("Timed out waiting for telemetry from the hub".into());
+            }
+        }
+    }
+
+    fn get(&self, name: &'static str) -> Option<TelemetryValue> {
+        self.current.get(name).cloned()
+    }
+
+    fn session_info_version(&self) -> Option<i32> {
+        self.session_info_version
+    }
+
+    fn session_info(&self) -> Option<String> {
+        self.session_info.clone()
+    }
+}
+
+/// Connect to the main app's hub if it's running, otherwise read the sim directly.
+pub fn open_overlay_source(
+    names: &[&'static str],
+) -> Result<Box<dyn TelemetrySource>, Box<dyn Error>> {
+    match HubSource::connect(HUB_ADDRESS, names) {
+        Ok(source) => Ok(Box::new(source)),
+        Err(..) => open_default_source(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(contents: &[u8]) -> Vec<u8> {
+        let mut frame = (contents.len() as u32).to_le_bytes().to_vec();
+        frame.extend(contents);
+        frame
+    }
+
+    #[test]
+    fn heartbeats_are_not_samples() {
+        let hub = TelemetryHub::start("127.0.0.1:0").unwrap();
+        let mut source = HubSource::connect(&hub.address().to_string(), &["Speed"]).unwrap();
+        source.buffer = [0; 4].repeat(3);
+        assert!(!source.take_latest_frame().unwrap());
+        assert!(source.buffer.is_empty());
+
+        let mut sample = vec![SESSION_NONE];
+        encode_value(Some(&TelemetryValue::Float(42.)), &mut sample);
+        source.buffer = [frame(&[]), frame(&sample), frame(&[])].concat();
+        assert!(source.take_latest_frame().unwrap());
+        assert_eq!(source.get("Speed"), Some(TelemetryValue::Float(42.)));
+    }
+
+    #[test]
+    fn slow_subscriber_does_not_hold_up_others() {
+        let hub = TelemetryHub::start("127.0.0.1:0").unwrap();
+        // Connects but never says what it wants
+        let _silent = TcpStream::connect(hub.address()).unwrap();
+        let mut source = HubSource::connect(&hub.address().to_string(), &["Speed"]).unwrap();
+        // Well within the silent client's subscription timeout
+        source.sample(Duration::from_millis(300)).unwrap();
+        assert!(source.get("Speed").is_some());
+    }
+
+    #[test]
+    fn heartbeats_keep_the_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let mut source = HubSource::connect(&address, &["Speed"]).unwrap();
+        let (mut hub, _) = listener.accept().unwrap();
+        assert!(source.sample(Duration::from_millis(20)).is_err());
+
+        hub.write_all(&[0; 4]).unwrap();
+        assert!(matches!(source.sample(Duration::from_millis(100)), Ok(Sampled::Idle)));
+        assert_eq!(source.get("Speed"), None);
+    }
+}
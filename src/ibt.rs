@@ -0,0 +1,396 @@
+use crate::telemetry::{Sampled, TelemetrySource, TelemetryValue};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// Byte sizes of the structures at the start of an .ibt file, as laid out by the iRacing SDK.
+const HEADER_LENGTH: usize = 112;
+const DISK_SUB_HEADER_LENGTH: usize = 32;
+const VAR_HEADER_LENGTH: usize = 144;
+const MAX_NAME_LENGTH: usize = 32;
+const MAX_DESCRIPTION_LENGTH: usize = 64;
+
+#[derive(Clone, Debug)]
+pub struct IbtVariable {
+    pub name: String,
+    pub description: String,
+    pub unit: String,
+    value_type: i32,
+    offset: usize,
+    count: usize,
+}
+
+/// A telemetry file written to disk by iRacing, loaded fully into memory.
+pub struct IbtFile {
+    pub tick_rate: u32,
+    pub session_info_version: i32,
+    pub session_info: String,
+    pub lap_count: i32,
+    variables: HashMap<String, IbtVariable>,
+    record_length: usize,
+    records: Vec<u8>,
+}
+
+/// The bytes at `at..at + length`, if the file is long enough.
+fn read_bytes(bytes: &[u8], at: usize, length: usize) -> Result<&[u8], Box<dyn Error>> {
+    let end = at.checked_add(length).ok_or("Unexpected end of telemetry file")?;
+    Ok(bytes.get(at..end).ok_or("Unexpected end of telemetry file")?)
+}
+
+fn read_i32(bytes: &[u8], at: usize) -> Result<i32, Box<dyn Error>> {
+    Ok(i32::from_le_bytes(read_bytes(bytes, at, 4)?.try_into()?))
+}
+
+/// A count, length or offset, which can't be negative in a valid file.
+fn read_usize(bytes: &[u8], at: usize) -> Result<usize, Box<dyn Error>> {
+    Ok(usize::try_from(read_i32(bytes, at)?).map_err(|_| "Negative size in telemetry file")?)
+}
+
+fn read_str(bytes: &[u8], at: usize, length: usize) -> Result<String, Box<dyn Error>> {
+    let raw = read_bytes(bytes, at, length)?;
+    let end = raw.iter().position(|&b| b == 0).unwrap_or(length);
+    // iRacing writes ISO-8859-1, which maps byte for byte onto the first 256 chars
+    Ok(raw[..end].iter().map(|&b| b as char).collect())
+}
+
+impl IbtFile {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if bytes.len() < HEADER_LENGTH + DISK_SUB_HEADER_LENGTH {
+            return Err("Not a valid iRacing telemetry file".into());
+        }
+        let tick_rate = read_i32(bytes, 8)?;
+        let session_info_version = read_i32(bytes, 12)?;
+        let session_info_length = read_usize(bytes, 16)?;
+        let session_info_offset = read_usize(bytes, 20)?;
+        let n_vars = read_usize(bytes, 24)?;
+        let var_header_offset = read_usize(bytes, 28)?;
+        let record_length = read_usize(bytes, 36)?;
+        // Files only ever use the first of the four variable buffers
+        let records_offset = read_usize(bytes, 52)?;
+        let lap_count = read_i32(bytes, HEADER_LENGTH + 24)?;
+        let record_count = read_usize(bytes, HEADER_LENGTH + 28)?;
+        if record_length == 0 || records_offset > bytes.len() {
+            return Err("Not a valid iRacing telemetry file".into());
+        }
+        // Checked before anything is sized by the count
+        n_vars
+            .checked_mul(VAR_HEADER_LENGTH)
+            .and_then(|length| var_header_offset.checked_add(length))
+            .filter(|&end| end <= bytes.len())
+            .ok_or("Unexpected end of telemetry file")?;
+
+        let mut variables = HashMap::with_capacity(n_vars);
+        for i in 0..n_vars {
+            let at = var_header_offset + i * VAR_HEADER_LENGTH;
+            let variable = IbtVariable {
+                value_type: read_i32(bytes, at)?,
+                offset: read_usize(bytes, at + 4)?,
+                count: read_i32(bytes, at + 8)?.max(1) as usize,
+                name: read_str(bytes, at + 16, MAX_NAME_LENGTH)?,
+                description: read_str(bytes, at + 16 + MAX_NAME_LENGTH, MAX_DESCRIPTION_LENGTH)?,
+                unit: read_str(
+                    bytes,
+                    at + 16 + MAX_NAME_LENGTH + MAX_DESCRIPTION_LENGTH,
+                    MAX_NAME_LENGTH,
+                )?,
+            };
+            variables.insert(variable.name.clone(), variable);
+        }
+
+        let session_info = read_str(bytes, session_info_offset, session_info_length)?;
+
+        // Trust the data over the record count, which isn't written if iRacing didn't close the file cleanly
+        let available = bytes.len().saturating_sub(records_offset) / record_length;
+        let record_count = if record_count == 0 {
+            available
+        } else {
+            record_count.min(available)
+        };
+        let records =
+            bytes[records_offset..records_offset + record_count * record_length].to_vec();
+
+        Ok(Self {
+            tick_rate: tick_rate.max(1) as u32,
+            session_info_version,
+            session_info,
+            lap_count,
+            variables,
+            record_length,
+            records,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len() / self.record_length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn variables(&self) -> impl Iterator<Item = &IbtVariable> {
+        self.variables.values()
+    }
+
+    /// Read a variable from the record at `index`.
+    pub fn get(&self, index: usize, name: &str) -> Option<TelemetryValue> {
+        let variable = self.variables.get(name)?;
+        let record_start = index.checked_mul(self.record_length)?;
+        let start = record_start.checked_add(variable.offset)?;
+        let record = self.records.get(start..record_start + self.record_length)?;
+        let word = |i: usize| -> Option<[u8; 4]> { record.get(i * 4..i * 4 + 4)?.try_into().ok() };
+        match (variable.value_type, variable.count) {
+            (0, _) => Some(TelemetryValue::Char(*record.first()?)),
+            (1, 1) => Some(TelemetryValue::Bool(*record.first()? > 0)),
+            (1, n) => Some(TelemetryValue::BoolVec(
+                record.get(..n)?.iter().map(|&b| b > 0).collect(),
+            )),
+            (2, 1) => Some(TelemetryValue::Int(i32::from_le_bytes(word(0)?))),
+            (2, n) => Some(TelemetryValue::IntVec(
+                (0..n).map(|i| word(i).map(i32::from_le_bytes)).collect::<Option<_>>()?,
+            )),
+            (3, 1) => Some(TelemetryValue::Bits(u32::from_le_bytes(word(0)?))),
+            (3, n) => Some(TelemetryValue::BitsVec(
+                (0..n).map(|i| word(i).map(u32::from_le_bytes)).collect::<Option<_>>()?,
+            )),
+            (4, 1) => Some(TelemetryValue::Float(f32::from_le_bytes(word(0)?))),
+            (4, n) => Some(TelemetryValue::FloatVec(
+                (0..n).map(|i| word(i).map(f32::from_le_bytes)).collect::<Option<_>>()?,
+            )),
+            (5, _) => Some(TelemetryValue::Double(f64::from_le_bytes(
+                record.get(..8)?.try_into().ok()?,
+            ))),
+            _ => None,
+        }
+    }
+}
+
+/// How quickly an `IbtSource` plays back its file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlaybackRate {
+    /// Follow the file's tick rate, multiplied by the given factor.
+    RealTime(f32),
+    /// Move on one record every time a sample is requested, for tests.
+    Unpaced,
+}
+
+/// Replays an `IbtFile` as though it were live telemetry.
+pub struct IbtSource {
+    file: IbtFile,
+    rate: PlaybackRate,
+    started: Option<Instant>,
+    position: Option<usize>,
+}
+
+impl PlaybackRate {
+    /// Real time sped up or slowed down by `multiplier`, which has to be above 0
+    /// for playback to ever move on.
+    pub fn real_time(multiplier: f32) -> Result<Self, Box<dyn Error>> {
+        if multiplier > 0. && multiplier.is_finite() {
+            Ok(PlaybackRate::RealTime(multiplier))
+        } else {
+            Err(format!("Playback speed must be above 0, not {}", multiplier).into())
+        }
+    }
+}
+
+impl IbtSource {
+    pub fn new(file: IbtFile, rate: PlaybackRate) -> Self {
+        Self {
+            file,
+            rate,
+            started: None,
+            position: None,
+        }
+    }
+
+    pub fn open(path: impl AsRef<Path>, rate: PlaybackRate) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::new(IbtFile::open(path)?, rate))
+    }
+
+    pub fn file(&self) -> &IbtFile {
+        &self.file
+    }
+
+    pub fn position(&self) -> Option<usize> {
+        self.position
+    }
+
+    fn due_record(&self, started: Instant, multiplier: f32) -> usize {
+        (started.elapsed().as_secs_f32() * self.file.tick_rate as f32 * multiplier) as usize
+    }
+}
+
+impl TelemetrySource for IbtSource {
+    fn sample(&mut self, timeout: Duration) -> Result<Sampled, Box<dyn Error>> {
+        let next = match self.rate {
+            PlaybackRate::Unpaced => self.position.map_or(0, |position| position + 1),
+            PlaybackRate::RealTime(multiplier) => {
+                let started = *self.started.get_or_insert_with(Instant::now);
+                let deadline = Instant::now() + timeout;
+                let mut due = self.due_record(started, multiplier);
+                // Block like the live connection until a new record is due
+                while self.position.is_some_and(|position| due <= position) {
+                    if Instant::now() >= deadline {
+                        return Err("Timed out waiting for the next telemetry record".into());
+                    }
+                    std::thread::sleep(Duration::from_millis(1));
+                    due = self.due_record(started, multiplier);
+                }
+                due
+            }
+        };
+        if next >= self.file.len() {
+            return Err("End of telemetry file".into());
+        }
+        self.position = Some(next);
+        Ok(Sampled::New)
+    }
+
+    fn get(&self, name: &'static str) -> Option<TelemetryValue> {
+        self.file.get(self.position?, name)
+    }
+
+    fn session_info_version(&self) -> Option<i32> {
+        Some(self.file.session_info_version)
+    }
+
+    fn session_info(&self) -> Option<String> {
+        Some(self.file.session_info.clone())
+    }
+}
+
+/// An .ibt file of `records`, for tests. Variables are (name, type, offset),
+/// each holding one value.
+#[cfg(test)]
+pub(crate) fn write_ibt(variables: &[(&str, i32, usize)], session_info: &str, records: &[Vec<u8>]) -> Vec<u8> {
+    let record_length = records.first().map_or(4, Vec::len);
+    let var_header_offset = HEADER_LENGTH + DISK_SUB_HEADER_LENGTH;
+    let session_info_offset = var_header_offset + variables.len() * VAR_HEADER_LENGTH;
+    let records_offset = session_info_offset + session_info.len();
+
+    let mut bytes = vec![0u8; records_offset];
+    let mut put = |at: usize, value: &[u8]| bytes[at..at + value.len()].copy_from_slice(value);
+    put(8, &60i32.to_le_bytes());
+    put(12, &1i32.to_le_bytes());
+    put(16, &(session_info.len() as i32).to_le_bytes());
+    put(20, &(session_info_offset as i32).to_le_bytes());
+    put(24, &(variables.len() as i32).to_le_bytes());
+    put(28, &(var_header_offset as i32).to_le_bytes());
+    put(36, &(record_length as i32).to_le_bytes());
+    put(52, &(records_offset as i32).to_le_bytes());
+    put(HEADER_LENGTH + 28, &(records.len() as i32).to_le_bytes());
+    for (i, (name, value_type, offset)) in variables.iter().enumerate() {
+        let at = var_header_offset + i * VAR_HEADER_LENGTH;
+        put(at, &value_type.to_le_bytes());
+        put(at + 4, &(*offset as i32).to_le_bytes());
+        put(at + 8, &1i32.to_le_bytes());
+        put(at + 16, name.as_bytes());
+    }
+    put(session_info_offset, session_info.as_bytes());
+    for record in records {
+        bytes.extend_from_slice(record);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SESSION: &str = "---\nWeekendInfo:\n TrackID: 42\n...\n";
+    const VARIABLES: &[(&str, i32, usize)] = &[("Speed", 4, 0), ("Gear", 2, 4), ("OnPitRoad", 1, 8)];
+
+    fn record(speed: f32, gear: i32, on_pit_road: bool) -> Vec<u8> {
+        let mut record = Vec::new();
+        record.extend(speed.to_le_bytes());
+        record.extend(gear.to_le_bytes());
+        record.extend([on_pit_road as u8, 0, 0, 0]);
+        record
+    }
+
+    fn good_file() -> Vec<u8> {
+        write_ibt(VARIABLES, SESSION, &[record(10., 1, true), record(20., 2, false), record(30., 3, false)])
+    }
+
+    fn with_i32(mut bytes: Vec<u8>, at: usize, value: i32) -> Vec<u8> {
+        bytes[at..at + 4].copy_from_slice(&value.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn reads_a_good_file() {
+        let file = IbtFile::from_bytes(&good_file()).unwrap();
+        assert_eq!(file.len(), 3);
+        assert_eq!(file.tick_rate, 60);
+        assert_eq!(file.session_info, SESSION);
+        assert_eq!(file.variables().count(), 3);
+        assert_eq!(file.get(1, "Speed"), Some(TelemetryValue::Float(20.)));
+        assert_eq!(file.get(2, "Gear"), Some(TelemetryValue::Int(3)));
+        assert_eq!(file.get(0, "OnPitRoad"), Some(TelemetryValue::Bool(true)));
+        assert_eq!(file.get(3, "Speed"), None);
+        assert_eq!(file.get(0, "RPM"), None);
+    }
+
+    #[test]
+    fn unwritten_record_count_uses_the_data() {
+        let bytes = with_i32(good_file(), HEADER_LENGTH + 28, 0);
+        assert_eq!(IbtFile::from_bytes(&bytes).unwrap().len(), 3);
+        // A count past the end of the data is cut short
+        let bytes = with_i32(good_file(), HEADER_LENGTH + 28, 1000);
+        assert_eq!(IbtFile::from_bytes(&bytes).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn corrupt_headers_are_errors() {
+        let corrupt = [
+            ("negative variable count", with_i32(good_file(), 24, -1)),
+            ("huge variable count", with_i32(good_file(), 24, i32::MAX)),
+            ("negative variable offset", with_i32(good_file(), 28, -144)),
+            ("variables past the end", with_i32(good_file(), 28, i32::MAX)),
+            ("negative session info length", with_i32(good_file(), 16, -1)),
+            ("session info past the end", with_i32(good_file(), 20, i32::MAX - 2)),
+            ("negative record length", with_i32(good_file(), 36, -4)),
+            ("zero record length", with_i32(good_file(), 36, 0)),
+            ("negative records offset", with_i32(good_file(), 52, -1)),
+            ("negative record count", with_i32(good_file(), HEADER_LENGTH + 28, -1)),
+            ("truncated header", good_file()[..HEADER_LENGTH].to_vec()),
+        ];
+        for (what, bytes) in corrupt {
+            assert!(IbtFile::from_bytes(&bytes).is_err(), "{}", what);
+        }
+    }
+
+    #[test]
+    fn bad_variable_offset_reads_nothing() {
+        let bytes = with_i32(good_file(), HEADER_LENGTH + DISK_SUB_HEADER_LENGTH + 4, i32::MAX);
+        let file = IbtFile::from_bytes(&bytes).unwrap();
+        let speed = file.variables().find(|variable| variable.offset == i32::MAX as usize).unwrap();
+        assert_eq!(file.get(0, &speed.name.clone()), None);
+    }
+
+    #[test]
+    fn unpaced_playback_steps_through_every_record() {
+        let mut source = IbtSource::new(IbtFile::from_bytes(&good_file()).unwrap(), PlaybackRate::Unpaced);
+        let mut speeds = Vec::new();
+        while source.sample(Duration::ZERO).is_ok() {
+            speeds.push(source.get("Speed"));
+        }
+        let expected: Vec<_> = [10., 20., 30.].map(|speed| Some(TelemetryValue::Float(speed))).into();
+        assert_eq!(speeds, expected);
+    }
+
+    #[test]
+    fn playback_speed_has_to_be_positive() {
+        assert_eq!(PlaybackRate::real_time(2.).unwrap(), PlaybackRate::RealTime(2.));
+        assert!(PlaybackRate::real_time(0.).is_err());
+        assert!(PlaybackRate::real_time(-1.).is_err());
+        assert!(PlaybackRate::real_time(f32::NAN).is_err());
+    }
+}
@@ -0,0 +1,363 @@
+use crate::telemetry::{IRacingTelemetry, Variable, VariableType};
+use crate::trace::RingBuffer;
+use crate::util::DeltaReference;
+
+/// The telemetry needed for every delta reference, and for working out our own.
+pub const VARIABLES: &[Variable] = &[
+    Variable::new("Lap", VariableType::Int),
+    Variable::new("LapDistPct", VariableType::Float),
+    Variable::new("LapCurrentLapTime", VariableType::Float),
+    Variable::new("OnPitRoad", VariableType::Bool),
+    Variable::new("LapDeltaToBestLap", VariableType::Float),
+    Variable::new("LapDeltaToBestLap_OK", VariableType::Bool),
+    Variable::new("LapDeltaToSessionBestLap", VariableType::Float),
+    Variable::new("LapDeltaToSessionBestLap_OK", VariableType::Bool),
+    Variable::new("LapDeltaToOptimalLap", VariableType::Float),
+    Variable::new("LapDeltaToOptimalLap_OK", VariableType::Bool),
+];
+
+/// How much of the lap has to have been seen for it to count as a best lap.
+const LAP_COVERAGE: f32 = 0.02;
+
+/// How far `LapDistPct` can step backwards, e.g. from jitter when crawling,
+/// before the lap is treated as reset. Half a percent is a few tens of metres.
+const BACKWARD_TOLERANCE: f32 = 0.005;
+
+/// Change in the delta, in seconds, before it counts as gaining or losing time.
+const TREND_THRESHOLD: f32 = 0.01;
+
+/// Half a second of deltas at the sim's rate.
+const TREND_SAMPLES: usize = 30;
+
+/// The sim's delta to `reference`, if it's valid right now.
+pub fn sim_delta(telemetry: &IRacingTelemetry, reference: &DeltaReference) -> Option<f32> {
+    let (delta, ok) = match reference {
+        DeltaReference::BestLap => ("LapDeltaToBestLap", "LapDeltaToBestLap_OK"),
+        DeltaReference::SessionBestLap => ("LapDeltaToSessionBestLap", "LapDeltaToSessionBestLap_OK"),
+        DeltaReference::OptimalLap => ("LapDeltaToOptimalLap", "LapDeltaToOptimalLap_OK"),
+    };
+    telemetry
+        .float(delta)
+        .filter(|_| telemetry.bool(ok).unwrap_or_default())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LapSample {
+    pub lap: i32,
+    pub lap_dist_pct: f32,
+    /// Seconds since the start of the lap.
+    pub lap_time: f32,
+    pub on_pit_road: bool,
+}
+
+impl LapSample {
+    pub fn from_telemetry(telemetry: &IRacingTelemetry) -> Option<Self> {
+        Some(Self {
+            lap: telemetry.int("Lap")?,
+            lap_dist_pct: telemetry.float("LapDistPct")?,
+            lap_time: telemetry.float("LapCurrentLapTime")?,
+            on_pit_road: telemetry.bool("OnPitRoad").unwrap_or_default(),
+        })
+    }
+}
+
+/// A delta to our own best lap, worked out by comparing the time taken to
+/// reach each point around the lap.
+#[derive(Default)]
+pub struct InternalDelta {
+    lap: Option<i32>,
+    /// (distance, time) pairs, in order of distance
+    current: Vec<(f32, f32)>,
+    valid: bool,
+    best: Option<Vec<(f32, f32)>>,
+}
+
+impl InternalDelta {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn best_lap_time(&self) -> Option<f32> {
+        self.best.as_ref()?.last().map(|(_, time)| *time)
+    }
+
+    pub fn update(&mut self, sample: LapSample) {
+        if self.lap != Some(sample.lap) {
+            let complete = self.lap == Some(sample.lap - 1)
+                && self.current.first().is_some_and(|(pct, _)| *pct < LAP_COVERAGE)
+                && self.current.last().is_some_and(|(pct, _)| *pct > 1. - LAP_COVERAGE);
+            if complete && self.valid {
+                let lap_time = self.current.last().map(|(_, time)| *time);
+                if self.best_lap_time().is_none_or(|best| lap_time.is_some_and(|time| time < best)) {
+                    self.best = Some(std::mem::take(&mut self.current));
+                }
+            }
+            self.current.clear();
+            self.lap = Some(sample.lap);
+            self.valid = true;
+        }
+        self.valid &= !sample.on_pit_road;
+        match self.current.last() {
+            // Going a long way backwards means a reset or a tow, so the lap can't be used
+            Some((pct, _)) if sample.lap_dist_pct < *pct - BACKWARD_TOLERANCE => self.valid = false,
+            // A small step back is noise, and skipped to keep the distances in order
+            Some((pct, _)) if sample.lap_dist_pct < *pct => {}
+            _ => self.current.push((sample.lap_dist_pct, sample.lap_time)),
+        }
+    }
+
+    /// Seconds behind (positive) or ahead of (negative) the best lap at this point.
+    pub fn delta(&self, sample: &LapSample) -> Option<f32> {
+        let best = self.best.as_ref()?;
+        let after = best.partition_point(|(pct, _)| *pct < sample.lap_dist_pct);
+        let best_time = match (best.get(after.wrapping_sub(1)), best.get(after)) {
+            (Some((pct_a, time_a)), Some((pct_b, time_b))) if pct_b > pct_a => {
+                time_a + (time_b - time_a) * (sample.lap_dist_pct - pct_a) / (pct_b - pct_a)
+            }
+            (Some((_, time)), _) | (None, Some((_, time))) => *time,
+            (None, None) => return None,
+        };
+        Some(sample.lap_time - best_time)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trend {
+    Gaining,
+    Steady,
+    Losing,
+}
+
+/// The delta shown on the overlay.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DeltaReading {
+    pub delta: f32,
+    pub trend: Trend,
+    /// Whether the delta is our own, because the sim's isn't valid.
+    pub internal: bool,
+}
+
+/// Follows the chosen delta every sample, falling back to our own when the sim's
+/// isn't valid, and tracks which way it's moving.
+pub struct DeltaTracker {
+    reference: DeltaReference,
+    internal: InternalDelta,
+    history: RingBuffer<f32>,
+    reading: Option<DeltaReading>,
+}
+
+impl DeltaTracker {
+    pub fn new(reference: DeltaReference) -> Self {
+        Self {
+            reference,
+            internal: InternalDelta::new(),
+            history: RingBuffer::new(TREND_SAMPLES),
+            reading: None,
+        }
+    }
+
+    /// Compare to `reference` from now on. The trend starts again, as the old
+    /// one was for a different lap.
+    pub fn set_reference(&mut self, reference: DeltaReference) {
+        if reference != self.reference {
+            self.reference = reference;
+            self.history.clear();
+        }
+    }
+
+    pub fn update(&mut self, telemetry: &IRacingTelemetry) {
+        let sample = LapSample::from_telemetry(telemetry);
+        if let Some(sample) = sample {
+            self.internal.update(sample);
+        }
+        let (delta, internal) = match sim_delta(telemetry, &self.reference) {
+            Some(delta) => (Some(delta), false),
+            None => (sample.and_then(|sample| self.internal.delta(&sample)), true),
+        };
+        let Some(delta) = delta else {
+            self.history.clear();
+            self.reading = None;
+            return;
+        };
+        // Switching between the sim's delta and ours would look like a jump
+        if self.reading.is_some_and(|reading| reading.internal != internal) {
+            self.history.clear();
+        }
+        self.history.push(delta);
+        let change = delta - self.history.iter().next().copied().unwrap_or(delta);
+        let trend = if change < -TREND_THRESHOLD {
+            Trend::Gaining
+        } else if change > TREND_THRESHOLD {
+            Trend::Losing
+        } else {
+            Trend::Steady
+        };
+        self.reading = Some(DeltaReading {
+            delta,
+            trend,
+            internal,
+        });
+    }
+
+    pub fn reading(&self) -> Option<DeltaReading> {
+        self.reading
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::{IRacingLogging, ScriptedSource, TelemetryValue};
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    fn sample(lap: i32, lap_dist_pct: f32, lap_time: f32) -> LapSample {
+        LapSample {
+            lap,
+            lap_dist_pct,
+            lap_time,
+            on_pit_road: false,
+        }
+    }
+
+    /// A whole lap at an even pace, taking `lap_time` seconds, in tenths.
+    fn drive_lap(delta: &mut InternalDelta, lap: i32, lap_time: f32) {
+        for tenth in 0..=10 {
+            let pct = tenth as f32 / 10.;
+            delta.update(sample(lap, pct, pct * lap_time));
+        }
+    }
+
+    #[test]
+    fn interpolates_between_best_lap_points() {
+        let mut delta = InternalDelta::new();
+        drive_lap(&mut delta, 1, 100.);
+        delta.update(sample(2, 0., 0.));
+        assert_eq!(delta.best_lap_time(), Some(100.));
+
+        // A second behind at a quarter of the lap, between the points at 20% and 30%
+        assert!((delta.delta(&sample(2, 0.25, 26.)).unwrap() - 1.).abs() < 1e-4);
+        assert!((delta.delta(&sample(2, 0.05, 4.)).unwrap() + 1.).abs() < 1e-4);
+        // Past the last point, the lap time is the comparison
+        assert!((delta.delta(&sample(2, 1., 99.)).unwrap() + 1.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn only_faster_laps_replace_the_best() {
+        let mut delta = InternalDelta::new();
+        drive_lap(&mut delta, 1, 100.);
+        drive_lap(&mut delta, 2, 105.);
+        drive_lap(&mut delta, 3, 98.);
+        delta.update(sample(4, 0., 0.));
+        assert_eq!(delta.best_lap_time(), Some(98.));
+    }
+
+    #[test]
+    fn partial_laps_are_not_best_laps() {
+        let mut delta = InternalDelta::new();
+        // Joining halfway round
+        delta.update(sample(1, 0.5, 0.));
+        delta.update(sample(1, 1., 50.));
+        delta.update(sample(2, 0., 0.));
+        assert_eq!(delta.best_lap_time(), None);
+        assert_eq!(delta.delta(&sample(2, 0.5, 50.)), None);
+    }
+
+    #[test]
+    fn pit_road_invalidates_the_lap() {
+        let mut delta = InternalDelta::new();
+        delta.update(sample(1, 0., 0.));
+        delta.update(LapSample {
+            on_pit_road: true,
+            ..sample(1, 0.5, 50.)
+        });
+        delta.update(sample(1, 1., 100.));
+        delta.update(sample(2, 0., 0.));
+        assert_eq!(delta.best_lap_time(), None);
+    }
+
+    #[test]
+    fn small_backward_steps_keep_the_lap() {
+        let mut delta = InternalDelta::new();
+        delta.update(sample(1, 0., 0.));
+        delta.update(sample(1, 0.5, 50.));
+        delta.update(sample(1, 0.498, 50.1));
+        delta.update(sample(1, 0.6, 60.));
+        delta.update(sample(1, 1., 100.));
+        delta.update(sample(2, 0., 0.));
+        assert_eq!(delta.best_lap_time(), Some(100.));
+        // The step back isn't used as a point
+        assert!((delta.delta(&sample(2, 0.55, 55.)).unwrap()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn large_backward_steps_invalidate_the_lap() {
+        let mut delta = InternalDelta::new();
+        delta.update(sample(1, 0., 0.));
+        delta.update(sample(1, 0.5, 50.));
+        // Reset to the pits, or towed
+        delta.update(sample(1, 0.2, 51.));
+        delta.update(sample(1, 1., 100.));
+        delta.update(sample(2, 0., 0.));
+        assert_eq!(delta.best_lap_time(), None);
+    }
+
+    fn sim_frame(delta: f32, ok: bool) -> HashMap<&'static str, TelemetryValue> {
+        HashMap::from([
+            ("Lap", TelemetryValue::Int(1)),
+            ("LapDistPct", TelemetryValue::Float(0.5)),
+            ("LapCurrentLapTime", TelemetryValue::Float(50.)),
+            ("OnPitRoad", TelemetryValue::Bool(false)),
+            ("LapDeltaToBestLap", TelemetryValue::Float(delta)),
+            ("LapDeltaToBestLap_OK", TelemetryValue::Bool(ok)),
+        ])
+    }
+
+    /// The readings after each frame.
+    fn track(frames: Vec<HashMap<&'static str, TelemetryValue>>) -> Vec<Option<DeltaReading>> {
+        let count = frames.len();
+        let mut logging = IRacingLogging::with_source(Box::new(ScriptedSource::new(frames)), VARIABLES);
+        let mut tracker = DeltaTracker::new(DeltaReference::BestLap);
+        (0..count)
+            .map(|_| {
+                assert!(logging.read_telemetry(Duration::ZERO));
+                tracker.update(&logging.telemetry);
+                tracker.reading()
+            })
+            .collect()
+    }
+
+    fn trends(readings: &[Option<DeltaReading>]) -> Vec<Option<Trend>> {
+        readings.iter().map(|reading| reading.map(|reading| reading.trend)).collect()
+    }
+
+    #[test]
+    fn trend_follows_the_delta() {
+        let losing = track((0..5).map(|i| sim_frame(i as f32 * 0.05, true)).collect());
+        assert_eq!(trends(&losing)[4], Some(Trend::Losing));
+        assert_eq!(losing[4].unwrap().delta, 0.2);
+        assert!(!losing[4].unwrap().internal);
+
+        let gaining = track((0..5).map(|i| sim_frame(-(i as f32) * 0.05, true)).collect());
+        assert_eq!(trends(&gaining)[4], Some(Trend::Gaining));
+
+        // Wobbles smaller than the threshold are steady
+        let steady = track((0..5).map(|i| sim_frame(0.3 + (i % 2) as f32 * 0.005, true)).collect());
+        assert!(trends(&steady).iter().all(|trend| *trend == Some(Trend::Steady)));
+    }
+
+    #[test]
+    fn trend_starts_again_without_a_delta() {
+        let readings = track(vec![
+            sim_frame(0., true),
+            sim_frame(0.5, true),
+            // No valid sim delta and no best lap of our own yet
+            sim_frame(0.5, false),
+            sim_frame(0.5, true),
+        ]);
+        assert_eq!(
+            trends(&readings),
+            vec![Some(Trend::Steady), Some(Trend::Losing), None, Some(Trend::Steady)]
+        );
+    }
+}
@@ -0,0 +1,359 @@
+use crate::session::SessionDetails;
+use crate::telemetry::{IRacingTelemetry, Variable, VariableType};
+use crate::trace::RingBuffer;
+
+/// The telemetry needed to time laps and sectors.
+pub const VARIABLES: &[Variable] = &[
+    Variable::new("Lap", VariableType::Int),
+    Variable::new("LapDistPct", VariableType::Float),
+    Variable::new("LapCurrentLapTime", VariableType::Float),
+    Variable::new("SessionTime", VariableType::Double),
+    Variable::new("OnPitRoad", VariableType::Bool),
+    Variable::new("PlayerTrackSurface", VariableType::Int),
+];
+
+/// `PlayerTrackSurface` when the car has all four wheels off the track.
+const OFF_TRACK: i32 = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimingSample {
+    pub lap: i32,
+    pub lap_dist_pct: f32,
+    /// Seconds since the start of the lap.
+    pub lap_time: f32,
+    pub session_time: f64,
+    pub on_pit_road: bool,
+    pub off_track: bool,
+}
+
+impl TimingSample {
+    pub fn from_telemetry(telemetry: &IRacingTelemetry) -> Option<Self> {
+        Some(Self {
+            lap: telemetry.int("Lap")?,
+            lap_dist_pct: telemetry.float("LapDistPct")?,
+            lap_time: telemetry.float("LapCurrentLapTime")?,
+            session_time: telemetry.double("SessionTime")?,
+            on_pit_road: telemetry.bool("OnPitRoad").unwrap_or_default(),
+            off_track: telemetry.int("PlayerTrackSurface") == Some(OFF_TRACK),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SectorTime {
+    pub time: f32,
+    /// Whether the car stayed on track and out of the pits for the whole sector.
+    pub valid: bool,
+    /// Whether it was the player's best time for the sector when it was set.
+    pub personal_best: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LapRecord {
+    pub lap: i32,
+    pub time: f32,
+    pub sectors: Vec<SectorTime>,
+    /// Whether every sector was valid.
+    pub valid: bool,
+    /// Whether the car was on pit road at any point in the lap.
+    pub pit: bool,
+}
+
+/// The lap in progress, timed from when it started at the line.
+#[derive(Clone, Debug)]
+struct CurrentLap {
+    lap: i32,
+    sectors: Vec<SectorTime>,
+    sector_start: f32,
+    sector_valid: bool,
+    pit: bool,
+}
+
+/// Times the player's laps and sectors, keeping the most recent laps and
+/// the best lap and sectors for the session.
+pub struct LapHistory {
+    /// Where each sector starts, the first always at the line
+    sector_starts: Vec<f32>,
+    laps: RingBuffer<LapRecord>,
+    current: Option<CurrentLap>,
+    last: Option<TimingSample>,
+    best_lap: Option<f32>,
+    best_sectors: Vec<Option<f32>>,
+}
+
+impl LapHistory {
+    /// Keeps the last `laps` laps, split into sectors starting at `sector_starts`.
+    pub fn new(laps: usize, sector_starts: Vec<f32>) -> Self {
+        let sector_starts = Self::normalise_sectors(sector_starts);
+        Self {
+            best_sectors: vec![None; sector_starts.len()],
+            sector_starts,
+            laps: RingBuffer::new(laps),
+            current: None,
+            last: None,
+            best_lap: None,
+        }
+    }
+
+    /// In order, without duplicates, and with the first at the line.
+    fn normalise_sectors(sector_starts: Vec<f32>) -> Vec<f32> {
+        let mut sector_starts: Vec<f32> = sector_starts.into_iter().filter(|pct| *pct > 0. && *pct < 1.).collect();
+        sector_starts.sort_by(f32::total_cmp);
+        sector_starts.dedup();
+        sector_starts.insert(0, 0.);
+        sector_starts
+    }
+
+    /// The sectors from the session's `SplitTimeInfo`.
+    pub fn sector_starts(session: &SessionDetails) -> Vec<f32> {
+        session
+            .split_time_info
+            .sectors
+            .iter()
+            .map(|sector| sector.sector_start_pct)
+            .collect()
+    }
+
+    /// Start again with new sectors, if they've changed.
+    pub fn set_sectors(&mut self, sector_starts: Vec<f32>) {
+        let sector_starts = Self::normalise_sectors(sector_starts);
+        if sector_starts != self.sector_starts {
+            *self = Self::new(self.laps.capacity(), sector_starts);
+        }
+    }
+
+    /// Keep the last `laps` laps from now on.
+    pub fn set_laps(&mut self, laps: usize) {
+        self.laps.set_capacity(laps);
+    }
+
+    pub fn sector_count(&self) -> usize {
+        self.sector_starts.len()
+    }
+
+    /// Oldest first.
+    pub fn laps(&self) -> impl Iterator<Item = &LapRecord> {
+        self.laps.iter()
+    }
+
+    pub fn best_lap(&self) -> Option<f32> {
+        self.best_lap
+    }
+
+    /// Whether `time` is the player's current best for sector `sector`.
+    pub fn is_best_sector(&self, sector: usize, time: &SectorTime) -> bool {
+        time.valid && self.best_sectors.get(sector).copied().flatten() == Some(time.time)
+    }
+
+    /// The sectors done so far on the lap in progress.
+    pub fn current_sectors(&self) -> &[SectorTime] {
+        self.current.as_ref().map_or(&[], |current| &current.sectors)
+    }
+
+    pub fn update(&mut self, sample: TimingSample) {
+        let Some(last) = self.last.replace(sample) else {
+            return;
+        };
+        // Replays jumping about, or a reset, mean the timing can't be trusted
+        let jumped = sample.session_time <= last.session_time
+            || (sample.lap == last.lap && sample.lap_dist_pct < last.lap_dist_pct)
+            || (sample.lap != last.lap && sample.lap != last.lap + 1);
+        if jumped {
+            self.current = None;
+            return;
+        }
+
+        // Where this sample is on the lap the last one was on, so the line when it's been crossed
+        let (end_pct, end_time) = if sample.lap == last.lap {
+            (sample.lap_dist_pct, sample.lap_time)
+        } else {
+            (1., last.lap_time + (sample.session_time - last.session_time) as f32 - sample.lap_time)
+        };
+        // The time at `pct` between the last sample and this one
+        let time_at = |pct: f32| {
+            let covered = end_pct - last.lap_dist_pct;
+            if covered <= 0. {
+                return end_time;
+            }
+            last.lap_time + (end_time - last.lap_time) * (pct - last.lap_dist_pct) / covered
+        };
+        let sector_ends: Vec<f32> = self.sector_starts[1..].iter().copied().chain([1.]).collect();
+        if let Some(mut current) = self.current.take() {
+            while let Some(end) = sector_ends.get(current.sectors.len()).copied() {
+                if end > end_pct || end <= last.lap_dist_pct {
+                    break;
+                }
+                let end_time = if end < 1. { time_at(end) } else { end_time };
+                self.finish_sector(&mut current, end_time);
+            }
+            if sample.lap == last.lap {
+                current.sector_valid &= !sample.on_pit_road && !sample.off_track;
+                current.pit |= sample.on_pit_road;
+                self.current = Some(current);
+            } else if current.sectors.len() == self.sector_starts.len() {
+                self.finish_lap(current);
+            }
+        }
+
+        if sample.lap != last.lap {
+            self.current = Some(CurrentLap {
+                lap: sample.lap,
+                sectors: Vec::new(),
+                sector_start: 0.,
+                sector_valid: !sample.on_pit_road && !sample.off_track,
+                pit: sample.on_pit_road,
+            });
+        }
+    }
+
+    fn finish_sector(&mut self, current: &mut CurrentLap, end_time: f32) {
+        let sector = current.sectors.len();
+        let time = end_time - current.sector_start;
+        let best = &mut self.best_sectors[sector];
+        let personal_best = current.sector_valid && best.is_none_or(|best| time < best);
+        if personal_best {
+            *best = Some(time);
+        }
+        current.sectors.push(SectorTime {
+            time,
+            valid: current.sector_valid,
+            personal_best,
+        });
+        current.sector_start = end_time;
+        current.sector_valid = true;
+    }
+
+    fn finish_lap(&mut self, current: CurrentLap) {
+        let valid = current.sectors.iter().all(|sector| sector.valid);
+        let lap = LapRecord {
+            lap: current.lap,
+            time: current.sector_start,
+            sectors: current.sectors,
+            valid,
+            pit: current.pit,
+        };
+        if valid && self.best_lap.is_none_or(|best| lap.time < best) {
+            self.best_lap = Some(lap.time);
+        }
+        self.laps.push(lap);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: f64 = 0.1;
+
+    /// Samples of laps driven at a steady `lap_time` each, starting just before
+    /// the line and stopping before the last lap is finished.
+    fn laps(lap_times: &[f32]) -> Vec<TimingSample> {
+        let mut samples = Vec::new();
+        let mut session_time = 0.;
+        let mut lap_time = 0.95 * lap_times[0];
+        let mut lap = 0;
+        while lap < lap_times.len() as i32 {
+            let length = lap_times[lap as usize];
+            samples.push(TimingSample {
+                lap,
+                lap_dist_pct: lap_time / length,
+                lap_time,
+                session_time,
+                on_pit_road: false,
+                off_track: false,
+            });
+            session_time += STEP;
+            lap_time += STEP as f32;
+            if lap_time >= length {
+                lap_time -= length;
+                lap += 1;
+                lap_time *= lap_times.get(lap as usize).unwrap_or(&length) / length;
+            }
+        }
+        samples
+    }
+
+    fn record(history: &mut LapHistory, samples: &[TimingSample]) {
+        for sample in samples {
+            history.update(*sample);
+        }
+    }
+
+    #[test]
+    fn times_laps_and_sectors() {
+        let mut history = LapHistory::new(5, vec![0., 0.25, 0.5]);
+        record(&mut history, &laps(&[100., 90., 95., 100.]));
+
+        let laps: Vec<&LapRecord> = history.laps().collect();
+        // The first lap wasn't seen from the start
+        assert_eq!(laps.len(), 2);
+        assert_eq!(laps[0].lap, 1);
+        assert!((laps[0].time - 90.).abs() < 0.05, "{}", laps[0].time);
+        assert!((laps[1].time - 95.).abs() < 0.05, "{}", laps[1].time);
+        let sectors: Vec<f32> = laps[0].sectors.iter().map(|sector| sector.time).collect();
+        assert_eq!(sectors.len(), 3);
+        assert!((sectors[0] - 22.5).abs() < 0.05);
+        assert!((sectors[1] - 22.5).abs() < 0.05);
+        assert!((sectors[2] - 45.).abs() < 0.05);
+        assert!((history.best_lap().unwrap() - 90.).abs() < 0.05);
+    }
+
+    #[test]
+    fn personal_best_sectors() {
+        let mut history = LapHistory::new(5, vec![0., 0.5]);
+        record(&mut history, &laps(&[100., 100., 90., 95., 100.]));
+        let laps: Vec<&LapRecord> = history.laps().collect();
+
+        // Personal bests when set, but only the 90 second lap's are still the best
+        assert!(laps[0].sectors.iter().all(|sector| sector.personal_best));
+        assert!(laps[1].sectors.iter().all(|sector| sector.personal_best));
+        assert!(!history.is_best_sector(0, &laps[0].sectors[0]));
+        assert!(history.is_best_sector(0, &laps[1].sectors[0]));
+        assert!(history.is_best_sector(1, &laps[1].sectors[1]));
+        assert!(!laps[2].sectors[0].personal_best);
+    }
+
+    #[test]
+    fn marks_invalid_and_pit_laps() {
+        let mut samples = laps(&[100., 80., 90., 100., 100.]);
+        for sample in samples.iter_mut() {
+            // Off track in the second half of lap 1, and in the pits on lap 3
+            sample.off_track = sample.lap == 1 && sample.lap_dist_pct > 0.6 && sample.lap_dist_pct < 0.7;
+            sample.on_pit_road = sample.lap == 3;
+        }
+        let mut history = LapHistory::new(5, vec![0., 0.5]);
+        record(&mut history, &samples);
+        let laps: Vec<&LapRecord> = history.laps().collect();
+
+        assert!(!laps[0].valid);
+        assert!(laps[0].sectors[0].valid);
+        assert!(!laps[0].sectors[1].valid);
+        assert!(laps[1].valid);
+        assert!(laps[2].pit);
+        assert!(!laps[2].valid);
+        // The fast lap was invalid, so doesn't count
+        assert!((history.best_lap().unwrap() - 90.).abs() < 0.05);
+        assert!(history.is_best_sector(0, &laps[0].sectors[0]));
+    }
+
+    #[test]
+    fn keeps_the_last_laps() {
+        let mut history = LapHistory::new(2, vec![]);
+        record(&mut history, &laps(&[100., 90., 91., 92., 93., 100.]));
+        let laps: Vec<i32> = history.laps().map(|lap| lap.lap).collect();
+        assert_eq!(laps, vec![3, 4]);
+        assert_eq!(history.sector_count(), 1);
+        assert!((history.best_lap().unwrap() - 90.).abs() < 0.05);
+    }
+
+    #[test]
+    fn fewer_laps_drops_the_oldest() {
+        let mut history = LapHistory::new(5, vec![]);
+        record(&mut history, &laps(&[100., 90., 91., 92., 93., 100.]));
+        history.set_laps(2);
+        let laps: Vec<i32> = history.laps().map(|lap| lap.lap).collect();
+        assert_eq!(laps, vec![3, 4]);
+        // The best lap is kept even once its record has gone
+        assert!((history.best_lap().unwrap() - 90.).abs() < 0.05);
+    }
+}
@@ -0,0 +1,371 @@
+use crate::session::SessionDetails;
+use crate::telemetry::{IRacingTelemetry, Variable, VariableType};
+
+/// The telemetry needed to build the standings.
+pub const VARIABLES: &[Variable] = &[
+    Variable::new("PlayerCarIdx", VariableType::Int),
+    Variable::new("SessionNum", VariableType::Int),
+    Variable::new("CarIdxPosition", VariableType::IntArray),
+    Variable::new("CarIdxClassPosition", VariableType::IntArray),
+    Variable::new("CarIdxLap", VariableType::IntArray),
+    Variable::new("CarIdxLapDistPct", VariableType::FloatArray),
+    Variable::new("CarIdxLastLapTime", VariableType::FloatArray),
+    Variable::new("CarIdxBestLapTime", VariableType::FloatArray),
+    Variable::new("CarIdxF2Time", VariableType::FloatArray),
+    Variable::new("CarIdxOnPitRoad", VariableType::BoolArray),
+];
+
+/// One car's row in the standings. Times are in seconds, and negative when unknown.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Standing {
+    pub car_idx: usize,
+    /// Overall position, or 0 before the car has one.
+    pub position: i32,
+    pub class_position: i32,
+    pub class_id: i32,
+    pub last_lap: f32,
+    pub best_lap: f32,
+    pub gap_to_leader: f32,
+    /// Gap to the car one place ahead in the same class.
+    pub interval: f32,
+    /// Whole laps behind the class leader.
+    pub laps_down: i32,
+    pub on_pit_road: bool,
+}
+
+/// The cars in one class, in class order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassGroup {
+    pub class_id: i32,
+    pub name: String,
+    pub colour: u32,
+    pub standings: Vec<Standing>,
+}
+
+fn index<T: Copy + Default>(values: Option<&[T]>, car_idx: usize) -> T {
+    values
+        .and_then(|values| values.get(car_idx))
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Positive times only, as iRacing uses -1 and 0 for "no time".
+fn time(primary: f32, fallback: f32) -> f32 {
+    if primary > 0. {
+        primary
+    } else if fallback > 0. {
+        fallback
+    } else {
+        -1.
+    }
+}
+
+/// Builds the standings for every racing car, preferring the live telemetry and
+/// falling back to the session results where the telemetry has nothing yet.
+pub fn standings(telemetry: &IRacingTelemetry, session: &SessionDetails) -> Vec<Standing> {
+    let results = telemetry
+        .int("SessionNum")
+        .and_then(|session_num| session.session(session_num))
+        .map(|session| session.results())
+        .unwrap_or_default();
+    let positions = telemetry.int_array("CarIdxPosition");
+    let class_positions = telemetry.int_array("CarIdxClassPosition");
+    let laps = telemetry.int_array("CarIdxLap");
+    let lap_dist_pcts = telemetry.float_array("CarIdxLapDistPct");
+    let last_laps = telemetry.float_array("CarIdxLastLapTime");
+    let best_laps = telemetry.float_array("CarIdxBestLapTime");
+    let f2_times = telemetry.float_array("CarIdxF2Time");
+    let on_pit_road = telemetry.bool_array("CarIdxOnPitRoad");
+
+    let mut standings: Vec<(Standing, f32)> = session
+        .driver_info
+        .drivers
+        .iter()
+        .filter(|driver| driver.is_racing())
+        .map(|driver| {
+            let car_idx = driver.car_idx;
+            let result = results.iter().find(|result| result.car_idx == car_idx);
+            let position = match index(positions, car_idx) {
+                0 => result.map_or(0, |result| result.position),
+                position => position,
+            };
+            let class_position = match index(class_positions, car_idx) {
+                // Class positions in the results count from zero
+                0 => result.map_or(0, |result| result.class_position + 1),
+                position => position,
+            };
+            let distance = index(laps, car_idx) as f32 + index(lap_dist_pcts, car_idx).max(0.);
+            let standing = Standing {
+                car_idx,
+                position,
+                class_position,
+                class_id: driver.car_class_id,
+                last_lap: time(index(last_laps, car_idx), result.map_or(-1., |r| r.last_time)),
+                best_lap: time(index(best_laps, car_idx), result.map_or(-1., |r| r.fastest_time)),
+                gap_to_leader: index(f2_times, car_idx).max(0.),
+                interval: 0.,
+                laps_down: 0,
+                on_pit_road: index(on_pit_road, car_idx),
+            };
+            (standing, distance)
+        })
+        .collect();
+
+    // Cars without a position yet go to the bottom, in entry order
+    standings.sort_by_key(|(standing, _)| (standing.position <= 0, standing.position, standing.car_idx));
+
+    // Intervals and laps down are relative to the class, so multiclass gaps make sense
+    let mut class_leaders: Vec<(i32, f32, f32)> = Vec::new();
+    let mut class_previous: Vec<(i32, f32)> = Vec::new();
+    for (standing, distance) in standings.iter_mut() {
+        if standing.position <= 0 {
+            continue;
+        }
+        match class_leaders.iter().find(|(class_id, _, _)| *class_id == standing.class_id) {
+            Some((_, leader_gap, leader_distance)) => {
+                standing.laps_down = (leader_distance - *distance).floor().max(0.) as i32;
+                standing.gap_to_leader = (standing.gap_to_leader - leader_gap).max(0.);
+            }
+            None => {
+                class_leaders.push((standing.class_id, standing.gap_to_leader, *distance));
+                standing.gap_to_leader = 0.;
+            }
+        }
+        match class_previous.iter_mut().find(|(class_id, _)| *class_id == standing.class_id) {
+            Some((_, previous_gap)) => {
+                standing.interval = (standing.gap_to_leader - *previous_gap).max(0.);
+                *previous_gap = standing.gap_to_leader;
+            }
+            None => class_previous.push((standing.class_id, standing.gap_to_leader)),
+        }
+    }
+    standings.into_iter().map(|(standing, _)| standing).collect()
+}
+
+/// Splits the standings by class, with the class of the overall leader first.
+pub fn group_by_class(standings: Vec<Standing>, session: &SessionDetails) -> Vec<ClassGroup> {
+    let mut groups: Vec<ClassGroup> = Vec::new();
+    for standing in standings {
+        match groups.iter_mut().find(|group| group.class_id == standing.class_id) {
+            Some(group) => group.standings.push(standing),
+            None => {
+                let driver = session.driver(standing.car_idx);
+                groups.push(ClassGroup {
+                    class_id: standing.class_id,
+                    name: driver.map_or(String::new(), |d| d.car_class_short_name.clone()),
+                    colour: driver.map_or(0xffffff, |d| d.car_class_color),
+                    standings: vec![standing],
+                });
+            }
+        }
+    }
+    groups
+}
+
+/// Cuts `standings` down to `limit` rows, swapping the last row for the player
+/// if they would otherwise be cut.
+pub fn limit_rows(mut standings: Vec<Standing>, limit: usize, always_show: Option<usize>) -> Vec<Standing> {
+    if standings.len() <= limit {
+        return standings;
+    }
+    let player = always_show.and_then(|car_idx| {
+        standings
+            .iter()
+            .position(|standing| standing.car_idx == car_idx)
+            .filter(|position| *position >= limit)
+    });
+    match player {
+        Some(position) if limit > 0 => {
+            let player = standings.swap_remove(position);
+            standings.truncate(limit - 1);
+            standings.push(player);
+        }
+        _ => standings.truncate(limit),
+    }
+    standings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::{IRacingLogging, ScriptedSource, TelemetryValue};
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    // Two classes, with the pace car, and a car that has yet to get a position
+    const SESSION: &str = "---
+SessionInfo:
+ Sessions:
+ - SessionNum: 2
+   SessionType: Race
+   ResultsPositions:
+   - Position: 3
+     ClassPosition: 0
+     CarIdx: 3
+     LastTime: 112.5000
+     FastestTime: 111.0000
+DriverInfo:
+ DriverCarIdx: 4
+ Drivers:
+ - CarIdx: 0
+   UserName: Pace Car
+   CarIsPaceCar: 1
+ - CarIdx: 1
+   CarClassID: 1
+   CarClassShortName: GT3
+   CarClassColor: 0xffda59
+ - CarIdx: 2
+   CarClassID: 1
+   CarClassShortName: GT3
+   CarClassColor: 0xffda59
+ - CarIdx: 3
+   CarClassID: 2
+   CarClassShortName: GT4
+   CarClassColor: 0x33ceff
+ - CarIdx: 4
+   CarClassID: 1
+   CarClassShortName: GT3
+   CarClassColor: 0xffda59
+ - CarIdx: 5
+   CarClassID: 2
+   CarClassShortName: GT4
+   CarClassColor: 0x33ceff
+ - CarIdx: 6
+   CarClassID: 1
+   CarClassShortName: GT3
+   CarClassColor: 0xffda59
+...
+";
+
+    fn telemetry() -> IRacingTelemetry {
+        let frame = HashMap::from([
+            ("PlayerCarIdx", TelemetryValue::Int(4)),
+            ("SessionNum", TelemetryValue::Int(2)),
+            ("CarIdxPosition", TelemetryValue::IntVec(vec![0, 1, 2, 3, 4, 5, 0])),
+            ("CarIdxClassPosition", TelemetryValue::IntVec(vec![0, 1, 2, 1, 3, 2, 0])),
+            ("CarIdxLap", TelemetryValue::IntVec(vec![0, 10, 10, 9, 9, 9, 0])),
+            ("CarIdxLapDistPct", TelemetryValue::FloatVec(vec![0., 0.5, 0.4, 0.9, 0.45, 0.3, -1.])),
+            ("CarIdxLastLapTime", TelemetryValue::FloatVec(vec![0., 100., 101., -1., 0., 110., 0.])),
+            ("CarIdxBestLapTime", TelemetryValue::FloatVec(vec![0., 99., 100., -1., 0., 109., 0.])),
+            ("CarIdxF2Time", TelemetryValue::FloatVec(vec![0., 0., 5., 30., 100.5, 80., 0.])),
+            ("CarIdxOnPitRoad", TelemetryValue::BoolVec(vec![false, false, false, false, false, false, true])),
+        ]);
+        let mut logging = IRacingLogging::with_source(Box::new(ScriptedSource::new(vec![frame])), VARIABLES);
+        assert!(logging.read_telemetry(Duration::ZERO));
+        logging.telemetry
+    }
+
+    fn row(car_idx: usize) -> Standing {
+        Standing {
+            car_idx,
+            position: car_idx as i32 + 1,
+            class_position: car_idx as i32 + 1,
+            class_id: 1,
+            last_lap: -1.,
+            best_lap: -1.,
+            gap_to_leader: 0.,
+            interval: 0.,
+            laps_down: 0,
+            on_pit_road: false,
+        }
+    }
+
+    fn car_idxs(standings: &[Standing]) -> Vec<usize> {
+        standings.iter().map(|standing| standing.car_idx).collect()
+    }
+
+    #[test]
+    fn racing_cars_in_position_order() {
+        let session = SessionDetails::parse(SESSION).unwrap();
+        let standings = standings(&telemetry(), &session);
+        // No pace car, and the car without a position last
+        assert_eq!(car_idxs(&standings), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(standings[0].last_lap, 100.);
+        assert_eq!(standings[0].best_lap, 99.);
+    }
+
+    #[test]
+    fn gaps_are_within_each_class() {
+        let session = SessionDetails::parse(SESSION).unwrap();
+        let standings = standings(&telemetry(), &session);
+        let gaps: Vec<(f32, f32, i32)> = standings
+            .iter()
+            .map(|standing| (standing.gap_to_leader, standing.interval, standing.laps_down))
+            .collect();
+        assert_eq!(
+            gaps,
+            vec![
+                (0., 0., 0),
+                (5., 5., 0),
+                // The GT4 leader
+                (0., 0., 0),
+                (100.5, 95.5, 1),
+                (50., 50., 0),
+                (0., 0., 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn results_fill_in_missing_telemetry() {
+        let session = SessionDetails::parse(SESSION).unwrap();
+        let standings = standings(&telemetry(), &session);
+        let gt4_leader = &standings[2];
+        assert_eq!(gt4_leader.car_idx, 3);
+        assert_eq!(gt4_leader.last_lap, 112.5);
+        assert_eq!(gt4_leader.best_lap, 111.);
+    }
+
+    #[test]
+    fn car_without_a_position() {
+        let session = SessionDetails::parse(SESSION).unwrap();
+        let standings = standings(&telemetry(), &session);
+        let unplaced = standings.last().unwrap();
+        assert_eq!((unplaced.position, unplaced.class_position), (0, 0));
+        assert_eq!((unplaced.last_lap, unplaced.best_lap), (-1., -1.));
+        assert!(unplaced.on_pit_road);
+    }
+
+    #[test]
+    fn groups_classes_leader_first() {
+        let session = SessionDetails::parse(SESSION).unwrap();
+        let groups = group_by_class(standings(&telemetry(), &session), &session);
+        let summary: Vec<(&str, u32, Vec<usize>)> = groups
+            .iter()
+            .map(|group| (group.name.as_str(), group.colour, car_idxs(&group.standings)))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("GT3", 0xffda59, vec![1, 2, 4, 6]), ("GT4", 0x33ceff, vec![3, 5])]
+        );
+    }
+
+    #[test]
+    fn short_standings_are_not_limited() {
+        let standings: Vec<Standing> = (0..3).map(row).collect();
+        assert_eq!(car_idxs(&limit_rows(standings, 5, Some(2))), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn player_near_the_top_is_already_shown() {
+        let standings: Vec<Standing> = (0..10).map(row).collect();
+        assert_eq!(car_idxs(&limit_rows(standings.clone(), 4, Some(0))), vec![0, 1, 2, 3]);
+        assert_eq!(car_idxs(&limit_rows(standings, 4, Some(3))), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn player_near_the_bottom_takes_the_last_row() {
+        let standings: Vec<Standing> = (0..10).map(row).collect();
+        assert_eq!(car_idxs(&limit_rows(standings.clone(), 4, Some(9))), vec![0, 1, 2, 9]);
+        assert_eq!(car_idxs(&limit_rows(standings, 4, Some(4))), vec![0, 1, 2, 4]);
+    }
+
+    #[test]
+    fn limits_without_a_player() {
+        let standings: Vec<Standing> = (0..10).map(row).collect();
+        assert_eq!(car_idxs(&limit_rows(standings.clone(), 3, None)), vec![0, 1, 2]);
+        // A player who isn't in the standings, e.g. spectating
+        assert_eq!(car_idxs(&limit_rows(standings.clone(), 3, Some(42))), vec![0, 1, 2]);
+        assert!(limit_rows(standings, 0, Some(9)).is_empty());
+    }
+}
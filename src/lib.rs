@@ -0,0 +1,24 @@
+pub mod config_watch;
+pub mod fuel_calc;
+pub mod gaps;
+pub mod hub;
+pub mod ibt;
+pub mod lap_delta;
+pub mod lap_history;
+pub mod leaderboard;
+pub mod pit_loss;
+pub mod proximity;
+pub mod registry;
+pub mod session;
+pub mod session_flags;
+pub mod shift_lights;
+pub mod supervisor;
+pub mod telemetry;
+#[cfg(test)]
+mod test_support;
+pub mod time_format;
+pub mod trace;
+pub mod track_outline;
+pub mod tyre_data;
+pub mod util;
+pub mod widgets;
@@ -1,7 +1,6 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
 mod app;
-mod util;
 use eframe::egui;
 
 fn main() -> Result<(), eframe::Error> {
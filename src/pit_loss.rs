@@ -0,0 +1,412 @@
+use crate::gaps::{self, CarPosition, Gap};
+use crate::session::SessionDetails;
+use crate::telemetry::{IRacingTelemetry, Variable, VariableType};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The telemetry needed to learn the pit loss and work out where the player would rejoin.
+pub const VARIABLES: &[Variable] = &[
+    Variable::new("PlayerCarIdx", VariableType::Int),
+    Variable::new("SessionTime", VariableType::Double),
+    Variable::new("CarIdxLap", VariableType::IntArray),
+    Variable::new("CarIdxLapDistPct", VariableType::FloatArray),
+    Variable::new("CarIdxEstTime", VariableType::FloatArray),
+    Variable::new("CarIdxOnPitRoad", VariableType::BoolArray),
+];
+
+/// Laps a car can move between samples before it counts as a jump, like a tow to the pits.
+const MAX_STEP: f32 = 0.05;
+
+/// Laps per second below which a car in the pit lane counts as stopped in its box.
+const STATIONARY_SPEED: f32 = 1e-4;
+
+/// Transits the learned loss is averaged over, so it follows changes like a new pit speed limit.
+const MAX_TRANSITS: u32 = 20;
+
+/// A car part way down the pit lane.
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    session_time: f64,
+    distance: f32,
+    stationary: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CarState {
+    session_time: f64,
+    /// Laps plus the distance round the current one
+    distance: f32,
+    on_pit_road: bool,
+    entry: Option<Entry>,
+}
+
+/// Watches every car's trips down the pit lane and measures how much slower
+/// they are than staying out on track, not counting time stopped in the box.
+#[derive(Default)]
+pub struct PitLaneTimer {
+    cars: HashMap<usize, CarState>,
+}
+
+impl PitLaneTimer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the loss in seconds for every car that left the pit lane since
+    /// the last update, given each car's lap time from `lap_time`.
+    pub fn update(&mut self, session_time: f64, cars: &[CarPosition], lap_time: impl Fn(usize) -> f32) -> Vec<f32> {
+        let mut losses = Vec::new();
+        self.cars.retain(|car_idx, _| cars.iter().any(|car| car.car_idx == *car_idx));
+        for car in cars {
+            let distance = car.lap as f32 + car.lap_dist_pct;
+            let Some(last) = self.cars.get(&car.car_idx).copied().filter(|last| last.session_time < session_time) else {
+                // Cars already in the pits when first seen haven't been timed from the entry
+                self.cars.insert(
+                    car.car_idx,
+                    CarState {
+                        session_time,
+                        distance,
+                        on_pit_road: car.on_pit_road,
+                        entry: None,
+                    },
+                );
+                continue;
+            };
+            let dt = (session_time - last.session_time) as f32;
+            let step = distance - last.distance;
+            let joined = step.abs() < MAX_STEP;
+            let mut entry = match (last.on_pit_road, car.on_pit_road) {
+                (false, true) if joined => Some(Entry {
+                    session_time,
+                    distance,
+                    stationary: 0.,
+                }),
+                (_, true) if joined => last.entry,
+                _ => None,
+            };
+            if let Some(entry) = entry.as_mut().filter(|_| step / dt < STATIONARY_SPEED) {
+                entry.stationary += dt;
+            }
+            if let (Some(entry), false, true) = (last.entry, car.on_pit_road, joined) {
+                let lap_time = lap_time(car.car_idx);
+                let covered = distance - entry.distance;
+                let transit = (session_time - entry.session_time) as f32;
+                let loss = transit - entry.stationary - covered * lap_time;
+                if covered > 0. && covered < 0.5 && loss > 0. && loss < lap_time / 2. {
+                    losses.push(loss);
+                }
+            }
+            self.cars.insert(
+                car.car_idx,
+                CarState {
+                    session_time,
+                    distance,
+                    on_pit_road: car.on_pit_road,
+                    entry,
+                },
+            );
+        }
+        losses
+    }
+}
+
+/// The time lost driving through a track's pit lane, learned from watching cars use it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PitLoss {
+    pub track_id: i32,
+    /// Seconds, not counting time stopped in the box.
+    pub lane_loss: f32,
+    pub transits: u32,
+}
+
+impl PitLoss {
+    pub fn cache_path(cache_dir: &Path, track_id: i32) -> PathBuf {
+        cache_dir.join(format!("pit-{}.toml", track_id))
+    }
+
+    pub fn load(cache_dir: &Path, track_id: i32) -> Result<Self, Box<dyn Error>> {
+        Ok(toml::from_str(&fs::read_to_string(Self::cache_path(cache_dir, track_id))?)?)
+    }
+
+    pub fn save(&self, cache_dir: &Path) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(cache_dir)?;
+        fs::write(Self::cache_path(cache_dir, self.track_id), toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn add(&mut self, loss: f32) {
+        self.transits = (self.transits + 1).min(MAX_TRANSITS);
+        self.lane_loss += (loss - self.lane_loss) / self.transits as f32;
+    }
+}
+
+/// Keeps the pit loss for the current track, loading it from the cache and
+/// refining and caching it as cars are seen using the pit lane. Without a
+/// cache directory it's learned again every time.
+pub struct PitLossTracker {
+    cache_dir: Option<PathBuf>,
+    track_id: Option<i32>,
+    pit_loss: Option<PitLoss>,
+    timer: PitLaneTimer,
+}
+
+impl PitLossTracker {
+    pub fn new(cache_dir: Option<PathBuf>) -> Self {
+        if cache_dir.is_none() {
+            eprintln!("No cache directory for pit losses, so they won't be kept between sessions");
+        }
+        Self {
+            cache_dir,
+            track_id: None,
+            pit_loss: None,
+            timer: PitLaneTimer::new(),
+        }
+    }
+
+    pub fn pit_loss(&self) -> Option<&PitLoss> {
+        self.pit_loss.as_ref()
+    }
+
+    pub fn update(&mut self, telemetry: &IRacingTelemetry, session: Option<&SessionDetails>) {
+        let Some(session) = session else {
+            return;
+        };
+        let track_id = session.weekend_info.track_id;
+        if self.track_id != Some(track_id) {
+            self.track_id = Some(track_id);
+            self.pit_loss = self.cache_dir.as_ref().and_then(|dir| PitLoss::load(dir, track_id).ok());
+            self.timer = PitLaneTimer::new();
+        }
+        let Some(session_time) = telemetry.double("SessionTime") else {
+            return;
+        };
+        let cars: Vec<CarPosition> = CarPosition::all(telemetry)
+            .into_iter()
+            .filter(|car| session.driver(car.car_idx).is_some_and(|driver| driver.is_racing()))
+            .collect();
+        let losses = self.timer.update(session_time, &cars, |car_idx| gaps::est_lap_time(session, car_idx));
+        if losses.is_empty() {
+            return;
+        }
+        let pit_loss = self.pit_loss.get_or_insert_with(|| PitLoss {
+            track_id,
+            ..Default::default()
+        });
+        for loss in losses {
+            pit_loss.add(loss);
+        }
+        if let Some(Err(e)) = self.cache_dir.as_ref().map(|dir| pit_loss.save(dir)) {
+            eprintln!("Couldn't cache the pit loss: {}", e);
+        }
+    }
+}
+
+/// Where the player would be if they pitted now.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rejoin {
+    /// Race position among the cars given.
+    pub position: usize,
+    /// The closest cars on track after the stop, as from `gaps::closest`.
+    pub ahead: Vec<Gap>,
+    pub behind: Vec<Gap>,
+}
+
+/// Work out where `player` would rejoin after losing `pit_loss` seconds in the pits.
+pub fn rejoin(player: &CarPosition, cars: &[CarPosition], lap_time: f32, pit_loss: f32, count: usize) -> Rejoin {
+    let after: Vec<Gap> = gaps::gaps(player, cars, lap_time)
+        .into_iter()
+        .map(|gap| {
+            let race = gap.laps as f32 * lap_time + gap.seconds + pit_loss;
+            let seconds = gap.seconds + pit_loss;
+            let seconds = seconds - (seconds / lap_time).round() * lap_time;
+            Gap {
+                seconds,
+                laps: ((race - seconds) / lap_time).round() as i32,
+                ..gap
+            }
+        })
+        .collect();
+    let position = 1 + after
+        .iter()
+        .filter(|gap| gap.laps as f32 * lap_time + gap.seconds > 0.)
+        .count();
+    let (ahead, behind) = gaps::closest(&after, count);
+    Rejoin {
+        position,
+        ahead,
+        behind,
+    }
+}
+
+/// Where the player would rejoin among the racing cars, leaving out the pace car and spectators.
+pub fn rejoin_now(telemetry: &IRacingTelemetry, session: &SessionDetails, pit_loss: f32, count: usize) -> Option<Rejoin> {
+    let player_idx = usize::try_from(telemetry.int("PlayerCarIdx")?).ok()?;
+    let cars: Vec<CarPosition> = CarPosition::all(telemetry)
+        .into_iter()
+        .filter(|car| {
+            car.car_idx == player_idx || session.driver(car.car_idx).is_some_and(|d| d.is_racing())
+        })
+        .collect();
+    let player = cars.iter().find(|car| car.car_idx == player_idx)?;
+    let lap_time = gaps::est_lap_time(session, player_idx);
+    if lap_time <= 0. {
+        return None;
+    }
+    Some(rejoin(player, &cars, lap_time, pit_loss, count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::{IRacingLogging, ScriptedSource, TelemetryValue};
+    use crate::test_support::{cache_dir, car};
+    use std::time::Duration;
+
+    const SESSION: &str = "---
+WeekendInfo:
+ TrackID: 7
+DriverInfo:
+ DriverCarIdx: 0
+ DriverCarEstLapTime: 100.0000
+ Drivers:
+ - CarIdx: 0
+   UserName: Player
+...
+";
+    const STEP: f64 = 0.1;
+
+    /// A car on a 100 second lap that comes into the pits at 0.95 of the way
+    /// round, drives the lane at half speed, stops in its box on the line for
+    /// 20 seconds and leaves at 0.05, losing 10 seconds to the lane.
+    fn pit_stop() -> Vec<(f64, CarPosition)> {
+        let mut samples = Vec::new();
+        let mut distance = 2.8f64;
+        for i in 0..800 {
+            let session_time = i as f64 * STEP;
+            let in_lane = (2.95..3.05).contains(&distance);
+            let stopped = (25. ..45.).contains(&session_time);
+            let mut position = car(0, distance.floor() as i32, distance.fract() as f32);
+            position.on_pit_road = in_lane;
+            samples.push((session_time, position));
+            distance += match (in_lane, stopped) {
+                (_, true) => 0.,
+                (true, false) => 0.005 * STEP,
+                (false, false) => 0.01 * STEP,
+            };
+        }
+        samples
+    }
+
+    fn time(timer: &mut PitLaneTimer, samples: &[(f64, CarPosition)]) -> Vec<f32> {
+        samples
+            .iter()
+            .flat_map(|(session_time, car)| timer.update(*session_time, &[*car], |_| 100.))
+            .collect()
+    }
+
+    #[test]
+    fn measures_the_pit_lane_loss() {
+        let losses = time(&mut PitLaneTimer::new(), &pit_stop());
+        assert_eq!(losses.len(), 1);
+        assert!((losses[0] - 10.).abs() < 0.3, "{} isn't 10s", losses[0]);
+    }
+
+    #[test]
+    fn ignores_cars_that_start_in_the_pits() {
+        // Starting from in the box
+        let samples: Vec<_> = pit_stop().into_iter().filter(|(session_time, _)| *session_time >= 30.).collect();
+        assert!(time(&mut PitLaneTimer::new(), &samples).is_empty());
+    }
+
+    #[test]
+    fn ignores_tows_to_the_pits() {
+        // Jumping straight from out on track into the box
+        let samples: Vec<_> = pit_stop()
+            .into_iter()
+            .filter(|(session_time, _)| *session_time < 10. || *session_time >= 30.)
+            .map(|(session_time, car)| (session_time - if session_time >= 30. { 19.9 } else { 0. }, car))
+            .collect();
+        assert!(time(&mut PitLaneTimer::new(), &samples).is_empty());
+    }
+
+    #[test]
+    fn learns_and_caches_the_loss_per_track() {
+        let frames = pit_stop()
+            .into_iter()
+            .map(|(session_time, car)| {
+                HashMap::from([
+                    ("PlayerCarIdx", TelemetryValue::Int(0)),
+                    ("SessionTime", TelemetryValue::Double(session_time)),
+                    ("CarIdxLap", TelemetryValue::IntVec(vec![car.lap])),
+                    ("CarIdxLapDistPct", TelemetryValue::FloatVec(vec![car.lap_dist_pct])),
+                    ("CarIdxEstTime", TelemetryValue::FloatVec(vec![car.est_time])),
+                    ("CarIdxOnPitRoad", TelemetryValue::BoolVec(vec![car.on_pit_road])),
+                ])
+            })
+            .collect::<Vec<_>>();
+        let count = frames.len();
+        let source = ScriptedSource::new(frames).with_session_info(SESSION);
+        let mut logging = IRacingLogging::with_source(Box::new(source), VARIABLES);
+
+        let dir = cache_dir("pit-loss");
+        let mut tracker = PitLossTracker::new(Some(dir.clone()));
+        for _ in 0..count {
+            assert!(logging.read_telemetry(Duration::ZERO));
+            tracker.update(&logging.telemetry, logging.session.as_deref());
+        }
+        let learned = tracker.pit_loss().expect("no pit loss learned").clone();
+        assert_eq!(learned.track_id, 7);
+        assert_eq!(learned.transits, 1);
+        assert!((learned.lane_loss - 10.).abs() < 0.3);
+
+        assert_eq!(PitLoss::load(&dir, 7).unwrap(), learned);
+        assert!(PitLoss::load(&dir, 8).is_err());
+        let mut tracker = PitLossTracker::new(Some(dir.clone()));
+        assert!(logging.read_telemetry(Duration::ZERO));
+        tracker.update(&logging.telemetry, logging.session.as_deref());
+        assert_eq!(tracker.pit_loss(), Some(&learned));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn averages_transits() {
+        let mut pit_loss = PitLoss::default();
+        pit_loss.add(10.);
+        pit_loss.add(14.);
+        assert_eq!(pit_loss.transits, 2);
+        assert!((pit_loss.lane_loss - 12.).abs() < 1e-3);
+        for _ in 0..100 {
+            pit_loss.add(20.);
+        }
+        assert_eq!(pit_loss.transits, MAX_TRANSITS);
+        assert!((pit_loss.lane_loss - 20.).abs() < 0.1);
+    }
+
+    #[test]
+    fn rejoin_position_and_neighbours() {
+        let player = car(0, 5, 0.50);
+        let cars = [
+            player,
+            car(1, 5, 0.55),
+            car(2, 5, 0.40),
+            car(3, 5, 0.20),
+            // A lap down, just behind on track
+            car(4, 4, 0.45),
+        ];
+        let rejoin = rejoin(&player, &cars, 100., 20., 1);
+        assert_eq!(rejoin.position, 3);
+        assert_eq!(rejoin.ahead.len(), 1);
+        assert_eq!(rejoin.ahead[0].car_idx, 2);
+        assert!((rejoin.ahead[0].seconds - 10.).abs() < 1e-3);
+        assert_eq!(rejoin.behind[0].car_idx, 3);
+        assert!((rejoin.behind[0].seconds + 10.).abs() < 1e-3);
+
+        let rejoin = super::rejoin(&player, &cars, 100., 20., 4);
+        let lapped = rejoin.ahead.iter().find(|gap| gap.car_idx == 4).unwrap();
+        assert!((lapped.seconds - 15.).abs() < 1e-3);
+        assert_eq!(lapped.laps, -1);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::gaps::CarPosition;
+use crate::session::SessionDetails;
+use crate::telemetry::{IRacingTelemetry, Variable, VariableType};
+
+/// The telemetry needed for the radar.
+pub const VARIABLES: &[Variable] = &[
+    Variable::new("PlayerCarIdx", VariableType::Int),
+    Variable::new("CarLeftRight", VariableType::Int),
+    Variable::new("CarIdxLap", VariableType::IntArray),
+    Variable::new("CarIdxLapDistPct", VariableType::FloatArray),
+    Variable::new("CarIdxOnPitRoad", VariableType::BoolArray),
+];
+
+/// Roughly the length of a car in metres, so how close another has to be to overlap.
+pub const CAR_LENGTH: f32 = 5.;
+
+/// iRacing's spotter, from `CarLeftRight`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Alongside {
+    /// The spotter isn't running, e.g. in the garage.
+    #[default]
+    Off,
+    Clear,
+    Left,
+    Right,
+    LeftAndRight,
+    TwoLeft,
+    TwoRight,
+}
+
+impl Alongside {
+    pub fn from_telemetry(telemetry: &IRacingTelemetry) -> Self {
+        match telemetry.int("CarLeftRight") {
+            Some(1) => Alongside::Clear,
+            Some(2) => Alongside::Left,
+            Some(3) => Alongside::Right,
+            Some(4) => Alongside::LeftAndRight,
+            Some(5) => Alongside::TwoLeft,
+            Some(6) => Alongside::TwoRight,
+            _ => Alongside::Off,
+        }
+    }
+
+    /// How many cars are alongside on the (left, right).
+    pub fn counts(self) -> (usize, usize) {
+        match self {
+            Alongside::Off | Alongside::Clear => (0, 0),
+            Alongside::Left => (1, 0),
+            Alongside::Right => (0, 1),
+            Alongside::LeftAndRight => (1, 1),
+            Alongside::TwoLeft => (2, 0),
+            Alongside::TwoRight => (0, 2),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// A car near the player, as drawn on the radar.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RadarCar {
+    pub car_idx: usize,
+    /// Metres ahead of (positive) or behind (negative) the player.
+    pub distance: f32,
+    /// Which side it's on, when the spotter says there's a car there.
+    pub side: Option<Side>,
+}
+
+/// Every car within `range` metres of `player` along the track.
+pub fn nearby(player: &CarPosition, cars: &[CarPosition], track_length: f32, range: f32) -> Vec<(usize, f32)> {
+    cars.iter()
+        .filter(|car| car.car_idx != player.car_idx)
+        .map(|car| {
+            let delta = car.lap_dist_pct - player.lap_dist_pct;
+            (car.car_idx, (delta - delta.round()) * track_length)
+        })
+        .filter(|(_, distance)| distance.abs() <= range)
+        .collect()
+}
+
+/// Place the nearby cars on the radar, putting the closest overlapping ones
+/// on the sides the spotter says cars are alongside.
+pub fn place(mut nearby: Vec<(usize, f32)>, alongside: Alongside) -> Vec<RadarCar> {
+    nearby.sort_by(|a, b| a.1.abs().total_cmp(&b.1.abs()));
+    let (mut left, mut right) = alongside.counts();
+    nearby
+        .into_iter()
+        .map(|(car_idx, distance)| {
+            let side = if distance.abs() > CAR_LENGTH * 1.5 {
+                None
+            } else if left > 0 {
+                left -= 1;
+                Some(Side::Left)
+            } else if right > 0 {
+                right -= 1;
+                Some(Side::Right)
+            } else {
+                None
+            };
+            RadarCar {
+                car_idx,
+                distance,
+                side,
+            }
+        })
+        .collect()
+}
+
+/// The spotter and the cars within `range` metres of the player, leaving out
+/// the pace car, spectators and cars in the pits.
+pub fn radar(telemetry: &IRacingTelemetry, session: &SessionDetails, range: f32) -> Option<(Alongside, Vec<RadarCar>)> {
+    let player_idx = usize::try_from(telemetry.int("PlayerCarIdx")?).ok()?;
+    let track_length = session.weekend_info.track_length_km()? * 1000.;
+    let cars: Vec<CarPosition> = CarPosition::all(telemetry)
+        .into_iter()
+        .filter(|car| {
+            car.car_idx == player_idx
+                || (!car.on_pit_road && session.driver(car.car_idx).is_some_and(|d| d.is_racing()))
+        })
+        .collect();
+    let player = cars.iter().find(|car| car.car_idx == player_idx)?;
+    let alongside = Alongside::from_telemetry(telemetry);
+    Some((alongside, place(nearby(player, &cars, track_length, range), alongside)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::car;
+
+    #[test]
+    fn nearby_cars_across_the_line() {
+        // 1km lap, so a thousandth of a lap is a metre
+        let cars = [car(0, 1, 0.998), car(1, 1, 0.003), car(2, 1, 0.990), car(3, 1, 0.5)];
+        let mut nearby = nearby(&cars[0], &cars, 1000., 10.);
+        nearby.sort_by_key(|(car_idx, _)| *car_idx);
+        assert_eq!(nearby.len(), 2);
+        assert_eq!(nearby[0].0, 1);
+        assert!((nearby[0].1 - 5.).abs() < 0.01);
+        assert_eq!(nearby[1].0, 2);
+        assert!((nearby[1].1 + 8.).abs() < 0.01);
+    }
+
+    #[test]
+    fn alongside_cars_go_to_the_spotters_side() {
+        let placed = place(vec![(1, 12.), (2, -1.), (3, 3.)], Alongside::LeftAndRight);
+        assert_eq!(placed[0], RadarCar { car_idx: 2, distance: -1., side: Some(Side::Left) });
+        assert_eq!(placed[1].side, Some(Side::Right));
+        // Too far ahead to be alongside
+        assert_eq!(placed[2].side, None);
+    }
+
+    #[test]
+    fn no_sides_when_clear() {
+        let placed = place(vec![(1, 2.), (2, -3.)], Alongside::Clear);
+        assert!(placed.iter().all(|car| car.side.is_none()));
+        let placed = place(vec![(1, 2.), (2, -3.)], Alongside::TwoRight);
+        assert!(placed.iter().all(|car| car.side == Some(Side::Right)));
+    }
+
+    #[test]
+    fn counts_cars_alongside() {
+        assert_eq!(Alongside::Off.counts(), (0, 0));
+        assert_eq!(Alongside::Left.counts(), (1, 0));
+        assert_eq!(Alongside::TwoLeft.counts(), (2, 0));
+        assert_eq!(Alongside::LeftAndRight.counts(), (1, 1));
+    }
+}
@@ -0,0 +1,313 @@
+//! Every overlay, listed once. The main app's pages, the config file and the
+//! overlay processes are all generated from the list below, so adding an
+//! overlay means writing its widget and adding it here.
+
+use crate::telemetry::{TelemetrySnapshot, Variable};
+use crate::util::HomeConfig;
+use crate::widgets::{self, Overlay, Recorder};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// What a settings page changed this frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettingsChange {
+    Unchanged,
+    /// A setting was edited, e.g. a colour picked or a number dragged.
+    Edited,
+    /// The overlay's window was switched on or off.
+    Switched,
+}
+
+/// An overlay with its place in the registry, so it can find its settings in
+/// the config file.
+pub trait Registered: Overlay {
+    const ID: Overlays;
+
+    fn config(config: &WindowsConfig) -> &Self::Config;
+}
+
+macro_rules! overlays {
+    ($($id:ident => $module:ident::$widget:ident, $field:ident;)*) => {
+        /// The main app's pages: home, one per overlay, and the log.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+        pub enum Overlays {
+            Home,
+            $($id,)*
+            Log,
+        }
+
+        impl Overlays {
+            /// Every overlay that runs as its own window.
+            pub const WINDOWS: &'static [Overlays] = &[$(Overlays::$id),*];
+
+            /// The name shown on the page's button and the overlay's window.
+            pub fn title(self) -> &'static str {
+                match self {
+                    Overlays::Home => "Home",
+                    Overlays::Log => "Log",
+                    $(Overlays::$id => <widgets::$module::$widget as Overlay>::TITLE,)*
+                }
+            }
+
+            /// The name of the overlay's executable, for the pages that are overlays.
+            pub fn binary(self) -> Option<&'static str> {
+                match self {
+                    Overlays::Home | Overlays::Log => None,
+                    $(Overlays::$id => Some(<widgets::$module::$widget as Overlay>::BINARY),)*
+                }
+            }
+        }
+
+        // Overlays added later fall back to their defaults in existing config files
+        #[derive(Serialize, Deserialize, Default)]
+        #[serde(default)]
+        pub struct WindowsConfig {
+            /// Folder holding the overlay executables, instead of the app's own
+            pub overlay_dir: Option<PathBuf>,
+            pub home_config: HomeConfig,
+            $(pub $field: <widgets::$module::$widget as Overlay>::Config,)*
+        }
+
+        impl WindowsConfig {
+            /// The "Show window" setting of an overlay.
+            pub fn display_mut(&mut self, overlay: Overlays) -> Option<&mut bool> {
+                match overlay {
+                    Overlays::Home | Overlays::Log => None,
+                    $(Overlays::$id => Some(&mut self.$field.display),)*
+                }
+            }
+
+            /// Show an overlay's settings, and say what was changed.
+            pub fn settings_ui(&mut self, overlay: Overlays, ui: &mut egui::Ui) -> SettingsChange {
+                match overlay {
+                    Overlays::Home | Overlays::Log => SettingsChange::Unchanged,
+                    $(Overlays::$id => {
+                        let config = &mut self.$field;
+                        let switched = widgets::display_setting(ui, &mut config.display);
+                        let mut edited = widgets::font_size_setting(ui, &mut config.font_size);
+                        edited |= <widgets::$module::$widget as Overlay>::settings(ui, config);
+                        edited |= widgets::colour_settings(ui, &mut config.overlay_bgcolour, &mut config.overlay_fontcolour);
+                        if switched {
+                            SettingsChange::Switched
+                        } else if edited {
+                            SettingsChange::Edited
+                        } else {
+                            SettingsChange::Unchanged
+                        }
+                    })*
+                }
+            }
+        }
+
+        /// Every overlay, for hosting them all in one process.
+        pub struct Widgets {
+            $($module: widgets::$module::$widget,)*
+        }
+
+        impl Widgets {
+            pub fn new(config: &WindowsConfig) -> Self {
+                Self {
+                    $($module: Overlay::new(&config.$field),)*
+                }
+            }
+
+            /// The telemetry every overlay needs between them.
+            pub fn variables() -> Vec<Variable> {
+                let mut variables: Vec<Variable> = Vec::new();
+                let all = [$(<widgets::$module::$widget as Overlay>::variables()),*];
+                for variable in all.into_iter().flatten() {
+                    if !variables.iter().any(|known| known.name == variable.name) {
+                        variables.push(variable);
+                    }
+                }
+                variables
+            }
+
+            /// Records every sample for the overlays that keep their own history.
+            pub fn recorder(&self) -> impl Fn(&TelemetrySnapshot) + Send + 'static {
+                let recorders: Vec<Recorder> = [$(self.$module.recorder()),*].into_iter().flatten().collect();
+                move |snapshot| {
+                    for recorder in &recorders {
+                        recorder(snapshot);
+                    }
+                }
+            }
+
+            /// Draw `overlay` into `ui`, filling it.
+            pub fn show(&self, overlay: Overlays, ui: &mut egui::Ui, config: &WindowsConfig, snapshot: &TelemetrySnapshot) {
+                match overlay {
+                    Overlays::Home | Overlays::Log => {}
+                    $(Overlays::$id => self.$module.show(ui, &config.$field, snapshot),)*
+                }
+            }
+
+            /// Apply a changed config to every overlay.
+            pub fn reconfigure(&self, config: &WindowsConfig) {
+                $(self.$module.reconfigure(&config.$field);)*
+            }
+        }
+
+        /// A single overlay, for previewing it without building all the others.
+        pub enum Widget {
+            $($id(widgets::$module::$widget),)*
+        }
+
+        impl Widget {
+            /// The overlay on the page `overlay`, for the pages that are overlays.
+            pub fn new(overlay: Overlays, config: &WindowsConfig) -> Option<Self> {
+                match overlay {
+                    Overlays::Home | Overlays::Log => None,
+                    $(Overlays::$id => Some(Widget::$id(Overlay::new(&config.$field))),)*
+                }
+            }
+
+            pub fn overlay(&self) -> Overlays {
+                match self {
+                    $(Widget::$id(_) => Overlays::$id,)*
+                }
+            }
+
+            /// The telemetry the overlay reads.
+            pub fn variables(&self) -> Vec<Variable> {
+                match self {
+                    $(Widget::$id(_) => <widgets::$module::$widget as Overlay>::variables(),)*
+                }
+            }
+
+            pub fn recorder(&self) -> Option<Recorder> {
+                match self {
+                    $(Widget::$id(widget) => widget.recorder(),)*
+                }
+            }
+
+            /// Draw the overlay into `ui`, filling it.
+            pub fn show(&self, ui: &mut egui::Ui, config: &WindowsConfig, snapshot: &TelemetrySnapshot) {
+                match self {
+                    $(Widget::$id(widget) => widget.show(ui, &config.$field, snapshot),)*
+                }
+            }
+
+            pub fn reconfigure(&self, config: &WindowsConfig) {
+                match self {
+                    $(Widget::$id(widget) => widget.reconfigure(&config.$field),)*
+                }
+            }
+        }
+
+        $(
+            impl Registered for widgets::$module::$widget {
+                const ID: Overlays = Overlays::$id;
+
+                fn config(config: &WindowsConfig) -> &Self::Config {
+                    &config.$field
+                }
+            }
+        )*
+
+        #[cfg(test)]
+        fn each_variables() -> Vec<Vec<Variable>> {
+            vec![$(<widgets::$module::$widget as Overlay>::variables()),*]
+        }
+    };
+}
+
+// The field names are what the config file has always called them
+overlays! {
+    Speed => speed::SpeedWidget, speed_config;
+    LastLapTime => lastlaptime::LastLapTimeWidget, lastlaptime_config;
+    Relative => relative::RelativeWidget, relative_config;
+    Standings => standings::StandingsWidget, standings_config;
+    Fuel => fuel::FuelWidget, fuel_config;
+    Inputs => inputs::InputsWidget, inputs_config;
+    Delta => delta::DeltaWidget, delta_config;
+    TrackMap => trackmap::TrackMapWidget, trackmap_config;
+    Tyres => tyres::TyresWidget, tyres_config;
+    PitStrategy => pitstrategy::PitStrategyWidget, pitstrategy_config;
+    LapHistory => laphistory::LapHistoryWidget, laphistory_config;
+    ShiftLights => shiftlights::ShiftLightsWidget, shiftlights_config;
+    Flags => flags::FlagsWidget, flags_config;
+    Radar => radar::RadarWidget, radar_config;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_window_has_a_binary_and_display_toggle() {
+        let mut config = WindowsConfig::default();
+        for &overlay in Overlays::WINDOWS {
+            assert!(overlay.binary().is_some(), "{:?}", overlay);
+            assert!(config.display_mut(overlay).is_some(), "{:?}", overlay);
+        }
+        assert_eq!(Overlays::Home.binary(), None);
+        assert!(config.display_mut(Overlays::Log).is_none());
+    }
+
+    #[test]
+    fn every_window_builds_on_its_own() {
+        let config = WindowsConfig::default();
+        for &overlay in Overlays::WINDOWS {
+            let widget = Widget::new(overlay, &config).expect("no widget");
+            assert_eq!(widget.overlay(), overlay);
+        }
+        assert!(Widget::new(Overlays::Home, &config).is_none());
+        assert!(Widget::new(Overlays::Log, &config).is_none());
+    }
+
+    #[test]
+    fn binaries_and_titles_are_unique() {
+        for (i, overlay) in Overlays::WINDOWS.iter().enumerate() {
+            for other in &Overlays::WINDOWS[i + 1..] {
+                assert_ne!(overlay.binary(), other.binary());
+                assert_ne!(overlay.title(), other.title());
+            }
+        }
+    }
+
+    #[test]
+    fn config_keeps_its_field_names() {
+        let text = toml::to_string(&WindowsConfig::default()).unwrap();
+        for field in ["speed_config", "lastlaptime_config", "shiftlights_config", "radar_config"] {
+            assert!(text.contains(&format!("[{}]", field)), "{} missing", field);
+        }
+    }
+
+    #[test]
+    fn variables_are_shared_without_repeats() {
+        let variables = Widgets::variables();
+        for (i, variable) in variables.iter().enumerate() {
+            assert!(
+                !variables[i + 1..].iter().any(|other| other.name == variable.name),
+                "{} is repeated",
+                variable.name
+            );
+        }
+        for name in ["Speed", "RPM", "SessionFlags", "CarLeftRight", "LFtempCM", "FuelLevel"] {
+            assert!(variables.iter().any(|variable| variable.name == name), "{} missing", name);
+        }
+    }
+
+    #[test]
+    fn repeated_variables_agree_on_their_type() {
+        let variables = Widgets::variables();
+        for variable in each_variables().iter().flatten() {
+            let shared = variables.iter().find(|shared| shared.name == variable.name).unwrap();
+            assert_eq!(shared.kind, variable.kind, "{}", variable.name);
+        }
+    }
+
+    #[test]
+    fn untouched_settings_are_unchanged() {
+        let mut config = WindowsConfig::default();
+        let ctx = egui::Context::default();
+        let _ = ctx.run(Default::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                for &overlay in Overlays::WINDOWS {
+                    assert_eq!(config.settings_ui(overlay, ui), SettingsChange::Unchanged, "{:?}", overlay);
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,356 @@
+use serde::{Deserialize, Deserializer};
+
+/// Session info published by iRacing as YAML alongside the telemetry. Only the
+/// parts the overlays use are read, and anything missing is left at its default.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct SessionDetails {
+    pub weekend_info: WeekendInfo,
+    pub session_info: SessionInfo,
+    pub driver_info: DriverInfo,
+    pub split_time_info: SplitTimeInfo,
+}
+
+impl SessionDetails {
+    pub fn parse(yaml: &str) -> Result<Self, serde_yaml::Error> {
+        serde_yaml::from_str(yaml)
+    }
+
+    /// The session currently running, given the `SessionNum` telemetry variable.
+    pub fn session(&self, session_num: i32) -> Option<&Session> {
+        self.session_info
+            .sessions
+            .iter()
+            .find(|session| session.session_num == session_num)
+    }
+
+    pub fn driver(&self, car_idx: usize) -> Option<&Driver> {
+        self.driver_info
+            .drivers
+            .iter()
+            .find(|driver| driver.car_idx == car_idx)
+    }
+
+    pub fn player(&self) -> Option<&Driver> {
+        self.driver(self.driver_info.driver_car_idx)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct WeekendInfo {
+    pub track_name: String,
+    #[serde(rename = "TrackID")]
+    pub track_id: i32,
+    /// e.g. "5.51 km"
+    pub track_length: String,
+    pub track_display_name: String,
+    pub track_config_name: String,
+    pub track_type: String,
+    #[serde(rename = "SeriesID")]
+    pub series_id: i32,
+    #[serde(rename = "SessionID")]
+    pub session_id: i32,
+    #[serde(rename = "SubSessionID")]
+    pub sub_session_id: i32,
+    pub event_type: String,
+    pub category: String,
+    pub official: i32,
+    pub team_racing: i32,
+    pub num_car_classes: i32,
+    pub weekend_options: WeekendOptions,
+}
+
+impl WeekendInfo {
+    pub fn track_length_km(&self) -> Option<f32> {
+        self.track_length.split_whitespace().next()?.parse().ok()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct WeekendOptions {
+    pub num_starters: i32,
+    pub starting_grid: String,
+    pub standing_start: i32,
+    pub restarts: String,
+    pub course_cautions: String,
+    pub incident_limit: String,
+    pub fast_repairs_limit: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct SessionInfo {
+    pub sessions: Vec<Session>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct Session {
+    pub session_num: i32,
+    /// A lap count, or "unlimited"
+    pub session_laps: String,
+    /// e.g. "3600.0000 sec", or "unlimited"
+    pub session_time: String,
+    pub session_type: String,
+    pub session_name: String,
+    pub results_positions: Option<Vec<ResultPosition>>,
+    pub results_fastest_lap: Option<Vec<ResultFastestLap>>,
+    pub results_laps_complete: i32,
+    pub results_official: i32,
+}
+
+impl Session {
+    /// The lap limit, or `None` if the session isn't limited by laps.
+    pub fn laps(&self) -> Option<u32> {
+        self.session_laps.trim().parse().ok()
+    }
+
+    /// The time limit in seconds, or `None` if the session isn't limited by time.
+    pub fn time_limit(&self) -> Option<f32> {
+        self.session_time.split_whitespace().next()?.parse().ok()
+    }
+
+    pub fn results(&self) -> &[ResultPosition] {
+        self.results_positions.as_deref().unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct ResultPosition {
+    pub position: i32,
+    pub class_position: i32,
+    pub car_idx: usize,
+    pub lap: i32,
+    pub time: f32,
+    pub fastest_lap: i32,
+    pub fastest_time: f32,
+    pub last_time: f32,
+    pub laps_led: i32,
+    pub laps_complete: i32,
+    pub laps_driven: f32,
+    pub incidents: i32,
+    pub reason_out_id: i32,
+    pub reason_out_str: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct ResultFastestLap {
+    pub car_idx: usize,
+    pub fastest_lap: i32,
+    pub fastest_time: f32,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct DriverInfo {
+    pub driver_car_idx: usize,
+    #[serde(rename = "DriverCarIdleRPM")]
+    pub driver_car_idle_rpm: f32,
+    pub driver_car_red_line: f32,
+    pub driver_car_fuel_kg_per_ltr: f32,
+    pub driver_car_fuel_max_ltr: f32,
+    pub driver_car_max_fuel_pct: f32,
+    #[serde(rename = "DriverCarSLFirstRPM")]
+    pub driver_car_sl_first_rpm: f32,
+    #[serde(rename = "DriverCarSLShiftRPM")]
+    pub driver_car_sl_shift_rpm: f32,
+    #[serde(rename = "DriverCarSLLastRPM")]
+    pub driver_car_sl_last_rpm: f32,
+    #[serde(rename = "DriverCarSLBlinkRPM")]
+    pub driver_car_sl_blink_rpm: f32,
+    pub driver_pit_trk_pct: f32,
+    pub driver_car_est_lap_time: f32,
+    pub driver_incident_count: i32,
+    pub drivers: Vec<Driver>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct Driver {
+    pub car_idx: usize,
+    pub user_name: String,
+    pub abbrev_name: String,
+    pub initials: String,
+    #[serde(rename = "UserID")]
+    pub user_id: i64,
+    pub team_name: String,
+    pub car_number: String,
+    pub car_number_raw: i32,
+    #[serde(rename = "CarID")]
+    pub car_id: i32,
+    pub car_screen_name: String,
+    pub car_screen_name_short: String,
+    #[serde(rename = "CarClassID")]
+    pub car_class_id: i32,
+    pub car_class_short_name: String,
+    pub car_class_rel_speed: i32,
+    pub car_class_est_lap_time: f32,
+    #[serde(deserialize_with = "deserialize_colour")]
+    pub car_class_color: u32,
+    pub car_is_pace_car: i32,
+    #[serde(rename = "CarIsAI")]
+    pub car_is_ai: i32,
+    pub is_spectator: i32,
+    #[serde(rename = "IRating")]
+    pub i_rating: i32,
+    pub lic_level: i32,
+    pub lic_sub_level: i32,
+    pub lic_string: String,
+    #[serde(deserialize_with = "deserialize_colour")]
+    pub lic_color: u32,
+    pub club_name: String,
+    pub division_name: String,
+}
+
+impl Driver {
+    /// Whether the entry is a real car in the session, rather than the pace car or a spectator.
+    pub fn is_racing(&self) -> bool {
+        self.car_is_pace_car == 0 && self.is_spectator == 0
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct SplitTimeInfo {
+    pub sectors: Vec<Sector>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct Sector {
+    pub sector_num: usize,
+    pub sector_start_pct: f32,
+}
+
+/// Colours are written as hex, e.g. `0xffda59`, which may come through as a number or a string.
+fn deserialize_colour<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let raw = String::deserialize(deserializer)?;
+    let digits = raw.trim().trim_start_matches("0x").trim_start_matches("0X");
+    u32::from_str_radix(digits, 16).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SESSION: &str = r#"---
+WeekendInfo:
+ TrackName: spa 2022 gp
+ TrackID: 524
+ TrackLength: 6.93 km
+ TrackDisplayName: Circuit de Spa-Francorchamps
+ NumCarClasses: 2
+SessionInfo:
+ Sessions:
+ - SessionNum: 0
+   SessionLaps: unlimited
+   SessionTime: 1800.0000 sec
+   SessionType: Practice
+   ResultsPositions:
+ - SessionNum: 2
+   SessionLaps: 20
+   SessionTime: unlimited
+   SessionType: Race
+   ResultsPositions:
+   - Position: 1
+     ClassPosition: 0
+     CarIdx: 2
+     FastestTime: 137.812
+DriverInfo:
+ DriverCarIdx: 2
+ DriverCarRedLine: 8500.000
+ Drivers:
+ - CarIdx: 0
+   UserName: Pace Car
+   CarIsPaceCar: 1
+   CarClassColor: 0xffffff
+ - CarIdx: 1
+   UserName: Someone Watching
+   IsSpectator: 1
+   CarClassColor: 0x000000
+ - CarIdx: 2
+   UserName: Jo Bloggs
+   CarNumber: "42"
+   CarClassID: 4074
+   CarClassShortName: GT3
+   CarClassColor: 0xffda59
+   LicColor: 0x00c702
+   IRating: 2150
+ - CarIdx: 3
+   UserName: A N Other
+   CarClassShortName: GT4
+   CarClassColor: "0x33ceff"
+SplitTimeInfo:
+ Sectors:
+ - SectorNum: 0
+   SectorStartPct: 0.000000
+ - SectorNum: 1
+   SectorStartPct: 0.333000
+...
+"#;
+
+    #[test]
+    fn reads_a_session() {
+        let details = SessionDetails::parse(SESSION).unwrap();
+        assert_eq!(details.weekend_info.track_id, 524);
+        assert_eq!(details.weekend_info.track_length_km(), Some(6.93));
+        assert_eq!(details.player().unwrap().user_name, "Jo Bloggs");
+        assert_eq!(details.player().unwrap().i_rating, 2150);
+        assert_eq!(details.split_time_info.sectors[1].sector_start_pct, 0.333);
+
+        let race = details.session(2).unwrap();
+        assert_eq!(race.laps(), Some(20));
+        assert_eq!(race.time_limit(), None);
+        assert_eq!(race.results()[0].car_idx, 2);
+        let practice = details.session(0).unwrap();
+        assert_eq!(practice.laps(), None);
+        assert_eq!(practice.time_limit(), Some(1800.));
+        assert!(practice.results().is_empty());
+        assert!(details.session(1).is_none());
+    }
+
+    #[test]
+    fn reads_colours_as_hex() {
+        let details = SessionDetails::parse(SESSION).unwrap();
+        let player = details.player().unwrap();
+        assert_eq!(player.car_class_color, 0xffda59);
+        assert_eq!(player.lic_color, 0x00c702);
+        // Quoted the same as unquoted
+        assert_eq!(details.driver(3).unwrap().car_class_color, 0x33ceff);
+        assert!(SessionDetails::parse("DriverInfo:\n Drivers:\n - CarClassColor: 0xnothex\n").is_err());
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let details = SessionDetails::parse(SESSION).unwrap();
+        // Not in the YAML at all
+        assert_eq!(details.weekend_info.weekend_options, WeekendOptions::default());
+        assert_eq!(details.driver_info.driver_car_fuel_max_ltr, 0.);
+        let other = details.driver(3).unwrap();
+        assert_eq!(other.car_number, "");
+        assert_eq!(other.lic_color, 0);
+        assert_eq!(other.is_spectator, 0);
+
+        let sparse = SessionDetails::parse("---\nWeekendInfo:\n TrackID: 1\n...\n").unwrap();
+        assert_eq!(sparse.weekend_info.track_id, 1);
+        assert_eq!(sparse.driver_info, DriverInfo::default());
+        assert!(sparse.player().is_none());
+    }
+
+    #[test]
+    fn pace_car_and_spectators_are_not_racing() {
+        let details = SessionDetails::parse(SESSION).unwrap();
+        let racing: Vec<usize> = details
+            .driver_info
+            .drivers
+            .iter()
+            .filter(|driver| driver.is_racing())
+            .map(|driver| driver.car_idx)
+            .collect();
+        assert_eq!(racing, vec![2, 3]);
+    }
+}
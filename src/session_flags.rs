@@ -0,0 +1,314 @@
+use crate::telemetry::{IRacingTelemetry, Variable, VariableType};
+
+/// The telemetry the flags overlay shows.
+pub const VARIABLES: &[Variable] = &[
+    Variable::new("SessionFlags", VariableType::Bits),
+    Variable::new("SessionTime", VariableType::Double),
+    Variable::new("PlayerCarMyIncidentCount", VariableType::Int),
+];
+
+/// One bit of iRacing's `SessionFlags`, named as in the SDK's `irsdk_Flags`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flag {
+    Checkered,
+    White,
+    Green,
+    Yellow,
+    Red,
+    Blue,
+    Debris,
+    Crossed,
+    YellowWaving,
+    OneLapToGreen,
+    GreenHeld,
+    TenToGo,
+    FiveToGo,
+    RandomWaving,
+    /// Full course yellow
+    Caution,
+    CautionWaving,
+    Black,
+    Disqualify,
+    Servicible,
+    Furled,
+    /// The meatball, for damage that needs repairing
+    Repair,
+    StartHidden,
+    StartReady,
+    StartSet,
+    StartGo,
+}
+
+impl Flag {
+    pub const ALL: [Flag; 25] = [
+        Flag::Checkered,
+        Flag::White,
+        Flag::Green,
+        Flag::Yellow,
+        Flag::Red,
+        Flag::Blue,
+        Flag::Debris,
+        Flag::Crossed,
+        Flag::YellowWaving,
+        Flag::OneLapToGreen,
+        Flag::GreenHeld,
+        Flag::TenToGo,
+        Flag::FiveToGo,
+        Flag::RandomWaving,
+        Flag::Caution,
+        Flag::CautionWaving,
+        Flag::Black,
+        Flag::Disqualify,
+        Flag::Servicible,
+        Flag::Furled,
+        Flag::Repair,
+        Flag::StartHidden,
+        Flag::StartReady,
+        Flag::StartSet,
+        Flag::StartGo,
+    ];
+
+    pub const fn bit(self) -> u32 {
+        match self {
+            // Global flags
+            Flag::Checkered => 0x0000_0001,
+            Flag::White => 0x0000_0002,
+            Flag::Green => 0x0000_0004,
+            Flag::Yellow => 0x0000_0008,
+            Flag::Red => 0x0000_0010,
+            Flag::Blue => 0x0000_0020,
+            Flag::Debris => 0x0000_0040,
+            Flag::Crossed => 0x0000_0080,
+            Flag::YellowWaving => 0x0000_0100,
+            Flag::OneLapToGreen => 0x0000_0200,
+            Flag::GreenHeld => 0x0000_0400,
+            Flag::TenToGo => 0x0000_0800,
+            Flag::FiveToGo => 0x0000_1000,
+            Flag::RandomWaving => 0x0000_2000,
+            Flag::Caution => 0x0000_4000,
+            Flag::CautionWaving => 0x0000_8000,
+            // Flags for the player's car
+            Flag::Black => 0x0001_0000,
+            Flag::Disqualify => 0x0002_0000,
+            Flag::Servicible => 0x0004_0000,
+            Flag::Furled => 0x0008_0000,
+            Flag::Repair => 0x0010_0000,
+            // Start lights
+            Flag::StartHidden => 0x1000_0000,
+            Flag::StartReady => 0x2000_0000,
+            Flag::StartSet => 0x4000_0000,
+            Flag::StartGo => 0x8000_0000,
+        }
+    }
+}
+
+/// The flag the overlay shows, when several are out at once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlagPanel {
+    Disqualified,
+    Black,
+    Meatball,
+    Red,
+    Chequered,
+    FullCourseYellow,
+    LocalYellow,
+    Blue,
+    White,
+    Debris,
+    Green,
+}
+
+/// The `SessionFlags` bitfield.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SessionFlags(pub u32);
+
+impl SessionFlags {
+    pub fn from_telemetry(telemetry: &IRacingTelemetry) -> Option<Self> {
+        telemetry.bits("SessionFlags").map(Self)
+    }
+
+    pub fn contains(self, flag: Flag) -> bool {
+        self.0 & flag.bit() != 0
+    }
+
+    /// Every flag that's set, in the order of `Flag::ALL`.
+    pub fn flags(self) -> impl Iterator<Item = Flag> {
+        Flag::ALL.into_iter().filter(move |flag| self.contains(*flag))
+    }
+
+    pub fn full_course_yellow(self) -> bool {
+        self.contains(Flag::Caution) || self.contains(Flag::CautionWaving)
+    }
+
+    /// A yellow for an incident near the player, rather than a full course yellow.
+    pub fn local_yellow(self) -> bool {
+        (self.contains(Flag::Yellow) || self.contains(Flag::YellowWaving)) && !self.full_course_yellow()
+    }
+
+    /// Any yellow, local or full course.
+    pub fn yellow(self) -> bool {
+        self.full_course_yellow() || self.local_yellow()
+    }
+
+    /// The most important flag that's out, with the player's own flags first.
+    pub fn panel(self) -> Option<FlagPanel> {
+        let panel = if self.contains(Flag::Disqualify) {
+            FlagPanel::Disqualified
+        } else if self.contains(Flag::Black) {
+            FlagPanel::Black
+        } else if self.contains(Flag::Repair) {
+            FlagPanel::Meatball
+        } else if self.contains(Flag::Red) {
+            FlagPanel::Red
+        } else if self.contains(Flag::Checkered) {
+            FlagPanel::Chequered
+        } else if self.full_course_yellow() {
+            FlagPanel::FullCourseYellow
+        } else if self.local_yellow() {
+            FlagPanel::LocalYellow
+        } else if self.contains(Flag::Blue) {
+            FlagPanel::Blue
+        } else if self.contains(Flag::White) {
+            FlagPanel::White
+        } else if self.contains(Flag::Debris) {
+            FlagPanel::Debris
+        } else if self.contains(Flag::Green) || self.contains(Flag::GreenHeld) {
+            FlagPanel::Green
+        } else {
+            return None;
+        };
+        Some(panel)
+    }
+}
+
+/// Seconds the incident count flashes for after it goes up.
+const INCIDENT_FLASH: f64 = 3.;
+
+/// Follows the player's incident count, noting when it last went up.
+#[derive(Default)]
+pub struct IncidentCount {
+    count: Option<i32>,
+    increased_at: Option<f64>,
+}
+
+impl IncidentCount {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn count(&self) -> Option<i32> {
+        self.count
+    }
+
+    pub fn update(&mut self, count: i32, session_time: f64) {
+        // The count going down means a new session, which shouldn't flash
+        if self.count.is_some_and(|last| count > last) {
+            self.increased_at = Some(session_time);
+        }
+        self.count = Some(count);
+    }
+
+    /// Whether the count went up recently enough to still be flashing.
+    pub fn flashing(&self, session_time: f64) -> bool {
+        self.increased_at
+            .is_some_and(|at| session_time >= at && session_time - at < INCIDENT_FLASH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_bit_decodes_to_its_flag() {
+        // From irsdk_Flags in the iRacing SDK
+        let expected = [
+            (0x0000_0001, Flag::Checkered),
+            (0x0000_0002, Flag::White),
+            (0x0000_0004, Flag::Green),
+            (0x0000_0008, Flag::Yellow),
+            (0x0000_0010, Flag::Red),
+            (0x0000_0020, Flag::Blue),
+            (0x0000_0040, Flag::Debris),
+            (0x0000_0080, Flag::Crossed),
+            (0x0000_0100, Flag::YellowWaving),
+            (0x0000_0200, Flag::OneLapToGreen),
+            (0x0000_0400, Flag::GreenHeld),
+            (0x0000_0800, Flag::TenToGo),
+            (0x0000_1000, Flag::FiveToGo),
+            (0x0000_2000, Flag::RandomWaving),
+            (0x0000_4000, Flag::Caution),
+            (0x0000_8000, Flag::CautionWaving),
+            (0x0001_0000, Flag::Black),
+            (0x0002_0000, Flag::Disqualify),
+            (0x0004_0000, Flag::Servicible),
+            (0x0008_0000, Flag::Furled),
+            (0x0010_0000, Flag::Repair),
+            (0x1000_0000, Flag::StartHidden),
+            (0x2000_0000, Flag::StartReady),
+            (0x4000_0000, Flag::StartSet),
+            (0x8000_0000, Flag::StartGo),
+        ];
+        assert_eq!(expected.len(), Flag::ALL.len());
+        for (bit, flag) in expected {
+            assert_eq!(flag.bit(), bit, "{:?}", flag);
+            assert_eq!(SessionFlags(bit).flags().collect::<Vec<_>>(), vec![flag]);
+        }
+    }
+
+    #[test]
+    fn unused_bits_decode_to_nothing() {
+        assert_eq!(SessionFlags(0x0fe0_0000).flags().count(), 0);
+        assert_eq!(SessionFlags(0).panel(), None);
+    }
+
+    #[test]
+    fn decodes_combined_flags() {
+        let flags = SessionFlags(Flag::Green.bit() | Flag::Blue.bit() | Flag::StartGo.bit());
+        assert_eq!(flags.flags().collect::<Vec<_>>(), vec![Flag::Green, Flag::Blue, Flag::StartGo]);
+    }
+
+    #[test]
+    fn local_and_full_course_yellows() {
+        let local = SessionFlags(Flag::YellowWaving.bit());
+        assert!(local.local_yellow() && local.yellow() && !local.full_course_yellow());
+        assert_eq!(local.panel(), Some(FlagPanel::LocalYellow));
+
+        let full = SessionFlags(Flag::Yellow.bit() | Flag::Caution.bit());
+        assert!(full.full_course_yellow() && full.yellow() && !full.local_yellow());
+        assert_eq!(full.panel(), Some(FlagPanel::FullCourseYellow));
+        assert_eq!(SessionFlags(Flag::CautionWaving.bit()).panel(), Some(FlagPanel::FullCourseYellow));
+        assert!(!SessionFlags(Flag::Green.bit()).yellow());
+    }
+
+    #[test]
+    fn panel_shows_the_most_important_flag() {
+        let panel = |flags: &[Flag]| SessionFlags(flags.iter().map(|flag| flag.bit()).sum()).panel();
+        assert_eq!(panel(&[Flag::Green, Flag::Disqualify, Flag::Black]), Some(FlagPanel::Disqualified));
+        assert_eq!(panel(&[Flag::Green, Flag::Black, Flag::Repair]), Some(FlagPanel::Black));
+        assert_eq!(panel(&[Flag::Caution, Flag::Repair]), Some(FlagPanel::Meatball));
+        assert_eq!(panel(&[Flag::Red, Flag::Checkered]), Some(FlagPanel::Red));
+        assert_eq!(panel(&[Flag::Checkered, Flag::Blue]), Some(FlagPanel::Chequered));
+        assert_eq!(panel(&[Flag::Blue, Flag::Yellow]), Some(FlagPanel::LocalYellow));
+        assert_eq!(panel(&[Flag::White, Flag::Blue]), Some(FlagPanel::Blue));
+        assert_eq!(panel(&[Flag::Green, Flag::White]), Some(FlagPanel::White));
+        assert_eq!(panel(&[Flag::Green, Flag::Debris]), Some(FlagPanel::Debris));
+        assert_eq!(panel(&[Flag::GreenHeld]), Some(FlagPanel::Green));
+        // Nothing to show for the start lights on their own
+        assert_eq!(panel(&[Flag::StartReady, Flag::Servicible]), None);
+    }
+
+    #[test]
+    fn incident_count_flashes_when_it_goes_up() {
+        let mut incidents = IncidentCount::new();
+        incidents.update(0, 10.);
+        assert!(!incidents.flashing(10.));
+        incidents.update(2, 20.);
+        assert_eq!(incidents.count(), Some(2));
+        assert!(incidents.flashing(21.));
+        assert!(!incidents.flashing(20. + INCIDENT_FLASH));
+        // A new session starting from zero again
+        incidents.update(0, 0.5);
+        assert!(!incidents.flashing(1.));
+    }
+}
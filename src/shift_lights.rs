@@ -0,0 +1,106 @@
+use crate::telemetry::{IRacingTelemetry, Variable, VariableType};
+use crate::util::ShiftPoints;
+use std::collections::BTreeMap;
+
+/// The telemetry needed to drive the shift lights.
+pub const VARIABLES: &[Variable] = &[
+    Variable::new("RPM", VariableType::Float),
+    Variable::new("Gear", VariableType::Int),
+    Variable::new("PlayerCarSLFirstRPM", VariableType::Float),
+    Variable::new("PlayerCarSLShiftRPM", VariableType::Float),
+    Variable::new("PlayerCarSLBlinkRPM", VariableType::Float),
+];
+
+/// The car's own shift points, as used by its dash in the sim.
+pub fn sim_shift_points(telemetry: &IRacingTelemetry) -> Option<ShiftPoints> {
+    let points = ShiftPoints {
+        first_rpm: telemetry.float("PlayerCarSLFirstRPM")?,
+        shift_rpm: telemetry.float("PlayerCarSLShiftRPM")?,
+        blink_rpm: telemetry.float("PlayerCarSLBlinkRPM")?,
+    };
+    // Cars without shift lights report zeros
+    (points.shift_rpm > points.first_rpm).then_some(points)
+}
+
+/// Custom shift points for `car` if there are any, or the sim's otherwise.
+pub fn shift_points(
+    telemetry: &IRacingTelemetry,
+    car: Option<&str>,
+    custom: &BTreeMap<String, ShiftPoints>,
+) -> Option<ShiftPoints> {
+    car.and_then(|car| custom.get(car))
+        .copied()
+        .or_else(|| sim_shift_points(telemetry))
+}
+
+/// What the LEDs should show.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LedBar {
+    /// How many LEDs are lit, from the left.
+    pub lit: usize,
+    /// At or past the shift point, so every LED is lit.
+    pub shift: bool,
+    /// Past the blink point, so the LEDs should flash.
+    pub blink: bool,
+}
+
+/// Fill `count` LEDs evenly between the first light and the shift point.
+pub fn led_bar(rpm: f32, gear: i32, points: &ShiftPoints, count: usize) -> LedBar {
+    // Nothing to shift to in neutral
+    if gear == 0 || rpm < points.first_rpm {
+        return LedBar::default();
+    }
+    let fill = (rpm - points.first_rpm) / (points.shift_rpm - points.first_rpm).max(1.);
+    let shift = rpm >= points.shift_rpm;
+    LedBar {
+        lit: if shift {
+            count
+        } else {
+            ((fill * count as f32).floor() as usize + 1).min(count)
+        },
+        shift,
+        blink: rpm >= points.blink_rpm.max(points.shift_rpm),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINTS: ShiftPoints = ShiftPoints {
+        first_rpm: 5000.,
+        shift_rpm: 7000.,
+        blink_rpm: 7400.,
+    };
+
+    #[test]
+    fn fills_from_the_first_light() {
+        assert_eq!(led_bar(4999., 3, &POINTS, 10).lit, 0);
+        assert_eq!(led_bar(5000., 3, &POINTS, 10).lit, 1);
+        assert_eq!(led_bar(6000., 3, &POINTS, 10).lit, 6);
+        assert_eq!(led_bar(6999., 3, &POINTS, 10).lit, 10);
+        assert!(!led_bar(6999., 3, &POINTS, 10).shift);
+    }
+
+    #[test]
+    fn shifts_then_blinks() {
+        let bar = led_bar(7100., 3, &POINTS, 10);
+        assert_eq!(bar, LedBar { lit: 10, shift: true, blink: false });
+        let bar = led_bar(7400., 3, &POINTS, 10);
+        assert_eq!(bar, LedBar { lit: 10, shift: true, blink: true });
+    }
+
+    #[test]
+    fn blinks_no_earlier_than_the_shift_point() {
+        let points = ShiftPoints { blink_rpm: 0., ..POINTS };
+        assert!(!led_bar(6500., 3, &points, 10).blink);
+        assert!(led_bar(7000., 3, &points, 10).blink);
+    }
+
+    #[test]
+    fn dark_in_neutral() {
+        assert_eq!(led_bar(7500., 0, &POINTS, 10), LedBar::default());
+        // Still useful in reverse
+        assert!(led_bar(7500., -1, &POINTS, 10).shift);
+    }
+}
@@ -0,0 +1,243 @@
+use crate::util::Overlays;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+
+/// How many lines of overlay output the log keeps.
+pub const MAX_LOG_LINES: usize = 500;
+
+/// Messages from the overlays and the supervisor, oldest first.
+#[derive(Clone, Default)]
+pub struct OverlayLog(Arc<Mutex<VecDeque<String>>>);
+
+impl OverlayLog {
+    pub fn push(&self, line: String) {
+        let mut lines = self.0.lock().unwrap();
+        if lines.len() == MAX_LOG_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.0.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
+
+/// Launch `program` with its stderr going to `log`.
+fn spawn(name: &str, program: &Path, log: &OverlayLog) -> std::io::Result<Child> {
+    let mut child = Command::new(program).stderr(Stdio::piped()).spawn()?;
+    if let Some(stderr) = child.stderr.take() {
+        let log = log.clone();
+        let name = name.to_string();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                log.push(format!("[{}] {}", name, line));
+            }
+        });
+    }
+    Ok(child)
+}
+
+struct Process {
+    name: String,
+    program: PathBuf,
+    child: Child,
+    /// Times it's been restarted since it was last started by hand
+    restarts: u32,
+}
+
+/// Runs the overlay processes, noticing when they exit.
+#[derive(Default)]
+pub struct OverlaySupervisor {
+    processes: BTreeMap<Overlays, Process>,
+    log: OverlayLog,
+}
+
+impl OverlaySupervisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn log(&self) -> &OverlayLog {
+        &self.log
+    }
+
+    pub fn is_running(&self, id: Overlays) -> bool {
+        self.processes.contains_key(&id)
+    }
+
+    /// Launch `program` as the overlay `id`, with its stderr going to the log.
+    pub fn start(&mut self, id: Overlays, name: &str, program: &Path) -> std::io::Result<()> {
+        self.stop(id);
+        let child = spawn(name, program, &self.log)?;
+        self.processes.insert(
+            id,
+            Process {
+                name: name.to_string(),
+                program: program.to_path_buf(),
+                child,
+                restarts: 0,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn stop(&mut self, id: Overlays) {
+        if let Some(mut process) = self.processes.remove(&id) {
+            let _ = process.child.kill();
+            let _ = process.child.wait();
+        }
+    }
+
+    pub fn stop_all(&mut self) {
+        let ids: Vec<Overlays> = self.processes.keys().copied().collect();
+        for id in ids {
+            self.stop(id);
+        }
+    }
+
+    /// Check on every overlay, restarting ones that crashed up to
+    /// `restart_limit` times. Returns the overlays that have stopped for good,
+    /// whether they were closed or crashed too often.
+    pub fn poll(&mut self, restart_limit: u32) -> Vec<(Overlays, ExitStatus)> {
+        let mut exited = Vec::new();
+        for (id, process) in self.processes.iter_mut() {
+            let status = match process.child.try_wait() {
+                Ok(Some(status)) => status,
+                Ok(None) => continue,
+                Err(e) => {
+                    self.log.push(format!("[{}] couldn't be checked on: {}", process.name, e));
+                    continue;
+                }
+            };
+            // Closing the window is a clean exit, so only failures get restarted
+            if status.success() || process.restarts >= restart_limit {
+                self.log.push(format!("[{}] stopped with {}", process.name, status));
+                exited.push((*id, status));
+                continue;
+            }
+            process.restarts += 1;
+            self.log.push(format!(
+                "[{}] crashed with {}, restarting ({}/{})",
+                process.name, status, process.restarts, restart_limit
+            ));
+            match spawn(&process.name, &process.program, &self.log) {
+                Ok(child) => process.child = child,
+                Err(e) => {
+                    self.log.push(format!("[{}] failed to restart: {}", process.name, e));
+                    exited.push((*id, status));
+                }
+            }
+        }
+        for (id, _) in &exited {
+            self.processes.remove(id);
+        }
+        exited
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, Instant};
+
+    /// Writing a script while another test forks can leave it busy when run,
+    /// so the tests that run scripts take turns.
+    static SCRIPTS: Mutex<()> = Mutex::new(());
+
+    /// A shell script standing in for an overlay.
+    fn script(name: &str, body: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("supervisor-{}-{}", std::process::id(), name));
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    /// Poll until something stops for good, or give up after a few seconds.
+    fn poll_until_exit(supervisor: &mut OverlaySupervisor, restart_limit: u32) -> Vec<(Overlays, ExitStatus)> {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            let exited = supervisor.poll(restart_limit);
+            if !exited.is_empty() {
+                return exited;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        Vec::new()
+    }
+
+    #[test]
+    fn notices_a_closed_overlay() {
+        let _turn = SCRIPTS.lock().unwrap_or_else(|e| e.into_inner());
+        let program = script("closed", "exit 0");
+        let mut supervisor = OverlaySupervisor::new();
+        supervisor.start(Overlays::Speed, "speed", &program).unwrap();
+        let exited = poll_until_exit(&mut supervisor, 3);
+        assert_eq!(exited.len(), 1);
+        assert_eq!(exited[0].0, Overlays::Speed);
+        assert!(exited[0].1.success());
+        assert!(!supervisor.is_running(Overlays::Speed));
+    }
+
+    #[test]
+    fn restarts_crashes_up_to_the_limit() {
+        let _turn = SCRIPTS.lock().unwrap_or_else(|e| e.into_inner());
+        let program = script("crash", "echo boom >&2\nexit 3");
+        let mut supervisor = OverlaySupervisor::new();
+        supervisor.start(Overlays::Fuel, "fuel", &program).unwrap();
+        let exited = poll_until_exit(&mut supervisor, 2);
+        assert_eq!(exited[0].0, Overlays::Fuel);
+        assert_eq!(exited[0].1.code(), Some(3));
+        let lines = supervisor.log().lines();
+        assert_eq!(lines.iter().filter(|line| line.contains("restarting")).count(), 2);
+        assert!(lines.iter().any(|line| line == "[fuel] stopped with exit status: 3"));
+    }
+
+    #[test]
+    fn captures_stderr() {
+        let _turn = SCRIPTS.lock().unwrap_or_else(|e| e.into_inner());
+        let program = script("stderr", "echo first >&2\necho second >&2\nexit 0");
+        let mut supervisor = OverlaySupervisor::new();
+        supervisor.start(Overlays::Delta, "delta", &program).unwrap();
+        poll_until_exit(&mut supervisor, 0);
+        // The reader thread can finish just after the process does
+        let start = Instant::now();
+        while supervisor.log().lines().len() < 3 && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let lines = supervisor.log().lines();
+        assert!(lines.contains(&"[delta] first".to_string()));
+        assert!(lines.contains(&"[delta] second".to_string()));
+    }
+
+    #[test]
+    fn stopping_kills_the_overlay() {
+        let _turn = SCRIPTS.lock().unwrap_or_else(|e| e.into_inner());
+        let program = script("sleep", "sleep 30");
+        let mut supervisor = OverlaySupervisor::new();
+        supervisor.start(Overlays::Radar, "radar", &program).unwrap();
+        assert!(supervisor.is_running(Overlays::Radar));
+        supervisor.stop(Overlays::Radar);
+        assert!(!supervisor.is_running(Overlays::Radar));
+        assert!(supervisor.poll(3).is_empty());
+    }
+
+    #[test]
+    fn log_keeps_the_latest_lines() {
+        let log = OverlayLog::default();
+        for i in 0..MAX_LOG_LINES + 5 {
+            log.push(i.to_string());
+        }
+        let lines = log.lines();
+        assert_eq!(lines.len(), MAX_LOG_LINES);
+        assert_eq!(lines[0], "5");
+    }
+}
@@ -1,61 +1,1324 @@
-#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
-
-use iracing::telemetry::Value;
+#[cfg(target_os = "windows")]
+use iracing::telemetry::{Blocking, Sample, Value};
+#[cfg(target_os = "windows")]
 use iracing::Connection;
+use crate::hub::open_overlay_source;
+use crate::ibt::{IbtSource, PlaybackRate};
+use crate::session::SessionDetails;
+use arc_swap::ArcSwap;
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
 
+/// The type a telemetry variable is expected to have. Array types are for
+/// variables with one value per car, like `CarIdxLapDistPct`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VariableType {
+    Char,
+    Bool,
+    Int,
+    Bits,
+    Float,
+    Double,
+    IntArray,
+    BitsArray,
+    FloatArray,
+    BoolArray,
+}
+
+/// A telemetry variable an overlay wants, by its iRacing name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Variable {
+    pub name: &'static str,
+    pub kind: VariableType,
+}
+
+impl Variable {
+    pub const fn new(name: &'static str, kind: VariableType) -> Self {
+        Self { name, kind }
+    }
+}
+
+/// Why a subscribed variable couldn't be read.
+#[derive(Clone, Debug, PartialEq)]
+pub enum VariableIssue {
+    Missing(&'static str),
+    WrongType {
+        name: &'static str,
+        expected: VariableType,
+        found: VariableType,
+    },
+}
+
+impl fmt::Display for VariableIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VariableIssue::Missing(name) => write!(f, "Telemetry variable {} not found", name),
+            VariableIssue::WrongType {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Telemetry variable {} is {:?}, expected {:?}",
+                name, found, expected
+            ),
+        }
+    }
+}
+
+/// The values of the subscribed variables in the latest sample.
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct IRacingTelemetry {
-    pub speed: f32,
-    pub lastlaptime: f32,
+    values: HashMap<&'static str, TelemetryValue>,
+    pub issues: Vec<VariableIssue>,
+}
+
+impl IRacingTelemetry {
+    pub fn get(&self, name: &str) -> Option<&TelemetryValue> {
+        self.values.get(name)
+    }
+
+    pub fn bool(&self, name: &str) -> Option<bool> {
+        match self.get(name)? {
+            TelemetryValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn int(&self, name: &str) -> Option<i32> {
+        match self.get(name)? {
+            TelemetryValue::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn bits(&self, name: &str) -> Option<u32> {
+        match self.get(name)? {
+            TelemetryValue::Bits(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn float(&self, name: &str) -> Option<f32> {
+        match self.get(name)? {
+            TelemetryValue::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    pub fn double(&self, name: &str) -> Option<f64> {
+        match self.get(name)? {
+            TelemetryValue::Double(d) => Some(*d),
+            _ => None,
+        }
+    }
+
+    pub fn int_array(&self, name: &str) -> Option<&[i32]> {
+        match self.get(name)? {
+            TelemetryValue::IntVec(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn bits_array(&self, name: &str) -> Option<&[u32]> {
+        match self.get(name)? {
+            TelemetryValue::BitsVec(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn float_array(&self, name: &str) -> Option<&[f32]> {
+        match self.get(name)? {
+            TelemetryValue::FloatVec(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn bool_array(&self, name: &str) -> Option<&[bool]> {
+        match self.get(name)? {
+            TelemetryValue::BoolVec(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// A single telemetry variable, independent of where the sample came from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TelemetryValue {
+    Char(u8),
+    Bool(bool),
+    Int(i32),
+    Bits(u32),
+    Float(f32),
+    Double(f64),
+    IntVec(Vec<i32>),
+    BitsVec(Vec<u32>),
+    FloatVec(Vec<f32>),
+    BoolVec(Vec<bool>),
+}
+
+impl TelemetryValue {
+    pub fn kind(&self) -> VariableType {
+        match self {
+            TelemetryValue::Char(..) => VariableType::Char,
+            TelemetryValue::Bool(..) => VariableType::Bool,
+            TelemetryValue::Int(..) => VariableType::Int,
+            TelemetryValue::Bits(..) => VariableType::Bits,
+            TelemetryValue::Float(..) => VariableType::Float,
+            TelemetryValue::Double(..) => VariableType::Double,
+            TelemetryValue::IntVec(..) => VariableType::IntArray,
+            TelemetryValue::BitsVec(..) => VariableType::BitsArray,
+            TelemetryValue::FloatVec(..) => VariableType::FloatArray,
+            TelemetryValue::BoolVec(..) => VariableType::BoolArray,
+        }
+    }
+}
+
+/// What a source had to give when asked for a sample.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sampled {
+    /// A new sample, now the current one.
+    New,
+    /// Nothing new, but the source is still there, e.g. the hub while the sim
+    /// is closed. The source is kept rather than reopened.
+    Idle,
+}
+
+/// Something that can produce telemetry samples, e.g. the live sim or a script.
+pub trait TelemetrySource {
+    /// Wait up to `timeout` for the next sample and make it the current one.
+    /// Errors if there's no sample and no sign of the source either.
+    fn sample(&mut self, timeout: Duration) -> Result<Sampled, Box<dyn Error>>;
+
+    /// Read a variable by its iRacing name from the current sample.
+    fn get(&self, name: &'static str) -> Option<TelemetryValue>;
+
+    /// A counter which changes every time the session info is updated.
+    fn session_info_version(&self) -> Option<i32> {
+        None
+    }
+
+    /// The session info YAML, if the source has any.
+    fn session_info(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Our own view of the shared memory map, for the raw session info which the
+/// iracing crate only hands out already parsed into its own structs.
+#[cfg(target_os = "windows")]
+struct SharedMemory {
+    mapping: winapi::um::winnt::HANDLE,
+    view: *const u8,
+    /// How many bytes of the view can be read
+    size: usize,
+}
+
+#[cfg(target_os = "windows")]
+impl SharedMemory {
+    fn open() -> std::io::Result<Self> {
+        use winapi::um::handleapi::CloseHandle;
+        use winapi::um::memoryapi::{
+            MapViewOfFile, OpenFileMappingW, UnmapViewOfFile, VirtualQuery, FILE_MAP_READ,
+        };
+        use winapi::um::winnt::MEMORY_BASIC_INFORMATION;
+        let mut path: Vec<u16> = iracing::TELEMETRY_PATH.encode_utf16().collect();
+        path.push(0);
+        let mapping = unsafe { OpenFileMappingW(FILE_MAP_READ, 0, path.as_ptr()) };
+        if mapping.is_null() {
+            return Err(std::io::Error::last_os_error());
+        }
+        let view = unsafe { MapViewOfFile(mapping, FILE_MAP_READ, 0, 0, 0) };
+        if view.is_null() {
+            let error = std::io::Error::last_os_error();
+            unsafe { CloseHandle(mapping) };
+            return Err(error);
+        }
+        // The header's offsets are checked against the size of the view before they're followed
+        let mut info: MEMORY_BASIC_INFORMATION = unsafe { std::mem::zeroed() };
+        let queried =
+            unsafe { VirtualQuery(view, &mut info, std::mem::size_of::<MEMORY_BASIC_INFORMATION>()) };
+        if queried == 0 {
+            let error = std::io::Error::last_os_error();
+            unsafe {
+                UnmapViewOfFile(view);
+                CloseHandle(mapping);
+            }
+            return Err(error);
+        }
+        Ok(Self {
+            mapping,
+            view: view as *const u8,
+            size: info.RegionSize,
+        })
+    }
+
+    /// The bytes at `offset..offset + length`, if they're inside the view.
+    fn bytes(&self, offset: usize, length: usize) -> Option<&[u8]> {
+        let end = offset.checked_add(length)?;
+        if end > self.size {
+            return None;
+        }
+        Some(unsafe { std::slice::from_raw_parts(self.view.add(offset), length) })
+    }
+
+    fn read_i32(&self, offset: usize) -> Option<i32> {
+        Some(i32::from_le_bytes(self.bytes(offset, 4)?.try_into().ok()?))
+    }
+
+    fn session_info_version(&self) -> Option<i32> {
+        self.read_i32(12)
+    }
+
+    fn session_info(&self) -> Option<String> {
+        let length = usize::try_from(self.read_i32(16)?).ok()?;
+        let offset = usize::try_from(self.read_i32(20)?).ok()?;
+        let bytes = self.bytes(offset, length)?;
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(length);
+        // iRacing writes ISO-8859-1, which maps byte for byte onto the first 256 chars
+        Some(bytes[..end].iter().map(|&b| b as char).collect())
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        use winapi::um::handleapi::CloseHandle;
+        use winapi::um::memoryapi::UnmapViewOfFile;
+        unsafe {
+            UnmapViewOfFile(self.view as *const _);
+            CloseHandle(self.mapping);
+        }
+    }
+}
+
+/// Live telemetry read from the iRacing shared memory map.
+#[cfg(target_os = "windows")]
+pub struct LiveSource {
+    _connection: Connection,
+    blocking: Blocking,
+    memory: SharedMemory,
+    current: Option<Sample>,
+}
+
+#[cfg(target_os = "windows")]
+impl LiveSource {
+    pub fn new() -> std::io::Result<Self> {
+        let connection = Connection::new()?;
+        let blocking = connection.blocking()?;
+        Ok(Self {
+            _connection: connection,
+            blocking,
+            memory: SharedMemory::open()?,
+            current: None,
+        })
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl TelemetrySource for LiveSource {
+    fn sample(&mut self, timeout: Duration) -> Result<Sampled, Box<dyn Error>> {
+        self.current = Some(self.blocking.sample(timeout)?);
+        Ok(Sampled::New)
+    }
+
+    fn get(&self, name: &'static str) -> Option<TelemetryValue> {
+        let value = self.current.as_ref()?.get(name).ok()?;
+        match value {
+            Value::CHAR(c) => Some(TelemetryValue::Char(c)),
+            Value::BOOL(b) => Some(TelemetryValue::Bool(b)),
+            Value::INT(i) => Some(TelemetryValue::Int(i)),
+            Value::BITS(b) => Some(TelemetryValue::Bits(b)),
+            Value::FLOAT(f) => Some(TelemetryValue::Float(f)),
+            Value::DOUBLE(d) => Some(TelemetryValue::Double(d)),
+            Value::IntVec(v) => Some(TelemetryValue::IntVec(v)),
+            Value::FloatVec(v) => Some(TelemetryValue::FloatVec(v)),
+            Value::BoolVec(v) => Some(TelemetryValue::BoolVec(v)),
+            Value::UNKNOWN(..) => None,
+        }
+    }
+
+    fn session_info_version(&self) -> Option<i32> {
+        self.memory.session_info_version()
+    }
+
+    fn session_info(&self) -> Option<String> {
+        self.memory.session_info()
+    }
+}
+
+/// Plays back a fixed list of frames, one per sample, looping at the end.
+pub struct ScriptedSource {
+    frames: Vec<HashMap<&'static str, TelemetryValue>>,
+    position: Option<usize>,
+    session_info: Option<String>,
+}
+
+impl ScriptedSource {
+    pub fn new(frames: Vec<HashMap<&'static str, TelemetryValue>>) -> Self {
+        Self {
+            frames,
+            position: None,
+            session_info: None,
+        }
+    }
+
+    pub fn with_session_info(mut self, session_info: impl Into<String>) -> Self {
+        self.session_info = Some(session_info.into());
+        self
+    }
+}
+
+impl TelemetrySource for ScriptedSource {
+    fn sample(&mut self, _timeout: Duration) -> Result<Sampled, Box<dyn Error>> {
+        if self.frames.is_empty() {
+            return Err("Scripted source has no frames".into());
+        }
+        self.position = Some(match self.position {
+            Some(position) => (position + 1) % self.frames.len(),
+            None => 0,
+        });
+        Ok(Sampled::New)
+    }
+
+    fn get(&self, name: &'static str) -> Option<TelemetryValue> {
+        self.frames[self.position?].get(name).cloned()
+    }
+
+    fn session_info_version(&self) -> Option<i32> {
+        self.session_info.as_ref().map(|_| 1)
+    }
+
+    fn session_info(&self) -> Option<String> {
+        self.session_info.clone()
+    }
+}
+
+/// Generates a plausible looking lap on a loop, for working on overlays
+/// without the simulator running.
+pub struct SyntheticSource {
+    elapsed: u32,
+    tick: u32,
+    lap: u32,
+    lap_ticks: u32,
+    lastlaptime: f32,
+    bestlaptime: f32,
+    fuel_level: f32,
+    next_tick: Option<Instant>,
+}
+
+impl SyntheticSource {
+    const TICK_RATE: f32 = 60.;
+    const TICK_LENGTH: Duration = Duration::from_micros(16_667);
+    /// The player, four opponents at different paces and the pace car, which stays in the pits.
+    const CARS: usize = 6;
+    const OPPONENT_LAP_TIMES: [f32; 5] = [0., 87.5, 89.5, 94., 85.];
+    const SESSION_INFO: &'static str = "---
+WeekendInfo:
+ TrackName: synthetic
+ TrackID: 1
+ TrackLength: 4.00 km
+ TrackDisplayName: Synthetic Raceway
+ TrackConfigName: Grand Prix
+ TrackType: road course
+ NumCarClasses: 1
+SessionInfo:
+ Sessions:
+ - SessionNum: 0
+   SessionLaps: unlimited
+   SessionTime: 1800.0000 sec
+   SessionType: Race
+   SessionName: RACE
+   ResultsPositions:
+DriverInfo:
+ DriverCarIdx: 0
+ DriverCarFuelMaxLtr: 100.000
+ DriverCarEstLapTime: 90.0000
+ Drivers:
+ - CarIdx: 0
+   UserName: Synthetic Driver
+   AbbrevName: Driver, S
+   Initials: SD
+   UserID: 1
+   CarNumber: \"1\"
+   CarNumberRaw: 1
+   CarScreenName: Synthetic Car
+   CarClassID: 1
+   CarClassShortName: SYN
+   CarClassColor: 0xffffff
+   IRating: 1500
+   LicString: B 3.00
+   LicColor: 0x00c702
+ - CarIdx: 1
+   UserName: Alex Ahead
+   CarNumber: \"7\"
+   CarNumberRaw: 7
+   CarClassID: 1
+   CarClassShortName: SYN
+   CarClassColor: 0xffffff
+   IRating: 2100
+   LicString: A 2.41
+   LicColor: 0x0153db
+ - CarIdx: 2
+   UserName: Sam Steady
+   CarNumber: \"22\"
+   CarNumberRaw: 22
+   CarClassID: 1
+   CarClassShortName: SYN
+   CarClassColor: 0xffffff
+   IRating: 1620
+   LicString: C 3.80
+   LicColor: 0xfeec04
+ - CarIdx: 3
+   UserName: Jo Slow
+   CarNumber: \"40\"
+   CarNumberRaw: 40
+   CarClassID: 1
+   CarClassShortName: SYN
+   CarClassColor: 0xffffff
+   IRating: 980
+   LicString: D 2.10
+   LicColor: 0xfc8a27
+ - CarIdx: 4
+   UserName: Robin Rapid
+   CarNumber: \"3\"
+   CarNumberRaw: 3
+   CarClassID: 1
+   CarClassShortName: SYN
+   CarClassColor: 0xffffff
+   IRating: 3400
+   LicString: A 4.99
+   LicColor: 0x0153db
+ - CarIdx: 5
+   UserName: Pace Car
+   CarNumber: \"0\"
+   CarIsPaceCar: 1
+SplitTimeInfo:
+ Sectors:
+ - SectorNum: 0
+   SectorStartPct: 0.000000
+ - SectorNum: 1
+   SectorStartPct: 0.333333
+ - SectorNum: 2
+   SectorStartPct: 0.666667
+...
+";
+
+    /// Laps covered by a car, including the fraction of the current one.
+    fn car_distance(&self, car_idx: usize) -> f32 {
+        match car_idx {
+            0 => self.lap as f32 + self.tick as f32 / self.lap_ticks as f32,
+            5 => 0.,
+            _ => {
+                let lap_time = Self::OPPONENT_LAP_TIMES[car_idx];
+                self.elapsed as f32 / Self::TICK_RATE / lap_time + 0.02 * car_idx as f32
+            }
+        }
+    }
+
+    fn car_last_lap_time(&self, car_idx: usize) -> f32 {
+        match car_idx {
+            0 => self.lastlaptime,
+            5 => -1.,
+            _ if self.car_distance(car_idx) < 1. => -1.,
+            _ => Self::OPPONENT_LAP_TIMES[car_idx],
+        }
+    }
+
+    /// Race positions, counting from 1, with 0 for the pace car.
+    fn car_positions(&self) -> Vec<i32> {
+        let mut order: Vec<usize> = (0..Self::CARS - 1).collect();
+        order.sort_by(|a, b| self.car_distance(*b).total_cmp(&self.car_distance(*a)));
+        let mut positions = vec![0; Self::CARS];
+        for (position, car_idx) in order.into_iter().enumerate() {
+            positions[car_idx] = position as i32 + 1;
+        }
+        positions
+    }
+}
+
+impl Default for SyntheticSource {
+    fn default() -> Self {
+        Self {
+            elapsed: 0,
+            tick: 0,
+            lap: 0,
+            lap_ticks: 90 * 60,
+            lastlaptime: -1.,
+            bestlaptime: -1.,
+            fuel_level: 60.,
+            next_tick: None,
+        }
+    }
+}
+
+impl TelemetrySource for SyntheticSource {
+    fn sample(&mut self, timeout: Duration) -> Result<Sampled, Box<dyn Error>> {
+        // Tick at the same rate as the sim, rather than as fast as we're asked
+        let now = Instant::now();
+        let next_tick = *self.next_tick.get_or_insert(now);
+        if next_tick > now + timeout {
+            std::thread::sleep(timeout);
+            return Err("Timed out waiting for the next synthetic tick".into());
+        }
+        std::thread::sleep(next_tick.saturating_duration_since(now));
+        self.next_tick = Some(next_tick.max(now) + Self::TICK_LENGTH);
+        self.elapsed += 1;
+        self.tick += 1;
+        // Use a little more fuel on some laps than others
+        let fuel_per_lap = 2.5 + 0.1 * (self.lap % 3) as f32;
+        self.fuel_level = (self.fuel_level - fuel_per_lap / self.lap_ticks as f32).max(0.);
+        if self.tick >= self.lap_ticks {
+            // Vary the lap length a little so the last lap time moves
+            self.lastlaptime = self.lap_ticks as f32 / Self::TICK_RATE;
+            if self.bestlaptime <= 0. || self.lastlaptime < self.bestlaptime {
+                self.bestlaptime = self.lastlaptime;
+            }
+            self.lap += 1;
+            self.lap_ticks = 88 * 60 + (self.lap % 5) * 60;
+            self.tick = 0;
+        }
+        Ok(Sampled::New)
+    }
+
+    fn get(&self, name: &'static str) -> Option<TelemetryValue> {
+        let lap_pct = self.tick as f32 / self.lap_ticks as f32;
+        match name {
+            "Speed" => Some(TelemetryValue::Float(
+                45. + 25. * (lap_pct * std::f32::consts::TAU * 4.).sin(),
+            )),
+            // On the throttle while the speed is rising, and on the brakes while it's falling
+            "Throttle" => Some(TelemetryValue::Float(
+                (lap_pct * std::f32::consts::TAU * 4.).cos().max(0.),
+            )),
+            "Brake" => Some(TelemetryValue::Float(
+                (-(lap_pct * std::f32::consts::TAU * 4.).cos()).max(0.) * 0.9,
+            )),
+            "Clutch" => Some(TelemetryValue::Float(1.)),
+            "SteeringWheelAngle" => Some(TelemetryValue::Float(
+                0.8 * (lap_pct * std::f32::consts::TAU * 8.).sin(),
+            )),
+            "SteeringWheelAngleMax" => Some(TelemetryValue::Float(4.71)),
+            "Gear" => Some(TelemetryValue::Int(
+                (1. + (45. + 25. * (lap_pct * std::f32::consts::TAU * 4.).sin()) / 15.).min(6.) as i32,
+            )),
+            // Revs climb through each gear as the speed rises
+            "RPM" => Some(TelemetryValue::Float(
+                4000. + 3500. * ((45. + 25. * (lap_pct * std::f32::consts::TAU * 4.).sin()) / 15.).fract(),
+            )),
+            "PlayerCarSLFirstRPM" => Some(TelemetryValue::Float(5500.)),
+            "PlayerCarSLShiftRPM" => Some(TelemetryValue::Float(7000.)),
+            "PlayerCarSLBlinkRPM" => Some(TelemetryValue::Float(7300.)),
+            "LapLastLapTime" => Some(TelemetryValue::Float(self.lastlaptime)),
+            "LapBestLapTime" => Some(TelemetryValue::Float(self.bestlaptime)),
+            "LapDistPct" => Some(TelemetryValue::Float(lap_pct)),
+            // Heading round in a loop once a lap, so dead reckoning gives a closed outline
+            "Yaw" => Some(TelemetryValue::Float(
+                (lap_pct * std::f32::consts::TAU + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU)
+                    - std::f32::consts::PI,
+            )),
+            // Tyres warm up with speed, and wear a little every lap
+            name if name.len() > 2 && ["LF", "RF", "LR", "RR"].contains(&&name[..2]) => {
+                let speed = 45. + 25. * (lap_pct * std::f32::consts::TAU * 4.).sin();
+                match &name[2..] {
+                    "tempCL" => Some(TelemetryValue::Float(70. + speed * 0.4)),
+                    "tempCM" => Some(TelemetryValue::Float(72. + speed * 0.4)),
+                    "tempCR" => Some(TelemetryValue::Float(74. + speed * 0.45)),
+                    "wearL" | "wearM" | "wearR" => {
+                        Some(TelemetryValue::Float(1. - 0.005 * self.lap as f32))
+                    }
+                    "coldPressure" => Some(TelemetryValue::Float(172.)),
+                    _ => None,
+                }
+            }
+            "LapCurrentLapTime" => Some(TelemetryValue::Float(self.tick as f32 / Self::TICK_RATE)),
+            "Lap" => Some(TelemetryValue::Int(self.lap as i32 + 1)),
+            "SessionNum" => Some(TelemetryValue::Int(0)),
+            "SessionTime" => Some(TelemetryValue::Double(self.elapsed as f64 / Self::TICK_RATE as f64)),
+            "SessionTimeRemain" => Some(TelemetryValue::Double(
+                (1800. - self.elapsed as f64 / Self::TICK_RATE as f64).max(0.),
+            )),
+            // irsdk_green, with a blue flag for the last part of every lap
+            "SessionFlags" => Some(TelemetryValue::Bits(if lap_pct > 0.9 { 0x0024 } else { 0x0004 })),
+            // An off every other lap
+            "PlayerCarMyIncidentCount" => Some(TelemetryValue::Int(self.lap as i32 / 2)),
+            "LapCompleted" => Some(TelemetryValue::Int(self.lap as i32)),
+            "FuelLevel" => Some(TelemetryValue::Float(self.fuel_level)),
+            "OnPitRoad" => Some(TelemetryValue::Bool(false)),
+            "PlayerTrackSurface" => Some(TelemetryValue::Int(3)),
+            // Cars within a car's length are alongside, on the left and then the right
+            "CarLeftRight" => {
+                let player = self.car_distance(0);
+                let alongside = (1..Self::CARS - 1)
+                    .filter(|car_idx| {
+                        let delta = self.car_distance(*car_idx) - player;
+                        (delta - delta.round()).abs() < 0.00125
+                    })
+                    .count();
+                Some(TelemetryValue::Int(match alongside {
+                    0 => 1,
+                    1 => 2,
+                    _ => 4,
+                }))
+            }
+            "PlayerCarIdx" => Some(TelemetryValue::Int(0)),
+            "CarIdxLap" => Some(TelemetryValue::IntVec(
+                (0..Self::CARS).map(|car_idx| self.car_distance(car_idx).floor() as i32 + 1).collect(),
+            )),
+            "CarIdxLapDistPct" => Some(TelemetryValue::FloatVec(
+                (0..Self::CARS).map(|car_idx| self.car_distance(car_idx).fract()).collect(),
+            )),
+            "CarIdxEstTime" => Some(TelemetryValue::FloatVec(
+                (0..Self::CARS).map(|car_idx| self.car_distance(car_idx).fract() * 90.).collect(),
+            )),
+            "CarIdxOnPitRoad" => Some(TelemetryValue::BoolVec(
+                (0..Self::CARS).map(|car_idx| car_idx == 5).collect(),
+            )),
+            "CarIdxPosition" | "CarIdxClassPosition" => {
+                Some(TelemetryValue::IntVec(self.car_positions()))
+            }
+            "CarIdxLastLapTime" | "CarIdxBestLapTime" => Some(TelemetryValue::FloatVec(
+                (0..Self::CARS).map(|car_idx| self.car_last_lap_time(car_idx)).collect(),
+            )),
+            "CarIdxF2Time" => {
+                let leader = (0..Self::CARS)
+                    .map(|car_idx| self.car_distance(car_idx))
+                    .fold(0., f32::max);
+                Some(TelemetryValue::FloatVec(
+                    (0..Self::CARS).map(|car_idx| (leader - self.car_distance(car_idx)) * 90.).collect(),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    fn session_info_version(&self) -> Option<i32> {
+        Some(1)
+    }
+
+    fn session_info(&self) -> Option<String> {
+        Some(Self::SESSION_INFO.to_string())
+    }
+}
+
+/// Opens the .ibt file named by `IRACING_OVERLAY_REPLAY`, if set, at the
+/// rate given by `IRACING_OVERLAY_REPLAY_SPEED` (default real time).
+fn open_replay_source() -> Option<Result<Box<dyn TelemetrySource>, Box<dyn Error>>> {
+    let path = env::var_os("IRACING_OVERLAY_REPLAY")?;
+    let rate = match env::var("IRACING_OVERLAY_REPLAY_SPEED") {
+        Ok(speed) => speed
+            .parse()
+            .map_err(|_| format!("IRACING_OVERLAY_REPLAY_SPEED isn't a number: {}", speed).into())
+            .and_then(PlaybackRate::real_time),
+        Err(..) => Ok(PlaybackRate::RealTime(1.)),
+    };
+    Some(rate.and_then(|rate| {
+        IbtSource::open(path, rate).map(|source| Box::new(source) as Box<dyn TelemetrySource>)
+    }))
+}
+
+/// Opens the telemetry source overlays use when none is given explicitly.
+#[cfg(target_os = "windows")]
+pub fn open_default_source() -> Result<Box<dyn TelemetrySource>, Box<dyn Error>> {
+    open_replay_source().unwrap_or_else(|| Ok(Box::new(LiveSource::new()?)))
+}
+
+/// Opens the telemetry source overlays use when none is given explicitly.
+#[cfg(not(target_os = "windows"))]
+pub fn open_default_source() -> Result<Box<dyn TelemetrySource>, Box<dyn Error>> {
+    open_replay_source().unwrap_or_else(|| {
+        // Said on every connection, so made up data is never taken for the sim's
+        eprintln!("iRacing only runs on Windows, using synthetic telemetry instead");
+        Ok(Box::new(SyntheticSource::default()))
+    })
+}
+
+/// Opens a fresh telemetry source, called again whenever the connection is lost.
+pub type Connector = Box<dyn FnMut() -> Result<Box<dyn TelemetrySource>, Box<dyn Error>> + Send>;
+
+/// Connects to the main app's hub for the given variables.
+pub fn overlay_connector(variables: &[Variable]) -> Connector {
+    let names: Vec<&'static str> = variables.iter().map(|variable| variable.name).collect();
+    Box::new(move || open_overlay_source(&names))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// No telemetry is coming in, waiting for the next retry, or for the sim
+    /// behind a source that's still open.
+    Disconnected,
+    /// A source is open, but hasn't produced a sample yet.
+    Connecting,
+    /// Samples are arriving.
+    Connected,
+    /// Samples have stopped arriving for a while, the values shown are old.
+    Stale,
+}
+
+impl ConnectionState {
+    pub fn has_data(&self) -> bool {
+        matches!(self, ConnectionState::Connected | ConnectionState::Stale)
+    }
+}
+
+/// How long `IRacingLogging` waits on a quiet source before giving up on it,
+/// and how often it retries.
+#[derive(Clone, Copy, Debug)]
+struct ConnectionTimings {
+    stale_after: Duration,
+    disconnect_after: Duration,
+    first_retry_delay: Duration,
+    max_retry_delay: Duration,
+}
+
+impl Default for ConnectionTimings {
+    fn default() -> Self {
+        Self {
+            stale_after: Duration::from_secs(1),
+            disconnect_after: Duration::from_secs(5),
+            first_retry_delay: Duration::from_millis(500),
+            max_retry_delay: Duration::from_secs(5),
+        }
+    }
 }
 
 pub struct IRacingLogging {
-    connection: Connection,
-    last_update: Instant,
+    connector: Connector,
+    timings: ConnectionTimings,
+    source: Option<Box<dyn TelemetrySource>>,
+    state: ConnectionState,
+    last_sample: Instant,
+    /// When the source last showed it was still there, with a sample or without
+    last_heard: Instant,
+    next_attempt: Instant,
+    retry_delay: Duration,
+    variables: Vec<Variable>,
+    report_issue: Box<dyn FnMut(&VariableIssue) + Send>,
+    session_info_version: Option<i32>,
     pub telemetry: IRacingTelemetry,
+    pub session: Option<Arc<SessionDetails>>,
 }
 
 impl IRacingLogging {
-    pub fn new() -> Self {
+    pub fn new(variables: &[Variable]) -> Self {
+        Self::with_connector(overlay_connector(variables), variables)
+    }
+
+    pub fn with_connector(connector: Connector, variables: &[Variable]) -> Self {
+        let now = Instant::now();
+        let timings = ConnectionTimings::default();
         Self {
-            connection: Connection::new().expect("Failed to connect to iRacing"),
-            last_update: Instant::now(),
-            telemetry: IRacingTelemetry {
-                speed: 0.0,
-                lastlaptime: 0.0,
-            },
-        }
-    }
-
-    pub fn update_telemetry(&mut self) {
-        //println!("Checking");
-        if self.last_update.elapsed().as_millis() >= 16 {
-            //println!("updating");
-            //self.telemetry.speed += 1.;
-            //self.telemetry.lastlaptime += 0.5;
-            if let Ok(data) = self.connection.blocking().expect("Unable to start telemetry reader").sample(Duration::from_millis(16)) {
-                match data.get("Speed") {
-                    Err(..) => (),
-                    Ok(value) => match value {
-                        Value::FLOAT(f) => {
-                            self.telemetry.speed = f;
-                            ()
-                        },
-                        _ => (),
-                    }
+            connector,
+            timings,
+            source: None,
+            state: ConnectionState::Disconnected,
+            last_sample: now,
+            last_heard: now,
+            next_attempt: now,
+            retry_delay: timings.first_retry_delay,
+            variables: variables.to_vec(),
+            report_issue: Box::new(|issue| eprintln!("{}", issue)),
+            session_info_version: None,
+            telemetry: IRacingTelemetry::default(),
+            session: None,
+        }
+    }
+
+    /// Use an already open source, which won't be reopened if it stops producing samples.
+    pub fn with_source(source: Box<dyn TelemetrySource>, variables: &[Variable]) -> Self {
+        let mut logging = Self::with_connector(
+            Box::new(|| Err("Telemetry source cannot be reopened".into())),
+            variables,
+        );
+        logging.source = Some(source);
+        logging.state = ConnectionState::Connecting;
+        logging
+    }
+
+    /// Report problems with the variables through `report_issue` rather than to
+    /// stderr. Each is reported when it first appears.
+    pub fn with_issue_reporter(
+        mut self,
+        report_issue: impl FnMut(&VariableIssue) + Send + 'static,
+    ) -> Self {
+        self.report_issue = Box::new(report_issue);
+        self
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    fn try_connect(&mut self) {
+        if Instant::now() < self.next_attempt {
+            return;
+        }
+        match (self.connector)() {
+            Ok(source) => {
+                self.source = Some(source);
+                self.state = ConnectionState::Connecting;
+                self.last_sample = Instant::now();
+                self.last_heard = self.last_sample;
+            }
+            Err(..) => {
+                self.state = ConnectionState::Disconnected;
+                self.next_attempt = Instant::now() + self.retry_delay;
+                self.retry_delay = (self.retry_delay * 2).min(self.timings.max_retry_delay);
+            }
+        }
+    }
+
+    /// Wait up to `timeout` for a new sample, (re)connecting to the source as
+    /// needed. Returns the source if a new sample arrived.
+    pub fn poll(&mut self, timeout: Duration) -> Option<&dyn TelemetrySource> {
+        if self.sample_source(timeout) {
+            self.source.as_deref()
+        } else {
+            None
+        }
+    }
+
+    fn sample_source(&mut self, timeout: Duration) -> bool {
+        if self.source.is_none() {
+            self.try_connect();
+        }
+        let Some(source) = self.source.as_mut() else {
+            return false;
+        };
+        match source.sample(timeout) {
+            Ok(Sampled::New) => {
+                self.state = ConnectionState::Connected;
+                self.last_sample = Instant::now();
+                self.last_heard = self.last_sample;
+                self.retry_delay = self.timings.first_retry_delay;
+                return true;
+            }
+            Ok(Sampled::Idle) => self.last_heard = Instant::now(),
+            Err(..) => {}
+        }
+        if self.last_heard.elapsed() >= self.timings.disconnect_after {
+            // The sim has most likely closed, so let go of it and wait for it to come back
+            self.source = None;
+            self.state = ConnectionState::Disconnected;
+            self.next_attempt = Instant::now();
+            self.session_info_version = None;
+        } else if self.state.has_data() && self.last_sample.elapsed() >= self.timings.disconnect_after {
+            // The source is still there, but what it relays has stopped
+            self.state = ConnectionState::Disconnected;
+            self.session_info_version = None;
+        } else if self.state == ConnectionState::Connected
+            && self.last_sample.elapsed() >= self.timings.stale_after
+        {
+            self.state = ConnectionState::Stale;
+        }
+        false
+    }
+
+    /// Wait up to `timeout` for a new sample and copy it into `telemetry`.
+    /// Returns whether a new sample arrived.
+    pub fn read_telemetry(&mut self, timeout: Duration) -> bool {
+        if !self.sample_source(timeout) {
+            return false;
+        }
+        let Some(source) = self.source.as_deref() else {
+            return false;
+        };
+        let mut values = HashMap::with_capacity(self.variables.len());
+        let mut issues = Vec::new();
+        for variable in &self.variables {
+            match source.get(variable.name) {
+                None => issues.push(VariableIssue::Missing(variable.name)),
+                Some(value) if value.kind() != variable.kind => {
+                    issues.push(VariableIssue::WrongType {
+                        name: variable.name,
+                        expected: variable.kind,
+                        found: value.kind(),
+                    })
+                }
+                Some(value) => {
+                    values.insert(variable.name, value);
+                }
+            }
+        }
+        if issues != self.telemetry.issues {
+            for issue in &issues {
+                (self.report_issue)(issue);
+            }
+        }
+        self.telemetry = IRacingTelemetry { values, issues };
+
+        // Session info is large and rarely changes, so only parse it when iRacing says it has
+        let session_info_version = source.session_info_version();
+        if session_info_version != self.session_info_version {
+            self.session_info_version = session_info_version;
+            self.session = match source.session_info().map(|info| SessionDetails::parse(&info)) {
+                Some(Ok(session)) => Some(Arc::new(session)),
+                Some(Err(e)) => {
+                    eprintln!("Failed to parse session info: {}", e);
+                    self.session.take()
                 }
-                match data.get("LapLastLapTime") {
-                    Err(..) => (),
-                    Ok(value) => match value {
-                        Value::FLOAT(f) => {
-                            self.telemetry.lastlaptime = f;
-                            ()
-                        },
-                        _ => (),
+                None => None,
+            };
+        }
+        true
+    }
+}
+
+/// The latest telemetry, as published by a `TelemetrySampler`.
+#[derive(Clone, Debug)]
+pub struct TelemetrySnapshot {
+    pub state: ConnectionState,
+    pub telemetry: IRacingTelemetry,
+    pub session: Option<Arc<SessionDetails>>,
+}
+
+/// Samples telemetry on a background thread, so the overlays' render loop never
+/// waits on the sim.
+pub struct TelemetrySampler {
+    snapshot: Arc<ArcSwap<TelemetrySnapshot>>,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TelemetrySampler {
+    /// Start sampling `variables` from the source `connector` opens. `on_update` is called
+    /// from the sampling thread with the latest snapshot for every new sample, even one
+    /// that's the same as the last, and whenever the connection state changes. It's told
+    /// whether the snapshot changed, so every tick can be recorded while repaints are
+    /// only requested for a change.
+    pub fn start(
+        variables: &[Variable],
+        connector: Connector,
+        on_update: impl Fn(&TelemetrySnapshot, bool) + Send + 'static,
+    ) -> Self {
+        let snapshot = Arc::new(ArcSwap::from_pointee(TelemetrySnapshot {
+            state: ConnectionState::Disconnected,
+            telemetry: IRacingTelemetry::default(),
+            session: None,
+        }));
+        let running = Arc::new(AtomicBool::new(true));
+        let thread = {
+            let snapshot = snapshot.clone();
+            let running = running.clone();
+            let variables = variables.to_vec();
+            thread::spawn(move || {
+                let mut logging = IRacingLogging::with_connector(connector, &variables);
+                while running.load(Ordering::Relaxed) {
+                    let sampled = logging.read_telemetry(Duration::from_millis(16));
+                    let changed = {
+                        let latest = snapshot.load();
+                        let same_session = match (&logging.session, &latest.session) {
+                            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                            (a, b) => a.is_none() && b.is_none(),
+                        };
+                        logging.state() != latest.state
+                            || logging.telemetry != latest.telemetry
+                            || !same_session
+                    };
+                    if changed {
+                        let latest = Arc::new(TelemetrySnapshot {
+                            state: logging.state(),
+                            telemetry: logging.telemetry.clone(),
+                            session: logging.session.clone(),
+                        });
+                        snapshot.store(latest);
+                    }
+                    if changed || sampled {
+                        on_update(&snapshot.load(), changed);
                     }
+                    if logging.state() == ConnectionState::Disconnected {
+                        // Don't spin while waiting to retry the connection
+                        thread::sleep(Duration::from_millis(16));
+                    }
+                }
+            })
+        };
+        Self {
+            snapshot,
+            running,
+            thread: Some(thread),
+        }
+    }
+
+    pub fn latest(&self) -> Arc<TelemetrySnapshot> {
+        self.snapshot.load_full()
+    }
+}
+
+impl Drop for TelemetrySampler {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+
+    const VARIABLES: &[Variable] = &[
+        Variable::new("Speed", VariableType::Float),
+        Variable::new("LapLastLapTime", VariableType::Float),
+    ];
+
+    fn frame(speed: f32, last_lap_time: f32) -> HashMap<&'static str, TelemetryValue> {
+        HashMap::from([
+            ("Speed", TelemetryValue::Float(speed)),
+            ("LapLastLapTime", TelemetryValue::Float(last_lap_time)),
+        ])
+    }
+
+    fn scripted(frames: Vec<HashMap<&'static str, TelemetryValue>>) -> Connector {
+        Box::new(move || Ok(Box::new(ScriptedSource::new(frames.clone())) as Box<dyn TelemetrySource>))
+    }
+
+    /// Wait for the sampler to publish a snapshot that passes `done`, or give up after a few seconds.
+    fn wait_for(sampler: &TelemetrySampler, done: impl Fn(&TelemetrySnapshot) -> bool) -> Arc<TelemetrySnapshot> {
+        let start = Instant::now();
+        loop {
+            let snapshot = sampler.latest();
+            if done(&snapshot) || start.elapsed() > Duration::from_secs(5) {
+                return snapshot;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn sampler_reads_a_scripted_source() {
+        let sampler = TelemetrySampler::start(VARIABLES, scripted(vec![frame(30., 92.5)]), |_, _| {});
+        let snapshot = wait_for(&sampler, |snapshot| snapshot.state == ConnectionState::Connected);
+        assert_eq!(snapshot.state, ConnectionState::Connected);
+        assert_eq!(snapshot.telemetry.float("Speed"), Some(30.));
+        assert_eq!(snapshot.telemetry.float("LapLastLapTime"), Some(92.5));
+        assert!(snapshot.telemetry.issues.is_empty());
+    }
+
+    #[test]
+    fn sampler_updates_on_every_sample() {
+        let updates = Arc::new(AtomicUsize::new(0));
+        let changes = Arc::new(AtomicUsize::new(0));
+        let sampler = TelemetrySampler::start(VARIABLES, scripted(vec![frame(30., 92.5)]), {
+            let (updates, changes) = (updates.clone(), changes.clone());
+            move |_, changed| {
+                updates.fetch_add(1, Ordering::Relaxed);
+                if changed {
+                    changes.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        });
+        // The same frame every time, so the snapshot itself never changes after connecting
+        let start = Instant::now();
+        while updates.load(Ordering::Relaxed) < 10 && start.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(updates.load(Ordering::Relaxed) >= 10);
+        // Only connecting changes anything, so only that asks for a repaint
+        assert!(changes.load(Ordering::Relaxed) <= 2);
+        drop(sampler);
+    }
+
+    #[test]
+    fn scripted_frames_play_in_order_and_loop() {
+        let source = ScriptedSource::new(vec![frame(10., 90.), frame(20., 91.)]);
+        let mut logging = IRacingLogging::with_source(Box::new(source), VARIABLES);
+        let mut speeds = Vec::new();
+        for _ in 0..3 {
+            assert!(logging.read_telemetry(Duration::ZERO));
+            speeds.push(logging.telemetry.float("Speed").unwrap());
+        }
+        assert_eq!(speeds, vec![10., 20., 10.]);
+        assert_eq!(logging.telemetry.float("LapLastLapTime"), Some(90.));
+    }
+
+    #[test]
+    fn missing_variable_is_reported_once_and_the_rest_still_read() {
+        let variables = [VARIABLES, &[Variable::new("FuelLevel", VariableType::Float)]].concat();
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let source = ScriptedSource::new(vec![frame(10., 90.), frame(20., 91.)]);
+        let mut logging = IRacingLogging::with_source(Box::new(source), &variables).with_issue_reporter({
+            let reported = reported.clone();
+            move |issue| reported.lock().unwrap().push(issue.clone())
+        });
+        for speed in [10., 20., 10.] {
+            assert!(logging.read_telemetry(Duration::ZERO));
+            assert_eq!(logging.telemetry.float("Speed"), Some(speed));
+            assert_eq!(logging.telemetry.issues, vec![VariableIssue::Missing("FuelLevel")]);
+        }
+        assert_eq!(*reported.lock().unwrap(), vec![VariableIssue::Missing("FuelLevel")]);
+    }
+
+    #[test]
+    fn synthetic_source_has_speed_and_lap_times() {
+        let mut logging = IRacingLogging::with_source(Box::new(SyntheticSource::default()), VARIABLES);
+        assert!(logging.read_telemetry(Duration::from_millis(100)));
+        let speed = logging.telemetry.float("Speed").unwrap();
+        assert!((20.0..=70.0).contains(&speed), "{}", speed);
+        // No lap has been finished yet, which iRacing reports as -1
+        assert_eq!(logging.telemetry.float("LapLastLapTime"), Some(-1.));
+        assert!(logging.telemetry.issues.is_empty());
+    }
+
+    /// A source that stops producing samples while `alive` is false, like iRacing closing.
+    /// With `idle` it stays there while stopped, like the hub sending heartbeats.
+    struct StoppableSource {
+        alive: Arc<AtomicBool>,
+        idle: bool,
+        frames: ScriptedSource,
+    }
+
+    impl TelemetrySource for StoppableSource {
+        fn sample(&mut self, timeout: Duration) -> Result<Sampled, Box<dyn Error>> {
+            if !self.alive.load(Ordering::Relaxed) {
+                if self.idle {
+                    return Ok(Sampled::Idle);
                 }
+                return Err("Source has stopped".into());
+            }
+            self.frames.sample(timeout)
+        }
+
+        fn get(&self, name: &'static str) -> Option<TelemetryValue> {
+            self.frames.get(name)
+        }
+
+        fn session_info_version(&self) -> Option<i32> {
+            None
+        }
+
+        fn session_info(&self) -> Option<String> {
+            None
+        }
+    }
+
+    /// Connects to a `StoppableSource` while `alive` is true, counting every attempt.
+    fn stoppable(alive: &Arc<AtomicBool>, attempts: &Arc<AtomicUsize>) -> Connector {
+        let (alive, attempts) = (alive.clone(), attempts.clone());
+        Box::new(move || {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            if !alive.load(Ordering::Relaxed) {
+                return Err("Sim isn't running".into());
+            }
+            Ok(Box::new(StoppableSource {
+                alive: alive.clone(),
+                idle: false,
+                frames: ScriptedSource::new(vec![frame(30., 92.5)]),
+            }))
+        })
+    }
+
+    fn quick_timings() -> ConnectionTimings {
+        ConnectionTimings {
+            stale_after: Duration::from_millis(30),
+            disconnect_after: Duration::from_millis(90),
+            first_retry_delay: Duration::from_millis(10),
+            max_retry_delay: Duration::from_millis(40),
+        }
+    }
+
+    #[test]
+    fn goes_stale_then_disconnects_when_samples_stop() {
+        let alive = Arc::new(AtomicBool::new(true));
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let mut logging = IRacingLogging::with_connector(stoppable(&alive, &attempts), VARIABLES);
+        logging.timings = quick_timings();
+        assert!(logging.read_telemetry(Duration::ZERO));
+        assert_eq!(logging.state(), ConnectionState::Connected);
+
+        alive.store(false, Ordering::Relaxed);
+        assert!(!logging.read_telemetry(Duration::ZERO));
+        assert_eq!(logging.state(), ConnectionState::Connected);
+        thread::sleep(Duration::from_millis(40));
+        assert!(!logging.read_telemetry(Duration::ZERO));
+        assert_eq!(logging.state(), ConnectionState::Stale);
+        // The last values are kept while stale
+        assert_eq!(logging.telemetry.float("Speed"), Some(30.));
+        thread::sleep(Duration::from_millis(60));
+        assert!(!logging.read_telemetry(Duration::ZERO));
+        assert_eq!(logging.state(), ConnectionState::Disconnected);
+    }
+
+    #[test]
+    fn reconnects_when_the_source_comes_back() {
+        let alive = Arc::new(AtomicBool::new(true));
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let mut logging = IRacingLogging::with_connector(stoppable(&alive, &attempts), VARIABLES);
+        logging.timings = quick_timings();
+        assert!(logging.read_telemetry(Duration::ZERO));
+        alive.store(false, Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(100));
+        assert!(!logging.read_telemetry(Duration::ZERO));
+        assert_eq!(logging.state(), ConnectionState::Disconnected);
+
+        alive.store(true, Ordering::Relaxed);
+        assert!(logging.read_telemetry(Duration::ZERO));
+        assert_eq!(logging.state(), ConnectionState::Connected);
+        assert_eq!(attempts.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn keeps_a_source_that_is_idle() {
+        let alive = Arc::new(AtomicBool::new(true));
+        let source = StoppableSource {
+            alive: alive.clone(),
+            idle: true,
+            frames: ScriptedSource::new(vec![frame(30., 92.5)]),
+        };
+        let mut logging = IRacingLogging::with_source(Box::new(source), VARIABLES);
+        logging.timings = quick_timings();
+        assert!(logging.read_telemetry(Duration::ZERO));
+
+        alive.store(false, Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(100));
+        assert!(!logging.read_telemetry(Duration::ZERO));
+        assert_eq!(logging.state(), ConnectionState::Disconnected);
+        assert!(logging.source.is_some());
+
+        // The same source picks up again, as it can't be reopened
+        alive.store(true, Ordering::Relaxed);
+        assert!(logging.read_telemetry(Duration::ZERO));
+        assert_eq!(logging.state(), ConnectionState::Connected);
+    }
+
+    #[test]
+    fn retries_back_off_up_to_the_limit() {
+        let alive = Arc::new(AtomicBool::new(false));
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let mut logging = IRacingLogging::with_connector(stoppable(&alive, &attempts), VARIABLES);
+        logging.timings = quick_timings();
+        logging.retry_delay = logging.timings.first_retry_delay;
+
+        let mut delays = Vec::new();
+        for _ in 0..4 {
+            thread::sleep(logging.next_attempt.saturating_duration_since(Instant::now()));
+            assert!(!logging.read_telemetry(Duration::ZERO));
+            let waiting = logging.next_attempt.saturating_duration_since(Instant::now());
+            delays.push(logging.retry_delay);
+            // No attempts are made while waiting for the next retry
+            let before = attempts.load(Ordering::Relaxed);
+            assert!(!logging.read_telemetry(Duration::ZERO));
+            if !waiting.is_zero() {
+                assert_eq!(attempts.load(Ordering::Relaxed), before);
             }
-            self.last_update = Instant::now();
-        } 
+        }
+        let ms = Duration::from_millis;
+        assert_eq!(delays, vec![ms(20), ms(40), ms(40), ms(40)]);
+        assert_eq!(logging.state(), ConnectionState::Disconnected);
+
+        // A successful sample starts the backoff over
+        alive.store(true, Ordering::Relaxed);
+        thread::sleep(logging.next_attempt.saturating_duration_since(Instant::now()));
+        assert!(logging.read_telemetry(Duration::ZERO));
+        assert_eq!(logging.retry_delay, ms(10));
     }
 }
@@ -0,0 +1,23 @@
+//! Helpers shared between the unit tests of different modules.
+
+use crate::gaps::CarPosition;
+use std::path::PathBuf;
+
+/// An empty temporary folder for a test to cache things in, unique to `name`
+/// and this test run.
+pub fn cache_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("iracing-overlay-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+/// A car on a 100 second lap, out on track.
+pub fn car(car_idx: usize, lap: i32, lap_dist_pct: f32) -> CarPosition {
+    CarPosition {
+        car_idx,
+        lap,
+        lap_dist_pct,
+        est_time: lap_dist_pct * 100.,
+        on_pit_road: false,
+    }
+}
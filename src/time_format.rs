@@ -0,0 +1,71 @@
+/// Shown in place of times iRacing hasn't set yet, which it reports as zero or negative.
+pub const PLACEHOLDER: &str = "-";
+
+/// Format a lap time as `m:ss.mmm`, with `precision` decimal places (up to 3).
+pub fn lap_time(seconds: f32, precision: usize) -> String {
+    if !seconds.is_finite() || seconds <= 0. {
+        return PLACEHOLDER.to_string();
+    }
+    let precision = precision.min(3);
+    let scale = 10u64.pow(precision as u32);
+    // Rounded as a whole, so 59.9996 becomes 1:00.000 rather than 0:60.000
+    let total = (seconds as f64 * scale as f64).round() as u64;
+    let (minutes, seconds, fraction) = (total / (60 * scale), total / scale % 60, total % scale);
+    if precision == 0 {
+        format!("{}:{:02}", minutes, seconds)
+    } else {
+        format!("{}:{:02}.{:0width$}", minutes, seconds, fraction, width = precision)
+    }
+}
+
+/// Format a difference between times, always signed, e.g. `+0.125` or `-1:02.500`.
+pub fn delta(seconds: f32, precision: usize) -> String {
+    if !seconds.is_finite() {
+        return PLACEHOLDER.to_string();
+    }
+    let precision = precision.min(3);
+    let sign = if seconds < 0. { '-' } else { '+' };
+    let magnitude = seconds.abs();
+    let scale = 10f32.powi(precision as i32);
+    if (magnitude * scale).round() >= 60. * scale {
+        format!("{}{}", sign, lap_time(magnitude, precision))
+    } else {
+        format!("{}{:.*}", sign, precision, magnitude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_lap_times() {
+        assert_eq!(lap_time(93.4567, 3), "1:33.457");
+        assert_eq!(lap_time(93.4567, 1), "1:33.5");
+        assert_eq!(lap_time(93.4567, 0), "1:33");
+        assert_eq!(lap_time(5.05, 3), "0:05.050");
+        assert_eq!(lap_time(125., 5), "2:05.000");
+    }
+
+    #[test]
+    fn rounds_up_into_the_next_minute() {
+        assert_eq!(lap_time(59.9996, 3), "1:00.000");
+        assert_eq!(lap_time(59.96, 1), "1:00.0");
+    }
+
+    #[test]
+    fn placeholder_for_unset_times() {
+        assert_eq!(lap_time(-1., 3), PLACEHOLDER);
+        assert_eq!(lap_time(0., 3), PLACEHOLDER);
+        assert_eq!(lap_time(f32::NAN, 3), PLACEHOLDER);
+        assert_eq!(delta(f32::INFINITY, 3), PLACEHOLDER);
+    }
+
+    #[test]
+    fn formats_deltas() {
+        assert_eq!(delta(0.1254, 3), "+0.125");
+        assert_eq!(delta(-0.5, 2), "-0.50");
+        assert_eq!(delta(0., 3), "+0.000");
+        assert_eq!(delta(-62.5, 3), "-1:02.500");
+    }
+}
@@ -0,0 +1,175 @@
+use crate::telemetry::{IRacingTelemetry, Variable, VariableType};
+use std::collections::VecDeque;
+
+/// The telemetry the inputs overlay plots and reads out.
+pub const VARIABLES: &[Variable] = &[
+    Variable::new("Throttle", VariableType::Float),
+    Variable::new("Brake", VariableType::Float),
+    Variable::new("Clutch", VariableType::Float),
+    Variable::new("SteeringWheelAngle", VariableType::Float),
+    Variable::new("SteeringWheelAngleMax", VariableType::Float),
+    Variable::new("Gear", VariableType::Int),
+    Variable::new("Speed", VariableType::Float),
+];
+
+/// The rate iRacing updates its telemetry at, and so the rate traces are recorded at.
+pub const SAMPLE_RATE: f32 = 60.;
+
+fn samples_in(seconds: f32) -> usize {
+    (seconds * SAMPLE_RATE).round().max(1.) as usize
+}
+
+/// Keeps the most recent `capacity` values, dropping the oldest as new ones arrive.
+#[derive(Clone, Debug)]
+pub struct RingBuffer<T> {
+    values: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T> RingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            values: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Enough room for `seconds` of samples at the sim's rate.
+    pub fn with_duration(seconds: f32) -> Self {
+        Self::new(samples_in(seconds))
+    }
+
+    /// Change how many values are kept, dropping the oldest if there are now too many.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        while self.values.len() > capacity {
+            self.values.pop_front();
+        }
+        self.capacity = capacity;
+    }
+
+    /// Make room for `seconds` of samples, as with `with_duration`.
+    pub fn set_duration(&mut self, seconds: f32) {
+        self.set_capacity(samples_in(seconds));
+    }
+
+    pub fn push(&mut self, value: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.values.len() == self.capacity {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    /// Oldest first.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &T> {
+        self.values.iter()
+    }
+}
+
+/// Driver inputs at one tick, with every value scaled to 0 to 1, or -1 to 1 for steering.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InputSample {
+    pub throttle: f32,
+    pub brake: f32,
+    /// How far the clutch pedal is pressed, which is the opposite of what iRacing reports.
+    pub clutch: f32,
+    /// Positive to the left, as iRacing reports it.
+    pub steering: f32,
+}
+
+impl InputSample {
+    pub fn from_telemetry(telemetry: &IRacingTelemetry) -> Option<Self> {
+        let steering_max = telemetry
+            .float("SteeringWheelAngleMax")
+            .filter(|max| *max > 0.)
+            .unwrap_or(std::f32::consts::PI);
+        Some(Self {
+            throttle: telemetry.float("Throttle")?,
+            brake: telemetry.float("Brake")?,
+            clutch: 1. - telemetry.float("Clutch").unwrap_or(1.),
+            steering: (telemetry.float("SteeringWheelAngle").unwrap_or_default() / steering_max)
+                .clamp(-1., 1.),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(buffer: &RingBuffer<i32>) -> Vec<i32> {
+        buffer.iter().copied().collect()
+    }
+
+    #[test]
+    fn wraps_around_dropping_the_oldest() {
+        let mut buffer = RingBuffer::new(3);
+        for value in 1..=2 {
+            buffer.push(value);
+        }
+        assert_eq!(contents(&buffer), vec![1, 2]);
+        for value in 3..=7 {
+            buffer.push(value);
+        }
+        assert_eq!(contents(&buffer), vec![5, 6, 7]);
+        assert_eq!(buffer.len(), 3);
+    }
+
+    #[test]
+    fn shrinking_drops_the_oldest() {
+        let mut buffer = RingBuffer::new(5);
+        (1..=5).for_each(|value| buffer.push(value));
+        buffer.set_capacity(2);
+        assert_eq!(contents(&buffer), vec![4, 5]);
+        buffer.push(6);
+        assert_eq!(contents(&buffer), vec![5, 6]);
+    }
+
+    #[test]
+    fn growing_keeps_everything() {
+        let mut buffer = RingBuffer::new(2);
+        (1..=3).for_each(|value| buffer.push(value));
+        buffer.set_capacity(4);
+        assert_eq!(contents(&buffer), vec![2, 3]);
+        (4..=6).for_each(|value| buffer.push(value));
+        assert_eq!(contents(&buffer), vec![3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let mut buffer = RingBuffer::new(3);
+        buffer.push(1);
+        buffer.set_capacity(0);
+        buffer.push(2);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn durations_are_in_samples() {
+        assert_eq!(RingBuffer::<i32>::with_duration(2.).capacity(), 120);
+        let mut buffer = RingBuffer::<i32>::with_duration(2.);
+        buffer.set_duration(0.5);
+        assert_eq!(buffer.capacity(), 30);
+        // Always room for at least one
+        buffer.set_duration(0.);
+        assert_eq!(buffer.capacity(), 1);
+    }
+}
@@ -0,0 +1,405 @@
+use crate::session::SessionDetails;
+use crate::telemetry::{IRacingTelemetry, Variable, VariableType};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The telemetry needed to record an outline and plot the cars on it.
+pub const VARIABLES: &[Variable] = &[
+    Variable::new("PlayerCarIdx", VariableType::Int),
+    Variable::new("CarIdxLapDistPct", VariableType::FloatArray),
+    Variable::new("SessionTime", VariableType::Double),
+    Variable::new("Lap", VariableType::Int),
+    Variable::new("LapDistPct", VariableType::Float),
+    Variable::new("OnPitRoad", VariableType::Bool),
+    Variable::new("Speed", VariableType::Float),
+    Variable::new("Yaw", VariableType::Float),
+    // Only written to .ibt files, so live outlines come from the speed and yaw
+    Variable::new("Lat", VariableType::Double),
+    Variable::new("Lon", VariableType::Double),
+];
+
+/// Points in an outline, evenly spaced around the lap.
+const OUTLINE_POINTS: usize = 500;
+
+/// How much of the lap has to have been seen for it to be used.
+const LAP_COVERAGE: f32 = 0.02;
+
+const EARTH_RADIUS: f64 = 6_371_000.;
+
+/// The shape of a track in metres, with point `i` at `i / points.len()` of the way round the lap.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrackOutline {
+    pub track_id: i32,
+    pub points: Vec<(f32, f32)>,
+}
+
+impl TrackOutline {
+    pub fn cache_path(cache_dir: &Path, track_id: i32) -> PathBuf {
+        cache_dir.join(format!("track-{}.toml", track_id))
+    }
+
+    pub fn load(cache_dir: &Path, track_id: i32) -> Result<Self, Box<dyn Error>> {
+        let outline: Self = toml::from_str(&fs::read_to_string(Self::cache_path(cache_dir, track_id))?)?;
+        if outline.points.is_empty() {
+            return Err("Cached track outline has no points".into());
+        }
+        Ok(outline)
+    }
+
+    pub fn save(&self, cache_dir: &Path) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(cache_dir)?;
+        fs::write(Self::cache_path(cache_dir, self.track_id), toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Where on the outline a car `lap_dist_pct` of the way round the lap is.
+    pub fn position(&self, lap_dist_pct: f32) -> Option<(f32, f32)> {
+        let n = self.points.len();
+        if n == 0 {
+            return None;
+        }
+        let at = lap_dist_pct.rem_euclid(1.) * n as f32;
+        let (a, b) = (self.points[at as usize % n], self.points[(at as usize + 1) % n]);
+        let t = at.fract();
+        Some((a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t))
+    }
+
+    /// The smallest rectangle holding the whole outline, as (min x, min y, max x, max y).
+    pub fn bounds(&self) -> Option<(f32, f32, f32, f32)> {
+        self.points.iter().fold(None, |bounds, &(x, y)| {
+            Some(match bounds {
+                None => (x, y, x, y),
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+            })
+        })
+    }
+}
+
+/// The parts of a telemetry sample used to record an outline.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MapSample {
+    pub lap: i32,
+    pub lap_dist_pct: f32,
+    pub on_pit_road: bool,
+    pub session_time: f64,
+    pub lat_lon: Option<(f64, f64)>,
+    pub speed: f32,
+    pub yaw: f32,
+}
+
+impl MapSample {
+    pub fn from_telemetry(telemetry: &IRacingTelemetry) -> Option<Self> {
+        Some(Self {
+            lap: telemetry.int("Lap")?,
+            lap_dist_pct: telemetry.float("LapDistPct")?,
+            on_pit_road: telemetry.bool("OnPitRoad").unwrap_or_default(),
+            session_time: telemetry.double("SessionTime")?,
+            lat_lon: telemetry.double("Lat").zip(telemetry.double("Lon")),
+            speed: telemetry.float("Speed").unwrap_or_default(),
+            yaw: telemetry.float("Yaw").unwrap_or_default(),
+        })
+    }
+}
+
+/// Follows the player's car around a lap, producing an outline once it has a clean one.
+#[derive(Default)]
+pub struct OutlineRecorder {
+    lap: Option<i32>,
+    valid: bool,
+    origin: Option<(f64, f64)>,
+    position: (f32, f32),
+    last_time: Option<f64>,
+    /// (distance, x, y), in order of distance
+    points: Vec<(f32, f32, f32)>,
+}
+
+impl OutlineRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How much of the current lap has been recorded so far.
+    pub fn progress(&self) -> f32 {
+        self.points.last().map_or(0., |(pct, _, _)| *pct)
+    }
+
+    /// Returns the outline's points when `sample` completes a clean lap.
+    pub fn update(&mut self, sample: MapSample) -> Option<Vec<(f32, f32)>> {
+        let position = self.advance(&sample);
+        self.last_time = Some(sample.session_time);
+        let mut outline = None;
+        if self.lap != Some(sample.lap) {
+            let complete = self.lap == Some(sample.lap - 1)
+                && self.points.first().is_some_and(|(pct, _, _)| *pct < LAP_COVERAGE)
+                && self.points.last().is_some_and(|(pct, _, _)| *pct > 1. - LAP_COVERAGE);
+            if complete && self.valid {
+                // Close the loop with where the car crossed the line
+                self.points.push((1. + sample.lap_dist_pct, position.0, position.1));
+                outline = Some(self.resample());
+            }
+            self.lap = Some(sample.lap);
+            self.valid = true;
+            self.points.clear();
+            self.origin = sample.lat_lon;
+            self.position = (0., 0.);
+        } else {
+            self.position = position;
+        }
+
+        self.valid &= !sample.on_pit_road;
+        match self.points.last() {
+            // Going backwards means a reset or a tow, so the lap can't be used
+            Some((pct, _, _)) if sample.lap_dist_pct < *pct => self.valid = false,
+            _ => self.points.push((sample.lap_dist_pct, self.position.0, self.position.1)),
+        }
+        outline
+    }
+
+    /// Where `sample` puts the car relative to the start of the lap.
+    fn advance(&mut self, sample: &MapSample) -> (f32, f32) {
+        match sample.lat_lon {
+            Some((lat, lon)) => {
+                let (lat0, lon0) = *self.origin.get_or_insert((lat, lon));
+                (
+                    ((lon - lon0).to_radians() * lat0.to_radians().cos() * EARTH_RADIUS) as f32,
+                    ((lat - lat0).to_radians() * EARTH_RADIUS) as f32,
+                )
+            }
+            None => {
+                // Dead reckoning, ignoring gaps long enough to be pauses or dropouts
+                let dt = self
+                    .last_time
+                    .map_or(0., |last| (sample.session_time - last) as f32);
+                if !(0. ..0.5).contains(&dt) {
+                    return self.position;
+                }
+                (
+                    self.position.0 + sample.speed * sample.yaw.cos() * dt,
+                    self.position.1 + sample.speed * sample.yaw.sin() * dt,
+                )
+            }
+        }
+    }
+
+    /// The recorded position at `pct`, interpolating between samples.
+    fn at(&self, pct: f32) -> (f32, f32) {
+        let after = self.points.partition_point(|(p, _, _)| *p < pct).min(self.points.len() - 1);
+        let (a, b) = (self.points[after.saturating_sub(1)], self.points[after]);
+        let t = if b.0 > a.0 { ((pct - a.0) / (b.0 - a.0)).clamp(0., 1.) } else { 0. };
+        (a.1 + (b.1 - a.1) * t, a.2 + (b.2 - a.2) * t)
+    }
+
+    fn resample(&self) -> Vec<(f32, f32)> {
+        let first = self.points[0].0;
+        // Spread any gap between the end of the lap and its start around the
+        // whole lap, so drift from dead reckoning doesn't leave a jump
+        let (start, end) = (self.at(first), self.at(first + 1.));
+        let drift = (end.0 - start.0, end.1 - start.1);
+        (0..OUTLINE_POINTS)
+            .map(|i| {
+                let pct = (i as f32 / OUTLINE_POINTS as f32).max(first);
+                let (x, y) = self.at(pct);
+                let progress = pct - first;
+                (x - drift.0 * progress, y - drift.1 * progress)
+            })
+            .collect()
+    }
+}
+
+/// Keeps the outline for the current track, loading it from the cache or
+/// recording and caching it as needed. Without a cache directory outlines are
+/// only kept for as long as the mapper.
+pub struct TrackMapper {
+    cache_dir: Option<PathBuf>,
+    track_id: Option<i32>,
+    outline: Option<TrackOutline>,
+    recorder: OutlineRecorder,
+}
+
+impl TrackMapper {
+    pub fn new(cache_dir: Option<PathBuf>) -> Self {
+        if cache_dir.is_none() {
+            eprintln!("No cache directory for track outlines, so they won't be kept between sessions");
+        }
+        Self {
+            cache_dir,
+            track_id: None,
+            outline: None,
+            recorder: OutlineRecorder::new(),
+        }
+    }
+
+    /// Alongside the config file.
+    pub fn default_cache_dir() -> Option<PathBuf> {
+        let config = confy::get_configuration_file_path("iracing-overlays", None).ok()?;
+        Some(config.parent()?.to_path_buf())
+    }
+
+    pub fn outline(&self) -> Option<&TrackOutline> {
+        self.outline.as_ref()
+    }
+
+    pub fn recording_progress(&self) -> f32 {
+        self.recorder.progress()
+    }
+
+    pub fn update(&mut self, telemetry: &IRacingTelemetry, session: Option<&SessionDetails>) {
+        let Some(track_id) = session.map(|session| session.weekend_info.track_id) else {
+            return;
+        };
+        if self.track_id != Some(track_id) {
+            self.track_id = Some(track_id);
+            self.outline = self.cache_dir.as_ref().and_then(|dir| TrackOutline::load(dir, track_id).ok());
+            self.recorder = OutlineRecorder::new();
+        }
+        if self.outline.is_some() {
+            return;
+        }
+        let Some(points) = MapSample::from_telemetry(telemetry).and_then(|sample| self.recorder.update(sample)) else {
+            return;
+        };
+        let outline = TrackOutline { track_id, points };
+        if let Some(Err(e)) = self.cache_dir.as_ref().map(|dir| outline.save(dir)) {
+            eprintln!("Couldn't cache the track outline: {}", e);
+        }
+        self.outline = Some(outline);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ibt::{write_ibt, IbtFile, IbtSource, PlaybackRate};
+    use crate::telemetry::IRacingLogging;
+    use crate::test_support::cache_dir;
+    use std::f64::consts::TAU;
+    use std::time::Duration;
+
+    const SESSION: &str = "---\nWeekendInfo:\n TrackID: 42\n...\n";
+    const RECORDS_PER_LAP: usize = 200;
+    const RADIUS: f64 = 500.;
+
+    /// Writes an .ibt file of a car driving laps of a circle centred on (0, 0).
+    fn circle_laps(laps: usize) -> Vec<u8> {
+        // (name, type, offset)
+        let variables = [
+            ("SessionTime", 5, 0),
+            ("Lat", 5, 8),
+            ("Lon", 5, 16),
+            ("Lap", 2, 24),
+            ("LapDistPct", 4, 28),
+            ("Speed", 4, 32),
+            ("Yaw", 4, 36),
+            ("OnPitRoad", 1, 40),
+        ];
+        let records: Vec<Vec<u8>> = (0..laps * RECORDS_PER_LAP)
+            .map(|record| {
+                let pct = (record % RECORDS_PER_LAP) as f64 / RECORDS_PER_LAP as f64;
+                let angle = pct * TAU;
+                let (x, y) = (RADIUS * angle.cos(), RADIUS * angle.sin());
+                let mut bytes = Vec::with_capacity(44);
+                bytes.extend((record as f64 / 60.).to_le_bytes());
+                bytes.extend((y / EARTH_RADIUS).to_degrees().to_le_bytes());
+                bytes.extend((x / EARTH_RADIUS).to_degrees().to_le_bytes());
+                bytes.extend(((record / RECORDS_PER_LAP) as i32 + 1).to_le_bytes());
+                bytes.extend((pct as f32).to_le_bytes());
+                bytes.extend(((TAU * RADIUS * 60. / RECORDS_PER_LAP as f64) as f32).to_le_bytes());
+                bytes.extend(((angle + TAU / 4.) as f32).to_le_bytes());
+                bytes.extend([0; 4]);
+                bytes
+            })
+            .collect();
+        write_ibt(&variables, SESSION, &records)
+    }
+
+    fn replay(bytes: &[u8], mapper: &mut TrackMapper) {
+        let source = IbtSource::new(IbtFile::from_bytes(bytes).unwrap(), PlaybackRate::Unpaced);
+        let mut logging = IRacingLogging::with_source(Box::new(source), VARIABLES);
+        while logging.read_telemetry(Duration::ZERO) {
+            mapper.update(&logging.telemetry, logging.session.as_deref());
+        }
+    }
+
+    /// Every point should be the circle's radius from its centre, which starts `RADIUS` west of the first point.
+    fn assert_circle(outline: &TrackOutline, tolerance: f32) {
+        let (x0, y0) = outline.points[0];
+        for (x, y) in &outline.points {
+            let radius = ((x - x0 + RADIUS as f32).powi(2) + (y - y0).powi(2)).sqrt();
+            assert!((radius - RADIUS as f32).abs() < tolerance, "{} is off the circle", radius);
+        }
+    }
+
+    #[test]
+    fn records_an_outline_from_a_replay() {
+        let mut mapper = TrackMapper::new(None);
+        replay(&circle_laps(3), &mut mapper);
+
+        let outline = mapper.outline().expect("no outline recorded");
+        assert_eq!(outline.track_id, 42);
+        assert_eq!(outline.points.len(), OUTLINE_POINTS);
+        assert_circle(outline, 1.);
+        let quarter = outline.position(0.25).unwrap();
+        assert!((quarter.0 - (outline.points[0].0 - RADIUS as f32)).abs() < 1.);
+        assert!((quarter.1 - RADIUS as f32).abs() < 1.);
+    }
+
+    #[test]
+    fn caches_outlines_per_track() {
+        let dir = cache_dir("cache");
+        let mut mapper = TrackMapper::new(Some(dir.clone()));
+        replay(&circle_laps(3), &mut mapper);
+        let recorded = mapper.outline().unwrap().clone();
+
+        assert_eq!(TrackOutline::load(&dir, 42).unwrap(), recorded);
+        assert!(TrackOutline::load(&dir, 43).is_err());
+
+        // A single partial lap isn't enough to record from, so this has to come from the cache
+        let mut mapper = TrackMapper::new(Some(dir.clone()));
+        replay(&circle_laps(1), &mut mapper);
+        assert_eq!(mapper.outline(), Some(&recorded));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn dead_reckons_without_lat_lon() {
+        let mut recorder = OutlineRecorder::new();
+        let mut outline = None;
+        for record in 0..RECORDS_PER_LAP * 3 {
+            let pct = (record % RECORDS_PER_LAP) as f64 / RECORDS_PER_LAP as f64;
+            outline = outline.or(recorder.update(MapSample {
+                lap: (record / RECORDS_PER_LAP) as i32 + 1,
+                lap_dist_pct: pct as f32,
+                on_pit_road: false,
+                session_time: record as f64 / 60.,
+                lat_lon: None,
+                speed: (TAU * RADIUS * 60. / RECORDS_PER_LAP as f64) as f32,
+                yaw: (pct * TAU + TAU / 4.) as f32,
+            }));
+        }
+        let outline = TrackOutline {
+            track_id: 1,
+            points: outline.expect("no outline recorded"),
+        };
+        assert_circle(&outline, RADIUS as f32 * 0.05);
+    }
+
+    #[test]
+    fn ignores_laps_through_the_pits() {
+        let mut recorder = OutlineRecorder::new();
+        for record in 0..RECORDS_PER_LAP * 3 {
+            let pct = (record % RECORDS_PER_LAP) as f32 / RECORDS_PER_LAP as f32;
+            let outline = recorder.update(MapSample {
+                lap: (record / RECORDS_PER_LAP) as i32 + 1,
+                lap_dist_pct: pct,
+                on_pit_road: pct > 0.9,
+                session_time: record as f64 / 60.,
+                lat_lon: Some((0., pct as f64 / 1000.)),
+                speed: 0.,
+                yaw: 0.,
+            });
+            assert!(outline.is_none());
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::telemetry::{IRacingTelemetry, Variable, VariableType};
+
+/// The variable names for one corner of the car. Temperatures are carcass
+/// temperatures across the tread from the car's left to its right, which are
+/// the only ones iRacing updates on track; iRacing doesn't publish brake temperatures.
+pub struct CornerVariables {
+    pub name: &'static str,
+    pub temps: [&'static str; 3],
+    /// Only updated when the car is in the pits.
+    pub wear: [&'static str; 3],
+    /// As set in the garage, so only updated when the car is in the pits.
+    pub cold_pressure: &'static str,
+}
+
+/// Left front, right front, left rear, right rear.
+pub const CORNERS: [CornerVariables; 4] = [
+    CornerVariables {
+        name: "LF",
+        temps: ["LFtempCL", "LFtempCM", "LFtempCR"],
+        wear: ["LFwearL", "LFwearM", "LFwearR"],
+        cold_pressure: "LFcoldPressure",
+    },
+    CornerVariables {
+        name: "RF",
+        temps: ["RFtempCL", "RFtempCM", "RFtempCR"],
+        wear: ["RFwearL", "RFwearM", "RFwearR"],
+        cold_pressure: "RFcoldPressure",
+    },
+    CornerVariables {
+        name: "LR",
+        temps: ["LRtempCL", "LRtempCM", "LRtempCR"],
+        wear: ["LRwearL", "LRwearM", "LRwearR"],
+        cold_pressure: "LRcoldPressure",
+    },
+    CornerVariables {
+        name: "RR",
+        temps: ["RRtempCL", "RRtempCM", "RRtempCR"],
+        wear: ["RRwearL", "RRwearM", "RRwearR"],
+        cold_pressure: "RRcoldPressure",
+    },
+];
+
+pub fn variables() -> Vec<Variable> {
+    CORNERS
+        .iter()
+        .flat_map(|corner| {
+            corner
+                .temps
+                .iter()
+                .chain(corner.wear.iter())
+                .chain(std::iter::once(&corner.cold_pressure))
+                .map(|name| Variable::new(name, VariableType::Float))
+        })
+        .collect()
+}
+
+/// One tyre's readings, in °C, fractions of tread remaining and kPa.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TyreReading {
+    pub temps: [Option<f32>; 3],
+    pub wear: [Option<f32>; 3],
+    pub cold_pressure: Option<f32>,
+}
+
+impl TyreReading {
+    pub fn from_telemetry(telemetry: &IRacingTelemetry, corner: &CornerVariables) -> Self {
+        Self {
+            temps: corner.temps.map(|name| telemetry.float(name)),
+            wear: corner.wear.map(|name| telemetry.float(name)),
+            cold_pressure: telemetry.float(corner.cold_pressure),
+        }
+    }
+
+    /// All four corners, in the same order as `CORNERS`.
+    pub fn all(telemetry: &IRacingTelemetry) -> [Self; 4] {
+        [0, 1, 2, 3].map(|i| Self::from_telemetry(telemetry, &CORNERS[i]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::{IRacingLogging, ScriptedSource, TelemetryValue};
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    fn telemetry(values: &[(&'static str, f32)]) -> IRacingTelemetry {
+        let frame: HashMap<_, _> = values
+            .iter()
+            .map(|(name, value)| (*name, TelemetryValue::Float(*value)))
+            .collect();
+        let source = ScriptedSource::new(vec![frame]);
+        let mut logging = IRacingLogging::with_source(Box::new(source), &variables())
+            .with_issue_reporter(|_| {});
+        assert!(logging.read_telemetry(Duration::ZERO));
+        logging.telemetry
+    }
+
+    #[test]
+    fn every_corner_has_its_own_variables() {
+        let variables = variables();
+        assert_eq!(variables.len(), 4 * 7);
+        assert!(variables.iter().all(|variable| variable.kind == VariableType::Float));
+        for corner in &CORNERS {
+            let names = corner.temps.iter().chain(&corner.wear).chain([&corner.cold_pressure]);
+            assert!(names.into_iter().all(|name| name.starts_with(corner.name)));
+        }
+    }
+
+    #[test]
+    fn reads_a_corner() {
+        let telemetry = telemetry(&[
+            ("LFtempCL", 85.),
+            ("LFtempCM", 90.),
+            ("LFtempCR", 95.),
+            ("LFwearL", 0.98),
+            ("LFwearM", 0.97),
+            ("LFwearR", 0.96),
+            ("LFcoldPressure", 165.),
+        ]);
+        assert_eq!(
+            TyreReading::from_telemetry(&telemetry, &CORNERS[0]),
+            TyreReading {
+                temps: [Some(85.), Some(90.), Some(95.)],
+                wear: [Some(0.98), Some(0.97), Some(0.96)],
+                cold_pressure: Some(165.),
+            }
+        );
+    }
+
+    #[test]
+    fn missing_values_are_none() {
+        let telemetry = telemetry(&[("RRtempCM", 70.)]);
+        let reading = TyreReading::from_telemetry(&telemetry, &CORNERS[3]);
+        assert_eq!(reading.temps, [None, Some(70.), None]);
+        assert_eq!(reading.wear, [None; 3]);
+        assert_eq!(reading.cold_pressure, None);
+    }
+
+    #[test]
+    fn all_corners_in_order() {
+        let telemetry = telemetry(&[
+            ("LFcoldPressure", 1.),
+            ("RFcoldPressure", 2.),
+            ("LRcoldPressure", 3.),
+            ("RRcoldPressure", 4.),
+        ]);
+        let pressures = TyreReading::all(&telemetry).map(|reading| reading.cold_pressure);
+        assert_eq!(pressures, [Some(1.), Some(2.), Some(3.), Some(4.)]);
+    }
+}
@@ -1,6 +1,10 @@
+use crate::tyre_data::TyreReading;
 use eframe::egui;
 use serde::{Deserialize, Serialize};
-use std::process::Child;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub use crate::registry::{Overlays, WindowsConfig};
 
 pub fn toggle_ui(ui: &mut egui::Ui, on: &mut bool) -> egui::Response {
     // Widget code can be broken up in four steps:
@@ -62,10 +66,122 @@ pub fn toggle(on: &mut bool) -> impl egui::Widget + '_ {
     move |ui: &mut egui::Ui| toggle_ui(ui, on)
 }
 
-pub enum Overlays {
-    Home,
-    Speed,
-    LastLapTime,
+/// Where to find the overlay executables: `overlay_dir` if it's set, or else
+/// next to the running app, which covers both an install and `cargo build`.
+pub fn overlay_dir(overlay_dir: Option<&Path>) -> Result<PathBuf, String> {
+    if let Some(dir) = overlay_dir {
+        return Ok(dir.to_path_buf());
+    }
+    let exe = std::env::current_exe().map_err(|e| format!("Can't find the running app: {}", e))?;
+    exe.parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| format!("{} has no parent folder", exe.display()))
+}
+
+/// The executable for the overlay called `name` in `dir`.
+pub fn overlay_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}{}", name, std::env::consts::EXE_SUFFIX))
+}
+
+/// Green inside the `low..=high` window, shading to blue below it and red above it.
+pub fn heat_colour(value: f32, low: f32, high: f32) -> egui::Color32 {
+    let green = [40., 200., 40.];
+    let (towards, amount) = if value < low {
+        ([40., 110., 220.], low - value)
+    } else if value > high {
+        ([220., 40., 40.], value - high)
+    } else {
+        (green, 0.)
+    };
+    // Fully shaded at half the window's width outside it
+    let t = (amount / ((high - low) / 2.).max(1.)).min(1.);
+    let [r, g, b] = [0, 1, 2].map(|i| (green[i] + (towards[i] - green[i]) * t) as u8);
+    egui::Color32::from_rgb(r, g, b)
+}
+
+pub fn car_outline_ui(ui: &mut egui::Ui, tyres: &[TyreReading; 4], config: &TyresConfig) -> egui::Response {
+    let font_size = config.font_size;
+    let desired_size = egui::vec2(font_size * 18., font_size * 14.);
+    let (rect, response) = ui.allocate_exact_size(desired_size, egui::Sense::hover());
+    if !ui.is_rect_visible(rect) {
+        return response;
+    }
+    let painter = ui.painter();
+    let text_colour = config.overlay_fontcolour;
+    let small = egui::FontId::proportional(font_size * 0.7);
+
+    // The car's body down the middle, with a tyre at each corner
+    let body = egui::Rect::from_center_size(
+        rect.center(),
+        egui::vec2(rect.width() * 0.2, rect.height() * 0.85),
+    );
+    painter.rect_filled(body, body.width() * 0.3, config.car_colour);
+    let tyre_size = egui::vec2(rect.width() * 0.08, rect.height() * 0.22);
+    let gap = font_size * 0.2;
+
+    for (i, tyre) in tyres.iter().enumerate() {
+        let left = i % 2 == 0;
+        let front = i < 2;
+        let x = if left { body.left() - gap - tyre_size.x } else { body.right() + gap };
+        let y = if front {
+            body.top() + body.height() * 0.08
+        } else {
+            body.bottom() - body.height() * 0.08 - tyre_size.y
+        };
+        let tyre_rect = egui::Rect::from_min_size(egui::pos2(x, y), tyre_size);
+
+        // One stripe per temperature, across the tread from the car's left to its right
+        let stripe = tyre_size.x / 3.;
+        for (j, temp) in tyre.temps.iter().enumerate() {
+            let stripe_rect = egui::Rect::from_min_size(
+                egui::pos2(tyre_rect.left() + stripe * j as f32, tyre_rect.top()),
+                egui::vec2(stripe, tyre_size.y),
+            );
+            let fill = temp.map_or(egui::Color32::DARK_GRAY, |temp| {
+                heat_colour(temp, config.temp_min, config.temp_max)
+            });
+            painter.rect_filled(stripe_rect, 0., fill);
+        }
+        painter.rect_stroke(tyre_rect, 2., egui::Stroke::new(1., text_colour));
+
+        let number = |value: Option<f32>, scale: f32| value.map_or("-".to_string(), |v| format!("{:.0}", v * scale));
+        let lines = [
+            (
+                tyre.temps.map(|temp| number(temp, 1.)).join(" ") + " °C",
+                text_colour,
+            ),
+            (
+                format!("{} kPa*", number(tyre.cold_pressure, 1.)),
+                tyre.cold_pressure.map_or(text_colour, |pressure| {
+                    heat_colour(pressure, config.pressure_min, config.pressure_max)
+                }),
+            ),
+            (tyre.wear.map(|wear| number(wear, 100.)).join(" ") + " %*", text_colour),
+        ];
+        // Labels sit outside the tyres, away from the car
+        let (anchor, align) = if left {
+            (egui::pos2(tyre_rect.left() - gap, tyre_rect.top()), egui::Align2::RIGHT_TOP)
+        } else {
+            (egui::pos2(tyre_rect.right() + gap, tyre_rect.top()), egui::Align2::LEFT_TOP)
+        };
+        for (line, (text, colour)) in lines.into_iter().enumerate() {
+            let pos = anchor + egui::vec2(0., line as f32 * font_size * 0.9);
+            painter.text(pos, align, text, small.clone(), colour);
+        }
+    }
+
+    painter.text(
+        rect.center_bottom(),
+        egui::Align2::CENTER_BOTTOM,
+        "* updated in the pits",
+        small,
+        text_colour,
+    );
+    response
+}
+
+pub fn car_outline<'a>(tyres: &'a [TyreReading; 4], config: &'a TyresConfig) -> impl egui::Widget + 'a {
+    move |ui: &mut egui::Ui| car_outline_ui(ui, tyres, config)
 }
 
 #[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
@@ -76,11 +192,40 @@ pub enum SpeedUnits {
     Kilometresperhour,
 }
 
+impl SpeedUnits {
+    /// The unit's label, and what to multiply metres per second by to get it.
+    pub fn label_and_multiplier(&self) -> (&'static str, f32) {
+        match self {
+            SpeedUnits::Metrespersecond => ("m/s", 1.),
+            SpeedUnits::Milesperhour => ("mph", 3600. / 1609.),
+            SpeedUnits::Kilometresperhour => ("kph", 3.6),
+        }
+    }
+}
+
+/// Which lap the delta overlay compares against.
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+pub enum DeltaReference {
+    #[default]
+    BestLap,
+    SessionBestLap,
+    OptimalLap,
+}
+
 #[derive(Serialize, Deserialize)]
+// Options added later fall back to their defaults in existing config files
+#[serde(default)]
 pub struct HomeConfig {
     pub font_size: f32,
     pub bg_colour: egui::Color32,
     pub font_colour: egui::Color32,
+    /// Start overlays again when they crash
+    pub auto_restart: bool,
+    /// How many times in a row a crashing overlay is restarted
+    pub restart_limit: u32,
+    /// Draw the overlays as windows of the main app, instead of starting a
+    /// program for each
+    pub single_process: bool,
 }
 
 impl Default for HomeConfig {
@@ -89,6 +234,9 @@ impl Default for HomeConfig {
             font_size: 18.,
             bg_colour: egui::Color32::WHITE.linear_multiply(0.5),
             font_colour: egui::Color32::BLACK.linear_multiply(0.5),
+            auto_restart: true,
+            restart_limit: 3,
+            single_process: false,
         }
     }
 }
@@ -114,10 +262,69 @@ impl Default for SpeedConfig {
     }
 }
 
+/// Where the shift lights start to fill, are all lit and start to flash.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct ShiftPoints {
+    pub first_rpm: f32,
+    pub shift_rpm: f32,
+    pub blink_rpm: f32,
+}
+
+impl Default for ShiftPoints {
+    fn default() -> Self {
+        Self {
+            first_rpm: 5000.,
+            shift_rpm: 7000.,
+            blink_rpm: 7300.,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ShiftLightConfig {
+    pub display: bool,
+    pub font_size: f32,
+    pub led_count: usize,
+    pub show_gear: bool,
+    /// Shift points to use instead of the sim's, by the car's screen name
+    pub custom_shift_points: BTreeMap<String, ShiftPoints>,
+    pub low_colour: egui::Color32,
+    pub mid_colour: egui::Color32,
+    pub high_colour: egui::Color32,
+    pub shift_colour: egui::Color32,
+    pub overlay_bgcolour: egui::Color32,
+    pub overlay_fontcolour: egui::Color32,
+}
+
+impl Default for ShiftLightConfig {
+    fn default() -> Self {
+        Self {
+            display: false,
+            font_size: 18.,
+            led_count: 10,
+            show_gear: true,
+            custom_shift_points: BTreeMap::new(),
+            low_colour: egui::Color32::from_rgb(40, 200, 40),
+            mid_colour: egui::Color32::from_rgb(240, 200, 0),
+            high_colour: egui::Color32::from_rgb(220, 40, 40),
+            shift_colour: egui::Color32::from_rgb(40, 110, 220),
+            overlay_bgcolour: egui::Color32::WHITE.linear_multiply(0.5),
+            overlay_fontcolour: egui::Color32::BLACK.linear_multiply(0.5),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
+// Options added later fall back to their defaults in existing config files
+#[serde(default)]
 pub struct LaspLapTimeConfig {
     pub display: bool,
     pub font_size: f32,
+    /// Decimal places shown on times
+    pub precision: usize,
+    pub show_best_lap: bool,
+    pub show_delta_to_best: bool,
+    pub show_lap_number: bool,
     pub overlay_bgcolour: egui::Color32,
     pub overlay_fontcolour: egui::Color32,
 }
@@ -127,21 +334,380 @@ impl Default for LaspLapTimeConfig {
         Self {
             display: false,
             font_size: 18.,
+            precision: 3,
+            show_best_lap: false,
+            show_delta_to_best: false,
+            show_lap_number: false,
+            overlay_bgcolour: egui::Color32::WHITE.linear_multiply(0.5),
+            overlay_fontcolour: egui::Color32::BLACK.linear_multiply(0.5),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RelativeConfig {
+    pub display: bool,
+    pub font_size: f32,
+    /// How many cars to show either side of the player
+    pub cars_each_side: usize,
+    pub show_irating: bool,
+    pub overlay_bgcolour: egui::Color32,
+    pub overlay_fontcolour: egui::Color32,
+    pub lapping_colour: egui::Color32,
+    pub lapped_colour: egui::Color32,
+}
+
+impl Default for RelativeConfig {
+    fn default() -> Self {
+        Self {
+            display: false,
+            font_size: 18.,
+            cars_each_side: 3,
+            show_irating: true,
+            overlay_bgcolour: egui::Color32::WHITE.linear_multiply(0.5),
+            overlay_fontcolour: egui::Color32::BLACK.linear_multiply(0.5),
+            lapping_colour: egui::Color32::from_rgb(220, 60, 40),
+            lapped_colour: egui::Color32::from_rgb(40, 110, 220),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StandingsConfig {
+    pub display: bool,
+    pub font_size: f32,
+    pub group_by_class: bool,
+    /// Rows to show in each class, or overall when not grouping by class
+    pub max_rows: usize,
+    pub always_show_player: bool,
+    pub overlay_bgcolour: egui::Color32,
+    pub overlay_fontcolour: egui::Color32,
+}
+
+impl Default for StandingsConfig {
+    fn default() -> Self {
+        Self {
+            display: false,
+            font_size: 18.,
+            group_by_class: true,
+            max_rows: 10,
+            always_show_player: true,
+            overlay_bgcolour: egui::Color32::WHITE.linear_multiply(0.5),
+            overlay_fontcolour: egui::Color32::BLACK.linear_multiply(0.5),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FuelConfig {
+    pub display: bool,
+    pub font_size: f32,
+    /// Extra laps of fuel to allow for when working out how much is needed to finish
+    pub margin_laps: f32,
+    pub overlay_bgcolour: egui::Color32,
+    pub overlay_fontcolour: egui::Color32,
+}
+
+impl Default for FuelConfig {
+    fn default() -> Self {
+        Self {
+            display: false,
+            font_size: 18.,
+            margin_laps: 1.,
+            overlay_bgcolour: egui::Color32::WHITE.linear_multiply(0.5),
+            overlay_fontcolour: egui::Color32::BLACK.linear_multiply(0.5),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct InputsConfig {
+    pub display: bool,
+    pub font_size: f32,
+    /// Seconds of inputs to show in the traces
+    pub history_seconds: f32,
+    pub units: SpeedUnits,
+    pub throttle_colour: egui::Color32,
+    pub brake_colour: egui::Color32,
+    pub clutch_colour: egui::Color32,
+    pub steering_colour: egui::Color32,
+    pub overlay_bgcolour: egui::Color32,
+    pub overlay_fontcolour: egui::Color32,
+}
+
+impl Default for InputsConfig {
+    fn default() -> Self {
+        Self {
+            display: false,
+            font_size: 18.,
+            history_seconds: 5.,
+            units: SpeedUnits::default(),
+            throttle_colour: egui::Color32::from_rgb(40, 200, 40),
+            brake_colour: egui::Color32::from_rgb(220, 40, 40),
+            clutch_colour: egui::Color32::from_rgb(40, 110, 220),
+            steering_colour: egui::Color32::GRAY,
             overlay_bgcolour: egui::Color32::WHITE.linear_multiply(0.5),
             overlay_fontcolour: egui::Color32::BLACK.linear_multiply(0.5),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Default)]
-pub struct WindowsConfig {
-    pub home_config: HomeConfig,
-    pub speed_config: SpeedConfig,
-    pub lastlaptime_config: LaspLapTimeConfig,
+#[derive(Serialize, Deserialize)]
+pub struct DeltaConfig {
+    pub display: bool,
+    pub font_size: f32,
+    pub reference: DeltaReference,
+    /// Seconds either side of zero the bar fills up at
+    pub range_seconds: f32,
+    pub faster_colour: egui::Color32,
+    pub slower_colour: egui::Color32,
+    pub overlay_bgcolour: egui::Color32,
+    pub overlay_fontcolour: egui::Color32,
 }
 
-#[derive(Default)]
-pub struct WindowProcesses {
-    pub speed: Option<Child>,
-    pub lastlaptime: Option<Child>,
+impl Default for DeltaConfig {
+    fn default() -> Self {
+        Self {
+            display: false,
+            font_size: 18.,
+            reference: DeltaReference::default(),
+            range_seconds: 2.,
+            faster_colour: egui::Color32::from_rgb(40, 200, 40),
+            slower_colour: egui::Color32::from_rgb(220, 40, 40),
+            overlay_bgcolour: egui::Color32::WHITE.linear_multiply(0.5),
+            overlay_fontcolour: egui::Color32::BLACK.linear_multiply(0.5),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TrackMapConfig {
+    pub display: bool,
+    pub font_size: f32,
+    pub dot_size: f32,
+    pub track_colour: egui::Color32,
+    pub player_colour: egui::Color32,
+    pub overlay_bgcolour: egui::Color32,
+    pub overlay_fontcolour: egui::Color32,
+}
+
+impl Default for TrackMapConfig {
+    fn default() -> Self {
+        Self {
+            display: false,
+            font_size: 18.,
+            dot_size: 6.,
+            track_colour: egui::Color32::GRAY,
+            player_colour: egui::Color32::from_rgb(255, 200, 0),
+            overlay_bgcolour: egui::Color32::WHITE.linear_multiply(0.5),
+            overlay_fontcolour: egui::Color32::BLACK.linear_multiply(0.5),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TyresConfig {
+    pub display: bool,
+    pub font_size: f32,
+    /// Carcass temperature window in °C
+    pub temp_min: f32,
+    pub temp_max: f32,
+    /// Cold pressure window in kPa
+    pub pressure_min: f32,
+    pub pressure_max: f32,
+    pub car_colour: egui::Color32,
+    pub overlay_bgcolour: egui::Color32,
+    pub overlay_fontcolour: egui::Color32,
+}
+
+impl Default for TyresConfig {
+    fn default() -> Self {
+        Self {
+            display: false,
+            font_size: 18.,
+            temp_min: 80.,
+            temp_max: 100.,
+            pressure_min: 150.,
+            pressure_max: 190.,
+            car_colour: egui::Color32::DARK_GRAY,
+            overlay_bgcolour: egui::Color32::WHITE.linear_multiply(0.5),
+            overlay_fontcolour: egui::Color32::BLACK.linear_multiply(0.5),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PitStrategyConfig {
+    pub display: bool,
+    pub font_size: f32,
+    /// Seconds expected to be stopped in the box, added to the learned pit lane loss
+    pub stop_seconds: f32,
+    /// How many cars to show either side of where the player would rejoin
+    pub cars_each_side: usize,
+    pub overlay_bgcolour: egui::Color32,
+    pub overlay_fontcolour: egui::Color32,
+}
+
+impl Default for PitStrategyConfig {
+    fn default() -> Self {
+        Self {
+            display: false,
+            font_size: 18.,
+            stop_seconds: 20.,
+            cars_each_side: 2,
+            overlay_bgcolour: egui::Color32::WHITE.linear_multiply(0.5),
+            overlay_fontcolour: egui::Color32::BLACK.linear_multiply(0.5),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LapHistoryConfig {
+    pub display: bool,
+    pub font_size: f32,
+    /// How many finished laps to list
+    pub laps: usize,
+    pub show_sectors: bool,
+    /// The best lap and sectors of the session
+    pub best_colour: egui::Color32,
+    /// Sectors that were a personal best when they were set
+    pub personal_best_colour: egui::Color32,
+    pub invalid_colour: egui::Color32,
+    pub overlay_bgcolour: egui::Color32,
+    pub overlay_fontcolour: egui::Color32,
+}
+
+impl Default for LapHistoryConfig {
+    fn default() -> Self {
+        Self {
+            display: false,
+            font_size: 18.,
+            laps: 5,
+            show_sectors: true,
+            best_colour: egui::Color32::from_rgb(160, 60, 220),
+            personal_best_colour: egui::Color32::from_rgb(40, 180, 60),
+            invalid_colour: egui::Color32::GRAY,
+            overlay_bgcolour: egui::Color32::WHITE.linear_multiply(0.5),
+            overlay_fontcolour: egui::Color32::BLACK.linear_multiply(0.5),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FlagsConfig {
+    pub display: bool,
+    pub font_size: f32,
+    pub show_incidents: bool,
+    pub incident_flash_colour: egui::Color32,
+    pub overlay_bgcolour: egui::Color32,
+    pub overlay_fontcolour: egui::Color32,
+}
+
+impl Default for FlagsConfig {
+    fn default() -> Self {
+        Self {
+            display: false,
+            font_size: 18.,
+            show_incidents: true,
+            incident_flash_colour: egui::Color32::from_rgb(255, 140, 0),
+            overlay_bgcolour: egui::Color32::WHITE.linear_multiply(0.5),
+            overlay_fontcolour: egui::Color32::BLACK.linear_multiply(0.5),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RadarConfig {
+    pub display: bool,
+    pub font_size: f32,
+    /// Height of the radar in points
+    pub size: f32,
+    /// How see-through the whole radar is, from 0 to 1
+    pub opacity: f32,
+    /// Metres ahead and behind to show cars
+    pub range_metres: f32,
+    pub player_colour: egui::Color32,
+    pub car_colour: egui::Color32,
+    pub warning_colour: egui::Color32,
+    pub overlay_bgcolour: egui::Color32,
+    pub overlay_fontcolour: egui::Color32,
+}
+
+impl Default for RadarConfig {
+    fn default() -> Self {
+        Self {
+            display: false,
+            font_size: 18.,
+            size: 200.,
+            opacity: 0.8,
+            range_metres: 20.,
+            player_colour: egui::Color32::from_rgb(255, 200, 0),
+            car_colour: egui::Color32::LIGHT_GRAY,
+            warning_colour: egui::Color32::from_rgb(220, 40, 40),
+            overlay_bgcolour: egui::Color32::WHITE.linear_multiply(0.5),
+            overlay_fontcolour: egui::Color32::BLACK.linear_multiply(0.5),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlays_live_next_to_the_app_by_default() {
+        let exe = std::env::current_exe().unwrap();
+        assert_eq!(overlay_dir(None).unwrap(), exe.parent().unwrap());
+        let custom = Path::new("overlays");
+        assert_eq!(overlay_dir(Some(custom)).unwrap(), custom);
+    }
+
+    #[test]
+    fn overlay_path_adds_the_platform_suffix() {
+        let path = overlay_path(Path::new("overlays"), "speed");
+        assert_eq!(path.parent(), Some(Path::new("overlays")));
+        assert_eq!(path.file_stem().and_then(|stem| stem.to_str()), Some("speed"));
+        assert_eq!(path.extension().is_some(), cfg!(windows));
+    }
+
+    #[test]
+    fn speed_units_convert_from_metres_per_second() {
+        let convert = |units: SpeedUnits| {
+            let (label, multiplier) = units.label_and_multiplier();
+            (label, (10. * multiplier).round())
+        };
+        assert_eq!(convert(SpeedUnits::Metrespersecond), ("m/s", 10.));
+        assert_eq!(convert(SpeedUnits::Kilometresperhour), ("kph", 36.));
+        assert_eq!(convert(SpeedUnits::Milesperhour), ("mph", 22.));
+    }
+
+    #[test]
+    fn heat_colour_is_green_inside_the_window() {
+        let green = egui::Color32::from_rgb(40, 200, 40);
+        for value in [80., 90., 100.] {
+            assert_eq!(heat_colour(value, 80., 100.), green, "{}", value);
+        }
+    }
+
+    #[test]
+    fn heat_colour_shades_to_blue_below_the_window() {
+        // Halfway to fully shaded, at a quarter of the window's width below it
+        assert_eq!(heat_colour(75., 80., 100.), egui::Color32::from_rgb(40, 155, 130));
+        assert_eq!(heat_colour(70., 80., 100.), egui::Color32::from_rgb(40, 110, 220));
+        assert_eq!(heat_colour(0., 80., 100.), egui::Color32::from_rgb(40, 110, 220));
+    }
+
+    #[test]
+    fn heat_colour_shades_to_red_above_the_window() {
+        assert_eq!(heat_colour(105., 80., 100.), egui::Color32::from_rgb(130, 120, 40));
+        assert_eq!(heat_colour(110., 80., 100.), egui::Color32::from_rgb(220, 40, 40));
+        assert_eq!(heat_colour(500., 80., 100.), egui::Color32::from_rgb(220, 40, 40));
+    }
+
+    #[test]
+    fn heat_colour_handles_a_narrow_window() {
+        // Shading never happens over less than a unit, so an empty window doesn't divide by zero
+        assert_eq!(heat_colour(90., 90., 90.), egui::Color32::from_rgb(40, 200, 40));
+        assert_eq!(heat_colour(91., 90., 90.), egui::Color32::from_rgb(220, 40, 40));
+    }
 }
@@ -0,0 +1,139 @@
+use super::{overlay_frame, Overlay, Recorder};
+use crate::lap_delta::{self, DeltaReading, DeltaTracker, Trend};
+use crate::telemetry::{TelemetrySnapshot, Variable};
+use crate::util::{DeltaConfig, DeltaReference};
+use eframe::egui;
+use std::sync::{Arc, Mutex};
+
+pub struct DeltaWidget {
+    tracker: Arc<Mutex<DeltaTracker>>,
+}
+
+impl DeltaWidget {
+    fn render_bar(ui: &mut egui::Ui, config: &DeltaConfig, reading: &DeltaReading) {
+        let size = egui::vec2(ui.available_width(), config.font_size);
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        if !ui.is_rect_visible(rect) {
+            return;
+        }
+        let painter = ui.painter();
+        let fill = (reading.delta / config.range_seconds).clamp(-1., 1.);
+        let colour = if reading.delta < 0. {
+            config.faster_colour
+        } else {
+            config.slower_colour
+        };
+        // Faster fills to the right of the middle, slower to the left
+        let end = rect.center().x - fill * rect.width() / 2.;
+        let bar = egui::Rect::from_x_y_ranges(
+            rect.center().x.min(end)..=rect.center().x.max(end),
+            rect.y_range(),
+        );
+        painter.rect_filled(bar, 0., colour);
+        painter.vline(
+            rect.center().x,
+            rect.y_range(),
+            egui::Stroke::new(1., config.overlay_fontcolour),
+        );
+    }
+
+    fn render_trend(ui: &mut egui::Ui, config: &DeltaConfig, trend: Trend) {
+        let size = egui::Vec2::splat(config.font_size);
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        if !ui.is_rect_visible(rect) {
+            return;
+        }
+        let rect = rect.shrink(rect.width() * 0.2);
+        let points = match trend {
+            Trend::Gaining => vec![rect.left_top(), rect.right_top(), rect.center_bottom()],
+            Trend::Losing => vec![rect.left_bottom(), rect.right_bottom(), rect.center_top()],
+            Trend::Steady => return,
+        };
+        let colour = match trend {
+            Trend::Gaining => config.faster_colour,
+            _ => config.slower_colour,
+        };
+        ui.painter().add(egui::Shape::convex_polygon(points, colour, egui::Stroke::NONE));
+    }
+}
+
+impl Overlay for DeltaWidget {
+    type Config = DeltaConfig;
+    const TITLE: &'static str = "Delta";
+    const BINARY: &'static str = "delta";
+
+    fn variables() -> Vec<Variable> {
+        lap_delta::VARIABLES.to_vec()
+    }
+
+    fn new(config: &DeltaConfig) -> Self {
+        Self {
+            tracker: Arc::new(Mutex::new(DeltaTracker::new(config.reference.clone()))),
+        }
+    }
+
+    /// Our own delta needs every sample, not just the ones that get drawn
+    fn recorder(&self) -> Option<Recorder> {
+        let tracker = self.tracker.clone();
+        Some(Box::new(move |snapshot: &TelemetrySnapshot| {
+            if let Ok(mut tracker) = tracker.lock() {
+                tracker.update(&snapshot.telemetry);
+            }
+        }))
+    }
+
+    fn reconfigure(&self, config: &DeltaConfig) {
+        if let Ok(mut tracker) = self.tracker.lock() {
+            tracker.set_reference(config.reference.clone());
+        }
+    }
+
+    fn show(&self, ui: &mut egui::Ui, config: &DeltaConfig, snapshot: &TelemetrySnapshot) {
+        let reading = self.tracker.lock().ok().and_then(|tracker| tracker.reading());
+        overlay_frame(ui, config.font_size, config.overlay_bgcolour, config.overlay_fontcolour, |ui| {
+            if !snapshot.state.has_data() {
+                ui.label("Waiting for iRacing...");
+                return;
+            }
+            let Some(reading) = reading else {
+                ui.label("No delta yet");
+                return;
+            };
+            Self::render_bar(ui, config, &reading);
+            ui.horizontal(|ui| {
+                ui.label(format!("{:+.2}", reading.delta));
+                Self::render_trend(ui, config, reading.trend);
+                if reading.internal {
+                    ui.label("(own best)");
+                }
+            });
+        });
+    }
+
+    fn settings(ui: &mut egui::Ui, config: &mut DeltaConfig) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Compare to:");
+            changed |= ui.radio_value(&mut config.reference, DeltaReference::BestLap, "best lap").changed();
+            changed |= ui.radio_value(&mut config.reference, DeltaReference::SessionBestLap, "session best lap").changed();
+            changed |= ui.radio_value(&mut config.reference, DeltaReference::OptimalLap, "optimal lap").changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Seconds to fill the bar");
+            changed |= ui.add(egui::DragValue::new(&mut config.range_seconds).range(0.1..=10.0).speed(0.1)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick faster colour");
+            changed |= ui.color_edit_button_srgba(&mut config.faster_colour).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick slower colour");
+            changed |= ui.color_edit_button_srgba(&mut config.slower_colour).changed();
+        });
+
+        changed
+    }
+}
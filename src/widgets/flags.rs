@@ -0,0 +1,148 @@
+use super::{overlay_frame, Overlay, Recorder};
+use crate::session_flags::{self, FlagPanel, IncidentCount, SessionFlags};
+use crate::telemetry::{TelemetrySnapshot, Variable};
+use crate::util::{toggle, FlagsConfig};
+use eframe::egui::{self, RichText};
+use std::sync::{Arc, Mutex};
+
+/// How many times a second the incident count flashes after going up.
+const FLASH_RATE: f64 = 4.;
+
+/// The panel's colour, its text colour and what it says.
+fn flag_style(panel: FlagPanel) -> (egui::Color32, egui::Color32, &'static str) {
+    use egui::Color32;
+    match panel {
+        FlagPanel::Disqualified => (Color32::BLACK, Color32::WHITE, "DISQUALIFIED"),
+        FlagPanel::Black => (Color32::BLACK, Color32::WHITE, "BLACK FLAG"),
+        FlagPanel::Meatball => (Color32::BLACK, Color32::from_rgb(255, 140, 0), "REPAIR REQUIRED"),
+        FlagPanel::Red => (Color32::from_rgb(220, 30, 30), Color32::WHITE, "RED FLAG"),
+        FlagPanel::Chequered => (Color32::WHITE, Color32::BLACK, "CHEQUERED FLAG"),
+        FlagPanel::FullCourseYellow => (Color32::from_rgb(255, 210, 0), Color32::BLACK, "FULL COURSE YELLOW"),
+        FlagPanel::LocalYellow => (Color32::from_rgb(255, 210, 0), Color32::BLACK, "YELLOW"),
+        FlagPanel::Blue => (Color32::from_rgb(30, 90, 220), Color32::from_rgb(255, 210, 0), "BLUE FLAG"),
+        FlagPanel::White => (Color32::WHITE, Color32::BLACK, "WHITE FLAG"),
+        FlagPanel::Debris => (Color32::from_rgb(255, 210, 0), Color32::from_rgb(220, 30, 30), "DEBRIS"),
+        FlagPanel::Green => (Color32::from_rgb(30, 170, 60), Color32::WHITE, "GREEN"),
+    }
+}
+
+pub struct FlagsWidget {
+    incidents: Arc<Mutex<IncidentCount>>,
+}
+
+impl FlagsWidget {
+    fn render_panel(ui: &mut egui::Ui, config: &FlagsConfig, panel: FlagPanel) {
+        let size = egui::vec2(ui.available_width(), config.font_size * 3.);
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        if !ui.is_rect_visible(rect) {
+            return;
+        }
+        let (fill, text_colour, text) = flag_style(panel);
+        let painter = ui.painter();
+        painter.rect_filled(rect, 4., fill);
+        match panel {
+            FlagPanel::Chequered => {
+                let square = rect.height() / 4.;
+                for row in 0..4 {
+                    for column in 0..(rect.width() / square).ceil() as usize {
+                        if (row + column) % 2 == 1 {
+                            let min = rect.min + egui::vec2(column as f32 * square, row as f32 * square);
+                            let square = egui::Rect::from_min_size(min, egui::Vec2::splat(square)).intersect(rect);
+                            painter.rect_filled(square, 0., egui::Color32::BLACK);
+                        }
+                    }
+                }
+            }
+            FlagPanel::Meatball => {
+                painter.circle_filled(rect.left_center() + egui::vec2(rect.height() / 2., 0.), rect.height() * 0.35, text_colour);
+            }
+            _ => {}
+        }
+        let font = egui::FontId::proportional(config.font_size * 1.4);
+        let galley = painter.layout_no_wrap(text.to_string(), font, text_colour);
+        // A backing so the text can be read over the chequers
+        if panel == FlagPanel::Chequered {
+            let backing = egui::Rect::from_center_size(rect.center(), galley.size() + egui::vec2(8., 4.));
+            painter.rect_filled(backing, 2., fill);
+        }
+        painter.galley(rect.center() - galley.size() / 2., galley, text_colour);
+    }
+}
+
+impl Overlay for FlagsWidget {
+    type Config = FlagsConfig;
+    const TITLE: &'static str = "Flags";
+    const BINARY: &'static str = "flags";
+
+    fn variables() -> Vec<Variable> {
+        session_flags::VARIABLES.to_vec()
+    }
+
+    fn new(_config: &FlagsConfig) -> Self {
+        Self {
+            incidents: Arc::new(Mutex::new(IncidentCount::new())),
+        }
+    }
+
+    /// Every sample, so no increase in the incident count is missed
+    fn recorder(&self) -> Option<Recorder> {
+        let incidents = self.incidents.clone();
+        Some(Box::new(move |snapshot: &TelemetrySnapshot| {
+            let telemetry = &snapshot.telemetry;
+            if let (Ok(mut incidents), Some(count), Some(session_time)) = (
+                incidents.lock(),
+                telemetry.int("PlayerCarMyIncidentCount"),
+                telemetry.double("SessionTime"),
+            ) {
+                incidents.update(count, session_time);
+            }
+        }))
+    }
+
+    fn show(&self, ui: &mut egui::Ui, config: &FlagsConfig, snapshot: &TelemetrySnapshot) {
+        overlay_frame(ui, config.font_size, config.overlay_bgcolour, config.overlay_fontcolour, |ui| {
+            if !snapshot.state.has_data() {
+                ui.label("Waiting for iRacing...");
+                return;
+            }
+            match SessionFlags::from_telemetry(&snapshot.telemetry).and_then(|flags| flags.panel()) {
+                Some(panel) => Self::render_panel(ui, config, panel),
+                None => {
+                    ui.label("No flags");
+                }
+            }
+            if !config.show_incidents {
+                return;
+            }
+            let Ok(incidents) = self.incidents.lock() else {
+                return;
+            };
+            let Some(count) = incidents.count() else {
+                return;
+            };
+            let session_time = snapshot.telemetry.double("SessionTime").unwrap_or_default();
+            let text = RichText::new(format!("Incidents: {}x", count));
+            let lit = (ui.input(|i| i.time) * FLASH_RATE * 2.).floor() as i64 % 2 == 0;
+            if incidents.flashing(session_time) && lit {
+                ui.label(text.strong().background_color(config.incident_flash_colour));
+            } else {
+                ui.label(text);
+            }
+        });
+    }
+
+    fn settings(ui: &mut egui::Ui, config: &mut FlagsConfig) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Show incidents");
+            changed |= ui.add(toggle(&mut config.show_incidents)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick new incident colour");
+            changed |= ui.color_edit_button_srgba(&mut config.incident_flash_colour).changed();
+        });
+
+        changed
+    }
+}
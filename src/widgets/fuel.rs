@@ -0,0 +1,98 @@
+use super::{overlay_frame, Overlay, Recorder};
+use crate::fuel_calc::{self, FuelCalculator, FuelEstimate, FuelSample};
+use crate::telemetry::{TelemetrySnapshot, Variable};
+use crate::util::FuelConfig;
+use eframe::egui;
+use std::sync::{Arc, Mutex};
+
+fn litres(value: Option<f32>) -> String {
+    value.map_or("-".to_string(), |value| format!("{:.2} l", value))
+}
+
+pub struct FuelWidget {
+    calculator: Arc<Mutex<FuelCalculator>>,
+}
+
+impl Overlay for FuelWidget {
+    type Config = FuelConfig;
+    const TITLE: &'static str = "Fuel";
+    const BINARY: &'static str = "fuel";
+
+    fn variables() -> Vec<Variable> {
+        fuel_calc::VARIABLES.to_vec()
+    }
+
+    fn new(_config: &FuelConfig) -> Self {
+        Self {
+            calculator: Arc::new(Mutex::new(FuelCalculator::new())),
+        }
+    }
+
+    /// Usage is measured per lap, so needs every sample, not just the ones that get drawn.
+    fn recorder(&self) -> Option<Recorder> {
+        let calculator = self.calculator.clone();
+        Some(Box::new(move |snapshot: &TelemetrySnapshot| {
+            if let (Ok(mut calculator), Some(sample)) =
+                (calculator.lock(), FuelSample::from_telemetry(&snapshot.telemetry))
+            {
+                calculator.update(sample);
+            }
+        }))
+    }
+
+    fn show(&self, ui: &mut egui::Ui, config: &FuelConfig, snapshot: &TelemetrySnapshot) {
+        overlay_frame(ui, config.font_size, config.overlay_bgcolour, config.overlay_fontcolour, |ui| {
+            if !snapshot.state.has_data() {
+                ui.label("Waiting for iRacing...");
+                return;
+            }
+            let Some(fuel_level) = snapshot.telemetry.float("FuelLevel") else {
+                ui.label("Fuel level unavailable");
+                return;
+            };
+            let Ok(calculator) = self.calculator.lock() else {
+                return;
+            };
+            let session = snapshot.session.as_deref();
+            let estimate = FuelEstimate::new(
+                &calculator,
+                &snapshot.telemetry,
+                snapshot
+                    .telemetry
+                    .int("SessionNum")
+                    .and_then(|session_num| session?.session(session_num)),
+                session.map(|session| &session.driver_info),
+                config.margin_laps,
+            );
+            egui::Grid::new("fuel").show(ui, |ui| {
+                let rows = [
+                    ("Fuel", litres(Some(fuel_level))),
+                    ("Last lap", litres(estimate.last)),
+                    ("Average", litres(estimate.average)),
+                    ("Max", litres(estimate.max)),
+                    (
+                        "Laps left",
+                        estimate.laps_remaining.map_or("-".to_string(), |laps| format!("{:.1}", laps)),
+                    ),
+                    ("To finish", litres(estimate.to_finish)),
+                    ("Add at stop", litres(estimate.to_add)),
+                ];
+                for (label, value) in rows {
+                    ui.label(label);
+                    ui.label(value);
+                    ui.end_row();
+                }
+            });
+        });
+    }
+
+    fn settings(ui: &mut egui::Ui, config: &mut FuelConfig) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Extra laps of fuel");
+            changed |= ui.add(egui::DragValue::new(&mut config.margin_laps).range(0.0..=5.0).speed(0.1)).changed();
+        });
+
+        changed
+    }
+}
@@ -0,0 +1,145 @@
+use super::{overlay_frame, Overlay, Recorder};
+use crate::telemetry::{TelemetrySnapshot, Variable};
+use crate::trace::{self, InputSample, RingBuffer};
+use crate::util::{InputsConfig, SpeedUnits};
+use eframe::egui;
+use std::sync::{Arc, Mutex};
+
+pub struct InputsWidget {
+    history: Arc<Mutex<RingBuffer<InputSample>>>,
+}
+
+impl InputsWidget {
+    fn render_traces(&self, ui: &mut egui::Ui, config: &InputsConfig) {
+        let size = egui::vec2(ui.available_width(), config.font_size * 4.);
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        if !ui.is_rect_visible(rect) {
+            return;
+        }
+        let painter = ui.painter();
+        let grid = egui::Stroke::new(1., config.overlay_fontcolour.gamma_multiply(0.3));
+        for fraction in [0., 0.5, 1.] {
+            let y = egui::lerp(rect.bottom()..=rect.top(), fraction);
+            painter.hline(rect.x_range(), y, grid);
+        }
+
+        let Ok(history) = self.history.lock() else {
+            return;
+        };
+        let step = rect.width() / (history.capacity().max(2) - 1) as f32;
+        let start = rect.right() - step * (history.len().max(1) - 1) as f32;
+        let trace = |value: fn(&InputSample) -> f32, colour: egui::Color32| {
+            let points: Vec<egui::Pos2> = history
+                .iter()
+                .enumerate()
+                .map(|(i, sample)| {
+                    egui::pos2(
+                        start + step * i as f32,
+                        egui::lerp(rect.bottom()..=rect.top(), value(sample).clamp(0., 1.)),
+                    )
+                })
+                .collect();
+            egui::Shape::line(points, egui::Stroke::new(2., colour))
+        };
+        // Steering is drawn around the middle, with left at the top
+        painter.add(trace(|s| 0.5 + s.steering / 2., config.steering_colour));
+        painter.add(trace(|s| s.clutch, config.clutch_colour));
+        painter.add(trace(|s| s.brake, config.brake_colour));
+        painter.add(trace(|s| s.throttle, config.throttle_colour));
+    }
+}
+
+impl Overlay for InputsWidget {
+    type Config = InputsConfig;
+    const TITLE: &'static str = "Inputs";
+    const BINARY: &'static str = "inputs";
+
+    fn variables() -> Vec<Variable> {
+        trace::VARIABLES.to_vec()
+    }
+
+    fn new(config: &InputsConfig) -> Self {
+        Self {
+            history: Arc::new(Mutex::new(RingBuffer::with_duration(config.history_seconds))),
+        }
+    }
+
+    /// Record from the sampling thread, so no samples are lost when repaints are skipped
+    fn recorder(&self) -> Option<Recorder> {
+        let history = self.history.clone();
+        Some(Box::new(move |snapshot: &TelemetrySnapshot| {
+            if let Some(sample) = InputSample::from_telemetry(&snapshot.telemetry) {
+                if let Ok(mut history) = history.lock() {
+                    history.push(sample);
+                }
+            }
+        }))
+    }
+
+    fn reconfigure(&self, config: &InputsConfig) {
+        if let Ok(mut history) = self.history.lock() {
+            history.set_duration(config.history_seconds);
+        }
+    }
+
+    fn show(&self, ui: &mut egui::Ui, config: &InputsConfig, snapshot: &TelemetrySnapshot) {
+        overlay_frame(ui, config.font_size, config.overlay_bgcolour, config.overlay_fontcolour, |ui| {
+            if !snapshot.state.has_data() {
+                ui.label("Waiting for iRacing...");
+                return;
+            }
+            ui.horizontal(|ui| {
+                let gear = match snapshot.telemetry.int("Gear") {
+                    Some(-1) => "R".to_string(),
+                    Some(0) => "N".to_string(),
+                    Some(gear) => gear.to_string(),
+                    None => "-".to_string(),
+                };
+                ui.label(gear);
+                let (unitstring, multiplier) = config.units.label_and_multiplier();
+                match snapshot.telemetry.float("Speed") {
+                    Some(speed) => ui.label(format!("{:.0} {}", speed * multiplier, unitstring)),
+                    None => ui.label("Speed unavailable"),
+                };
+            });
+            self.render_traces(ui, config);
+        });
+    }
+
+    fn settings(ui: &mut egui::Ui, config: &mut InputsConfig) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Seconds of history");
+            changed |= ui.add(egui::DragValue::new(&mut config.history_seconds).range(1.0..=30.0).speed(0.5)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Units:");
+            changed |= ui.radio_value(&mut config.units, SpeedUnits::Metrespersecond, "metres per second").changed();
+            changed |= ui.radio_value(&mut config.units, SpeedUnits::Milesperhour, "miles per hour").changed();
+            changed |= ui.radio_value(&mut config.units, SpeedUnits::Kilometresperhour, "kilometres per hour").changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick throttle colour");
+            changed |= ui.color_edit_button_srgba(&mut config.throttle_colour).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick brake colour");
+            changed |= ui.color_edit_button_srgba(&mut config.brake_colour).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick clutch colour");
+            changed |= ui.color_edit_button_srgba(&mut config.clutch_colour).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick steering colour");
+            changed |= ui.color_edit_button_srgba(&mut config.steering_colour).changed();
+        });
+
+        changed
+    }
+}
@@ -0,0 +1,153 @@
+use super::{overlay_frame, Overlay, Recorder};
+use crate::lap_history::{self, LapHistory, LapRecord, SectorTime, TimingSample};
+use crate::telemetry::{TelemetrySnapshot, Variable};
+use crate::time_format;
+use crate::util::{toggle, LapHistoryConfig};
+use eframe::egui::{self, RichText};
+use std::sync::{Arc, Mutex};
+
+pub struct LapHistoryWidget {
+    history: Arc<Mutex<LapHistory>>,
+}
+
+impl LapHistoryWidget {
+    fn sector_text(config: &LapHistoryConfig, history: &LapHistory, sector: usize, time: &SectorTime) -> RichText {
+        let text = RichText::new(format!("{:.3}", time.time));
+        if !time.valid {
+            text.color(config.invalid_colour)
+        } else if history.is_best_sector(sector, time) {
+            text.color(config.best_colour)
+        } else if time.personal_best {
+            text.color(config.personal_best_colour)
+        } else {
+            text
+        }
+    }
+
+    fn render_lap(ui: &mut egui::Ui, config: &LapHistoryConfig, history: &LapHistory, lap: &LapRecord) {
+        let label = if lap.pit {
+            format!("{} (pit)", lap.lap)
+        } else {
+            lap.lap.to_string()
+        };
+        ui.label(label);
+        let time = RichText::new(time_format::lap_time(lap.time, 3));
+        let time = if !lap.valid {
+            time.color(config.invalid_colour).strikethrough()
+        } else if history.best_lap() == Some(lap.time) {
+            time.color(config.best_colour).strong()
+        } else {
+            time
+        };
+        ui.label(time);
+        if config.show_sectors {
+            for (sector, time) in lap.sectors.iter().enumerate() {
+                ui.label(Self::sector_text(config, history, sector, time));
+            }
+        }
+        ui.end_row();
+    }
+}
+
+impl Overlay for LapHistoryWidget {
+    type Config = LapHistoryConfig;
+    const TITLE: &'static str = "Lap History";
+    const BINARY: &'static str = "laphistory";
+
+    fn variables() -> Vec<Variable> {
+        lap_history::VARIABLES.to_vec()
+    }
+
+    fn new(config: &LapHistoryConfig) -> Self {
+        Self {
+            history: Arc::new(Mutex::new(LapHistory::new(config.laps, Vec::new()))),
+        }
+    }
+
+    /// Sectors are timed between samples, so every one is needed
+    fn recorder(&self) -> Option<Recorder> {
+        let history = self.history.clone();
+        Some(Box::new(move |snapshot: &TelemetrySnapshot| {
+            if let Ok(mut history) = history.lock() {
+                if let Some(session) = snapshot.session.as_deref() {
+                    history.set_sectors(LapHistory::sector_starts(session));
+                }
+                if let Some(sample) = TimingSample::from_telemetry(&snapshot.telemetry) {
+                    history.update(sample);
+                }
+            }
+        }))
+    }
+
+    fn reconfigure(&self, config: &LapHistoryConfig) {
+        if let Ok(mut history) = self.history.lock() {
+            history.set_laps(config.laps);
+        }
+    }
+
+    fn show(&self, ui: &mut egui::Ui, config: &LapHistoryConfig, snapshot: &TelemetrySnapshot) {
+        overlay_frame(ui, config.font_size, config.overlay_bgcolour, config.overlay_fontcolour, |ui| {
+            if !snapshot.state.has_data() {
+                ui.label("Waiting for iRacing...");
+                return;
+            }
+            let Ok(history) = self.history.lock() else {
+                return;
+            };
+            egui::Grid::new("laps").striped(true).show(ui, |ui| {
+                ui.label("Lap");
+                ui.label("Time");
+                if config.show_sectors {
+                    for sector in 1..=history.sector_count() {
+                        ui.label(format!("S{}", sector));
+                    }
+                }
+                ui.end_row();
+
+                // The lap in progress, then the finished ones newest first
+                if config.show_sectors {
+                    ui.label("");
+                    ui.label("");
+                    for (sector, time) in history.current_sectors().iter().enumerate() {
+                        ui.label(Self::sector_text(config, &history, sector, time));
+                    }
+                    ui.end_row();
+                }
+                let laps: Vec<&LapRecord> = history.laps().collect();
+                for lap in laps.into_iter().rev() {
+                    Self::render_lap(ui, config, &history, lap);
+                }
+            });
+        });
+    }
+
+    fn settings(ui: &mut egui::Ui, config: &mut LapHistoryConfig) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Laps to show");
+            changed |= ui.add(egui::DragValue::new(&mut config.laps).range(1..=20)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Show sectors");
+            changed |= ui.add(toggle(&mut config.show_sectors)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick best lap and sector colour");
+            changed |= ui.color_edit_button_srgba(&mut config.best_colour).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick personal best sector colour");
+            changed |= ui.color_edit_button_srgba(&mut config.personal_best_colour).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick invalid lap colour");
+            changed |= ui.color_edit_button_srgba(&mut config.invalid_colour).changed();
+        });
+
+        changed
+    }
+}
@@ -0,0 +1,87 @@
+use super::{overlay_frame, Overlay};
+use crate::telemetry::{TelemetrySnapshot, Variable, VariableType};
+use crate::time_format;
+use crate::util::{toggle, LaspLapTimeConfig};
+use eframe::egui;
+
+const VARIABLES: &[Variable] = &[
+    Variable::new("LapLastLapTime", VariableType::Float),
+    Variable::new("LapBestLapTime", VariableType::Float),
+    Variable::new("LapCompleted", VariableType::Int),
+];
+
+pub struct LastLapTimeWidget;
+
+impl Overlay for LastLapTimeWidget {
+    type Config = LaspLapTimeConfig;
+    const TITLE: &'static str = "Last Lap Time";
+    const BINARY: &'static str = "lastlaptime";
+
+    fn variables() -> Vec<Variable> {
+        VARIABLES.to_vec()
+    }
+
+    fn new(_config: &LaspLapTimeConfig) -> Self {
+        LastLapTimeWidget
+    }
+
+    fn show(&self, ui: &mut egui::Ui, config: &LaspLapTimeConfig, snapshot: &TelemetrySnapshot) {
+        overlay_frame(ui, config.font_size, config.overlay_bgcolour, config.overlay_fontcolour, |ui| {
+            if !snapshot.state.has_data() {
+                ui.label("Waiting for iRacing...");
+                return;
+            }
+            let Some(lastlaptime) = snapshot.telemetry.float("LapLastLapTime") else {
+                ui.label("Last lap time unavailable");
+                return;
+            };
+            let precision = config.precision;
+            if config.show_lap_number {
+                let lap = snapshot.telemetry.int("LapCompleted").filter(|lap| *lap > 0);
+                ui.label(format!(
+                    "Lap: {}",
+                    lap.map_or(time_format::PLACEHOLDER.to_string(), |lap| lap.to_string())
+                ));
+            }
+            ui.label(format!("Last lap: {}", time_format::lap_time(lastlaptime, precision)));
+            let bestlaptime = snapshot.telemetry.float("LapBestLapTime").unwrap_or_default();
+            if config.show_best_lap {
+                ui.label(format!("Best lap: {}", time_format::lap_time(bestlaptime, precision)));
+            }
+            if config.show_delta_to_best {
+                // Only meaningful once both laps have been set
+                let delta = if lastlaptime > 0. && bestlaptime > 0. {
+                    time_format::delta(lastlaptime - bestlaptime, precision)
+                } else {
+                    time_format::PLACEHOLDER.to_string()
+                };
+                ui.label(format!("Delta to best: {}", delta));
+            }
+        });
+    }
+
+    fn settings(ui: &mut egui::Ui, config: &mut LaspLapTimeConfig) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Decimal places");
+            changed |= ui.add(egui::DragValue::new(&mut config.precision).range(0..=3)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Show best lap");
+            changed |= ui.add(toggle(&mut config.show_best_lap)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Show delta to best");
+            changed |= ui.add(toggle(&mut config.show_delta_to_best)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Show lap number");
+            changed |= ui.add(toggle(&mut config.show_lap_number)).changed();
+        });
+
+        changed
+    }
+}
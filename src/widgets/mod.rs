@@ -0,0 +1,198 @@
+//! The overlays' contents, shared by the standalone overlay apps and the
+//! viewports the main app hosts when running everything in one process.
+
+pub mod delta;
+pub mod flags;
+pub mod fuel;
+pub mod inputs;
+pub mod lastlaptime;
+pub mod laphistory;
+pub mod pitstrategy;
+pub mod radar;
+pub mod relative;
+pub mod shiftlights;
+pub mod speed;
+pub mod standings;
+pub mod trackmap;
+pub mod tyres;
+
+use crate::config_watch::{ConfigWatcher, CONFIG_CHECK_INTERVAL};
+use crate::registry::Registered;
+use crate::telemetry::{self, TelemetrySampler, TelemetrySnapshot, Variable};
+use crate::util::{toggle, WindowsConfig};
+use eframe::egui::{self, CentralPanel, Context};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Called from the sampling thread with every new snapshot.
+pub type Recorder = Box<dyn Fn(&TelemetrySnapshot) + Send>;
+
+/// An overlay's contents and settings. Every overlay's config has the
+/// `display`, `font_size`, `overlay_bgcolour` and `overlay_fontcolour`
+/// settings, which the registry shows for it.
+pub trait Overlay: Sized {
+    type Config: Default + Serialize + DeserializeOwned;
+    /// Shown on the main app's button and the overlay's window
+    const TITLE: &'static str;
+    /// The overlay's executable, without the platform's suffix
+    const BINARY: &'static str;
+
+    /// The telemetry the overlay reads.
+    fn variables() -> Vec<Variable>;
+
+    fn new(config: &Self::Config) -> Self;
+
+    /// For overlays keeping their own history, which needs every sample
+    /// rather than just the ones that get drawn.
+    fn recorder(&self) -> Option<Recorder> {
+        None
+    }
+
+    fn show(&self, ui: &mut egui::Ui, config: &Self::Config, snapshot: &TelemetrySnapshot);
+
+    /// Apply a changed config to anything `new` built from it.
+    fn reconfigure(&self, _config: &Self::Config) {}
+
+    /// The overlay's own settings, shown between the font size and colours.
+    /// Returns true if any of them were changed.
+    fn settings(_ui: &mut egui::Ui, _config: &mut Self::Config) -> bool {
+        false
+    }
+}
+
+/// A colour as the SDK gives them, as 0xRRGGBB.
+pub fn colour(rgb: u32) -> egui::Color32 {
+    egui::Color32::from_rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+}
+
+/// Fill `ui` with an overlay's background, and show `add_contents` in its
+/// font. The style is set on the frame rather than the context, so overlays
+/// sharing a context each keep their own look.
+pub fn overlay_frame(
+    ui: &mut egui::Ui,
+    font_size: f32,
+    bgcolour: egui::Color32,
+    fontcolour: egui::Color32,
+    add_contents: impl FnOnce(&mut egui::Ui),
+) {
+    egui::Frame::central_panel(ui.style()).fill(bgcolour).show(ui, |ui| {
+        ui.set_min_size(ui.available_size());
+        ui.style_mut().text_styles = [
+            (
+                egui::TextStyle::Body,
+                egui::FontId::proportional(font_size),
+            ),
+        ]
+        .into();
+        ui.visuals_mut().override_text_color = Some(fontcolour);
+        add_contents(ui);
+    });
+}
+
+/// Give an overlay the whole of its window, leaving the background to it.
+pub fn overlay_window(ctx: &Context, add_contents: impl FnOnce(&mut egui::Ui)) {
+    CentralPanel::default().frame(egui::Frame::none()).show(ctx, add_contents);
+}
+
+/// The viewport every overlay window starts with.
+pub fn viewport(title: &str) -> egui::ViewportBuilder {
+    egui::ViewportBuilder::default()
+        .with_title(title)
+        .with_always_on_top()
+        .with_transparent(true)
+}
+
+/// The "Show window" switch, returning true if it was switched.
+pub fn display_setting(ui: &mut egui::Ui, display: &mut bool) -> bool {
+    ui.horizontal(|ui| {
+        ui.label(format!("Show window: {}", display));
+        ui.add(toggle(display)).clicked()
+    })
+    .inner
+}
+
+/// Returns true if the size was changed.
+pub fn font_size_setting(ui: &mut egui::Ui, font_size: &mut f32) -> bool {
+    ui.horizontal(|ui| {
+        ui.label("Font size");
+        ui.add(egui::DragValue::new(font_size).range(6.0..=40.0).speed(0.5)).changed()
+    })
+    .inner
+}
+
+/// Returns true if either colour was changed.
+pub fn colour_settings(ui: &mut egui::Ui, bgcolour: &mut egui::Color32, fontcolour: &mut egui::Color32) -> bool {
+    let background = ui.horizontal(|ui| {
+        ui.label("Pick background colour");
+        ui.color_edit_button_srgba(bgcolour).changed()
+    });
+
+    let font = ui.horizontal(|ui| {
+        ui.label("Pick font colour");
+        ui.color_edit_button_srgba(fontcolour).changed()
+    });
+    background.inner || font.inner
+}
+
+/// An overlay running as its own program, with its own telemetry.
+struct StandaloneApp<O: Registered> {
+    config: WindowsConfig,
+    watcher: ConfigWatcher,
+    overlay: O,
+    local_telem: TelemetrySampler,
+}
+
+impl<O: Registered> StandaloneApp<O> {
+    fn new(ctx: Context) -> Self {
+        let config: WindowsConfig = confy::load("iracing-overlays", None).unwrap_or_default();
+        let watcher = ConfigWatcher::new();
+        let overlay = O::new(O::config(&config));
+        let recorder = overlay.recorder();
+        let variables = O::variables();
+        Self {
+            config,
+            watcher,
+            overlay,
+            local_telem: TelemetrySampler::start(
+                &variables,
+                telemetry::overlay_connector(&variables),
+                move |snapshot, changed| {
+                    if let Some(recorder) = &recorder {
+                        recorder(snapshot);
+                    }
+                    if changed {
+                        ctx.request_repaint();
+                    }
+                },
+            ),
+        }
+    }
+}
+
+impl<O: Registered> eframe::App for StandaloneApp<O> {
+    fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
+        // Changes made in the main app apply straight away
+        if let Some(config) = self.watcher.reload() {
+            self.overlay.reconfigure(O::config(&config));
+            self.config = config;
+        }
+        let snapshot = self.local_telem.latest();
+        overlay_window(ctx, |ui| self.overlay.show(ui, O::config(&self.config), &snapshot));
+        // Keep checking the config while there's no telemetry to repaint for
+        ctx.request_repaint_after(CONFIG_CHECK_INTERVAL);
+    }
+}
+
+/// The whole of an overlay's program.
+pub fn run_standalone<O: Registered + 'static>() -> Result<(), eframe::Error> {
+    let options = eframe::NativeOptions {
+        viewport: viewport(O::TITLE),
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        O::TITLE,
+        options,
+        Box::new(|cc| Ok(Box::new(StandaloneApp::<O>::new(cc.egui_ctx.clone())))),
+    )
+}
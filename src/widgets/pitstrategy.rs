@@ -0,0 +1,114 @@
+use super::{overlay_frame, Overlay, Recorder};
+use crate::gaps::Gap;
+use crate::pit_loss::{self, PitLossTracker};
+use crate::session::SessionDetails;
+use crate::telemetry::{TelemetrySnapshot, Variable};
+use crate::track_outline::TrackMapper;
+use crate::util::PitStrategyConfig;
+use eframe::egui::{self, RichText};
+use std::sync::{Arc, Mutex};
+
+pub struct PitStrategyWidget {
+    tracker: Arc<Mutex<PitLossTracker>>,
+}
+
+impl PitStrategyWidget {
+    fn render_row(ui: &mut egui::Ui, session: &SessionDetails, car_idx: usize, gap: Option<&Gap>) {
+        let driver = session.driver(car_idx);
+        let text = |text: String| {
+            let text = RichText::new(text);
+            // Make the player's own row stand out
+            if gap.is_none() {
+                text.strong()
+            } else {
+                text
+            }
+        };
+        ui.label(text(driver.map_or(String::new(), |driver| format!("#{}", driver.car_number))));
+        ui.label(text(driver.map_or(String::new(), |driver| driver.user_name.clone())));
+        ui.label(text(gap.map_or("0.0".to_string(), |gap| format!("{:+.1}", gap.seconds))));
+        ui.end_row();
+    }
+}
+
+impl Overlay for PitStrategyWidget {
+    type Config = PitStrategyConfig;
+    const TITLE: &'static str = "Pit Strategy";
+    const BINARY: &'static str = "pitstrategy";
+
+    fn variables() -> Vec<Variable> {
+        pit_loss::VARIABLES.to_vec()
+    }
+
+    fn new(_config: &PitStrategyConfig) -> Self {
+        Self {
+            // Cached alongside the track outlines
+            tracker: Arc::new(Mutex::new(PitLossTracker::new(TrackMapper::default_cache_dir()))),
+        }
+    }
+
+    /// Timing cars through the pit lane needs every sample, not just the ones that get drawn
+    fn recorder(&self) -> Option<Recorder> {
+        let tracker = self.tracker.clone();
+        Some(Box::new(move |snapshot: &TelemetrySnapshot| {
+            if let Ok(mut tracker) = tracker.lock() {
+                tracker.update(&snapshot.telemetry, snapshot.session.as_deref());
+            }
+        }))
+    }
+
+    fn show(&self, ui: &mut egui::Ui, config: &PitStrategyConfig, snapshot: &TelemetrySnapshot) {
+        let learned = self.tracker.lock().ok().and_then(|tracker| tracker.pit_loss().cloned());
+        overlay_frame(ui, config.font_size, config.overlay_bgcolour, config.overlay_fontcolour, |ui| {
+            if !snapshot.state.has_data() {
+                ui.label("Waiting for iRacing...");
+                return;
+            }
+            let Some(session) = snapshot.session.as_deref() else {
+                ui.label("Waiting for session info...");
+                return;
+            };
+            let Some(learned) = learned else {
+                ui.label("Pit loss unknown until a car uses the pit lane");
+                return;
+            };
+            let total = learned.lane_loss + config.stop_seconds;
+            ui.label(format!(
+                "Pit loss {:.1}s (lane {:.1}s + stop {:.1}s)",
+                total, learned.lane_loss, config.stop_seconds
+            ));
+            let (Some(player_idx), Some(rejoin)) = (
+                snapshot.telemetry.int("PlayerCarIdx"),
+                pit_loss::rejoin_now(&snapshot.telemetry, session, total, config.cars_each_side),
+            ) else {
+                ui.label("Rejoin unavailable");
+                return;
+            };
+            ui.label(format!("Rejoin P{}", rejoin.position));
+            egui::Grid::new("rejoin").striped(true).show(ui, |ui| {
+                for gap in &rejoin.ahead {
+                    Self::render_row(ui, session, gap.car_idx, Some(gap));
+                }
+                Self::render_row(ui, session, player_idx as usize, None);
+                for gap in &rejoin.behind {
+                    Self::render_row(ui, session, gap.car_idx, Some(gap));
+                }
+            });
+        });
+    }
+
+    fn settings(ui: &mut egui::Ui, config: &mut PitStrategyConfig) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Time stopped in the box (s)");
+            changed |= ui.add(egui::DragValue::new(&mut config.stop_seconds).range(0.0..=300.0).speed(0.5)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Cars each side");
+            changed |= ui.add(egui::DragValue::new(&mut config.cars_each_side).range(1..=10)).changed();
+        });
+
+        changed
+    }
+}
@@ -0,0 +1,126 @@
+use super::{overlay_frame, Overlay};
+use crate::proximity::{self, Alongside, RadarCar, Side, CAR_LENGTH};
+use crate::telemetry::{TelemetrySnapshot, Variable};
+use crate::util::RadarConfig;
+use eframe::egui;
+
+/// Roughly the width of a car in metres.
+const CAR_WIDTH: f32 = 2.;
+
+pub struct RadarWidget;
+
+impl RadarWidget {
+    fn render_radar(ui: &mut egui::Ui, config: &RadarConfig, alongside: Alongside, cars: &[RadarCar]) {
+        let colour = |colour: egui::Color32| colour.gamma_multiply(config.opacity);
+        let size = egui::vec2(config.size * 0.6, config.size);
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        if !ui.is_rect_visible(rect) {
+            return;
+        }
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 4., colour(config.overlay_bgcolour));
+
+        // Metres to points, with the range ahead and behind filling the height
+        let scale = rect.height() / 2. / (config.range_metres + CAR_LENGTH);
+        let car_size = egui::vec2(CAR_WIDTH * scale, CAR_LENGTH * scale);
+        let lane = CAR_WIDTH * 1.4 * scale;
+        let car_rect = |x: f32, distance: f32| {
+            // Ahead is up the screen
+            egui::Rect::from_center_size(rect.center() + egui::vec2(x, -distance * scale), car_size)
+        };
+
+        // Light up the sides with cars alongside
+        let (left, right) = alongside.counts();
+        let warning = colour(config.warning_colour);
+        let edge = rect.width() * 0.08;
+        if left > 0 {
+            painter.rect_filled(egui::Rect::from_min_size(rect.min, egui::vec2(edge, rect.height())), 4., warning);
+        }
+        if right > 0 {
+            let min = egui::pos2(rect.right() - edge, rect.top());
+            painter.rect_filled(egui::Rect::from_min_size(min, egui::vec2(edge, rect.height())), 4., warning);
+        }
+
+        for car in cars {
+            let (x, fill) = match car.side {
+                Some(Side::Left) => (-lane, warning),
+                Some(Side::Right) => (lane, warning),
+                None => (0., colour(config.car_colour)),
+            };
+            painter.rect_filled(car_rect(x, car.distance), 2., fill);
+        }
+        painter.rect_filled(car_rect(0., 0.), 2., colour(config.player_colour));
+    }
+}
+
+impl Overlay for RadarWidget {
+    type Config = RadarConfig;
+    const TITLE: &'static str = "Radar";
+    const BINARY: &'static str = "radar";
+
+    fn variables() -> Vec<Variable> {
+        proximity::VARIABLES.to_vec()
+    }
+
+    fn new(_config: &RadarConfig) -> Self {
+        RadarWidget
+    }
+
+    fn show(&self, ui: &mut egui::Ui, config: &RadarConfig, snapshot: &TelemetrySnapshot) {
+        // The radar draws its own background, so it can be see-through
+        overlay_frame(ui, config.font_size, egui::Color32::TRANSPARENT, config.overlay_fontcolour, |ui| {
+            if !snapshot.state.has_data() {
+                ui.label("Waiting for iRacing...");
+                return;
+            }
+            let Some(session) = snapshot.session.as_deref() else {
+                ui.label("Waiting for session info...");
+                return;
+            };
+            let Some((alongside, cars)) = proximity::radar(&snapshot.telemetry, session, config.range_metres) else {
+                ui.label("Radar unavailable");
+                return;
+            };
+            // Nothing to show in the garage
+            if alongside == Alongside::Off {
+                return;
+            }
+            Self::render_radar(ui, config, alongside, &cars);
+        });
+    }
+
+    fn settings(ui: &mut egui::Ui, config: &mut RadarConfig) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Size");
+            changed |= ui.add(egui::DragValue::new(&mut config.size).range(50.0..=800.0).speed(5.)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Opacity");
+            changed |= ui.add(egui::Slider::new(&mut config.opacity, 0.1..=1.0)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Range (m)");
+            changed |= ui.add(egui::DragValue::new(&mut config.range_metres).range(5.0..=100.0).speed(0.5)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick player colour");
+            changed |= ui.color_edit_button_srgba(&mut config.player_colour).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick car colour");
+            changed |= ui.color_edit_button_srgba(&mut config.car_colour).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick alongside colour");
+            changed |= ui.color_edit_button_srgba(&mut config.warning_colour).changed();
+        });
+
+        changed
+    }
+}
@@ -0,0 +1,127 @@
+use super::{colour, overlay_frame, Overlay};
+use crate::gaps::{self, Gap};
+use crate::session::SessionDetails;
+use crate::telemetry::{TelemetrySnapshot, Variable};
+use crate::util::{toggle, RelativeConfig};
+use eframe::egui::{self, RichText};
+
+pub struct RelativeWidget;
+
+impl RelativeWidget {
+    fn text_colour(config: &RelativeConfig, gap: &Gap) -> egui::Color32 {
+        let colour = if gap.laps > 0 {
+            config.lapping_colour
+        } else if gap.laps < 0 {
+            config.lapped_colour
+        } else {
+            config.overlay_fontcolour
+        };
+        // Fade out cars in the pits, as they're not really racing you
+        if gap.on_pit_road {
+            colour.gamma_multiply(0.5)
+        } else {
+            colour
+        }
+    }
+
+    fn render_row(ui: &mut egui::Ui, config: &RelativeConfig, session: &SessionDetails, car_idx: usize, gap: Option<&Gap>) {
+        let text_colour = gap.map_or(config.overlay_fontcolour, |gap| Self::text_colour(config, gap));
+        let driver = session.driver(car_idx);
+        let text = |text: String| {
+            let text = RichText::new(text).color(text_colour);
+            // Make the player's own row stand out
+            if gap.is_none() {
+                text.strong()
+            } else {
+                text
+            }
+        };
+        ui.label(text(driver.map_or(String::new(), |driver| format!("#{}", driver.car_number))));
+        ui.label(text(driver.map_or(String::new(), |driver| driver.user_name.clone())));
+        if config.show_irating {
+            match driver {
+                Some(driver) => {
+                    ui.label(text(format!("{:.1}k", driver.i_rating as f32 / 1000.)));
+                    ui.label(RichText::new(&driver.lic_string).color(colour(driver.lic_color)));
+                }
+                None => {
+                    ui.label("");
+                    ui.label("");
+                }
+            }
+        }
+        ui.label(text(gap.map_or("0.0".to_string(), |gap| format!("{:+.1}", gap.seconds))));
+        ui.end_row();
+    }
+}
+
+impl Overlay for RelativeWidget {
+    type Config = RelativeConfig;
+    const TITLE: &'static str = "Relative";
+    const BINARY: &'static str = "relative";
+
+    fn variables() -> Vec<Variable> {
+        gaps::VARIABLES.to_vec()
+    }
+
+    fn new(_config: &RelativeConfig) -> Self {
+        RelativeWidget
+    }
+
+    fn show(&self, ui: &mut egui::Ui, config: &RelativeConfig, snapshot: &TelemetrySnapshot) {
+        overlay_frame(ui, config.font_size, config.overlay_bgcolour, config.overlay_fontcolour, |ui| {
+            if !snapshot.state.has_data() {
+                ui.label("Waiting for iRacing...");
+                return;
+            }
+            let Some(session) = snapshot.session.as_deref() else {
+                ui.label("Waiting for session info...");
+                return;
+            };
+            let Some(player_idx) = snapshot.telemetry.int("PlayerCarIdx") else {
+                ui.label("Relative unavailable");
+                return;
+            };
+            let Some((ahead, behind)) =
+                gaps::relative(&snapshot.telemetry, session, config.cars_each_side)
+            else {
+                ui.label("Relative unavailable");
+                return;
+            };
+            egui::Grid::new("relative").striped(true).show(ui, |ui| {
+                for gap in &ahead {
+                    Self::render_row(ui, config, session, gap.car_idx, Some(gap));
+                }
+                Self::render_row(ui, config, session, player_idx as usize, None);
+                for gap in &behind {
+                    Self::render_row(ui, config, session, gap.car_idx, Some(gap));
+                }
+            });
+        });
+    }
+
+    fn settings(ui: &mut egui::Ui, config: &mut RelativeConfig) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Cars ahead and behind");
+            changed |= ui.add(egui::DragValue::new(&mut config.cars_each_side).range(1..=10)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Show iRating and licence");
+            changed |= ui.add(toggle(&mut config.show_irating)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick colour for cars laps ahead");
+            changed |= ui.color_edit_button_srgba(&mut config.lapping_colour).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick colour for cars laps behind");
+            changed |= ui.color_edit_button_srgba(&mut config.lapped_colour).changed();
+        });
+
+        changed
+    }
+}
@@ -0,0 +1,163 @@
+use super::{overlay_frame, Overlay};
+use crate::shift_lights::{self, LedBar};
+use crate::telemetry::{TelemetrySnapshot, Variable};
+use crate::util::{toggle, ShiftLightConfig};
+use eframe::egui;
+
+/// How many times a second the lights flash past the blink point.
+const BLINK_RATE: f64 = 8.;
+
+pub struct ShiftLightsWidget;
+
+impl ShiftLightsWidget {
+    /// Green, then yellow, then red from left to right, like a wheel's display.
+    fn led_colour(config: &ShiftLightConfig, led: usize) -> egui::Color32 {
+        let position = (led as f32 + 0.5) / config.led_count as f32;
+        if position < 0.4 {
+            config.low_colour
+        } else if position < 0.75 {
+            config.mid_colour
+        } else {
+            config.high_colour
+        }
+    }
+
+    fn render_leds(ui: &mut egui::Ui, config: &ShiftLightConfig, bar: LedBar) {
+        let count = config.led_count.max(1);
+        let size = egui::vec2(ui.available_width(), config.font_size * 1.5);
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        if !ui.is_rect_visible(rect) {
+            return;
+        }
+        // Off for half of each flash
+        let dark = bar.blink && (ui.input(|i| i.time) * BLINK_RATE * 2.).floor() as i64 % 2 == 1;
+        if bar.blink {
+            ui.ctx().request_repaint_after(std::time::Duration::from_secs_f64(0.5 / BLINK_RATE));
+        }
+        let spacing = rect.width() / count as f32;
+        let radius = (spacing * 0.4).min(rect.height() / 2.);
+        let painter = ui.painter();
+        for led in 0..count {
+            let centre = egui::pos2(rect.left() + spacing * (led as f32 + 0.5), rect.center().y);
+            let colour = if bar.shift {
+                config.shift_colour
+            } else {
+                Self::led_colour(config, led)
+            };
+            let fill = if led < bar.lit && !dark {
+                colour
+            } else {
+                colour.gamma_multiply(0.15)
+            };
+            painter.circle(centre, radius, fill, egui::Stroke::new(1., config.overlay_fontcolour));
+        }
+    }
+}
+
+impl Overlay for ShiftLightsWidget {
+    type Config = ShiftLightConfig;
+    const TITLE: &'static str = "Shift Lights";
+    const BINARY: &'static str = "shiftlights";
+
+    fn variables() -> Vec<Variable> {
+        shift_lights::VARIABLES.to_vec()
+    }
+
+    fn new(_config: &ShiftLightConfig) -> Self {
+        ShiftLightsWidget
+    }
+
+    fn show(&self, ui: &mut egui::Ui, config: &ShiftLightConfig, snapshot: &TelemetrySnapshot) {
+        overlay_frame(ui, config.font_size, config.overlay_bgcolour, config.overlay_fontcolour, |ui| {
+            if !snapshot.state.has_data() {
+                ui.label("Waiting for iRacing...");
+                return;
+            }
+            let car = snapshot
+                .session
+                .as_deref()
+                .and_then(|session| session.player())
+                .map(|driver| driver.car_screen_name.as_str());
+            let Some(points) =
+                shift_lights::shift_points(&snapshot.telemetry, car, &config.custom_shift_points)
+            else {
+                ui.label("No shift points for this car");
+                return;
+            };
+            let rpm = snapshot.telemetry.float("RPM").unwrap_or_default();
+            let gear = snapshot.telemetry.int("Gear").unwrap_or_default();
+            Self::render_leds(ui, config, shift_lights::led_bar(rpm, gear, &points, config.led_count));
+            if config.show_gear {
+                ui.vertical_centered(|ui| {
+                    ui.label(match gear {
+                        -1 => "R".to_string(),
+                        0 => "N".to_string(),
+                        gear => gear.to_string(),
+                    });
+                });
+            }
+        });
+    }
+
+    fn settings(ui: &mut egui::Ui, config: &mut ShiftLightConfig) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Number of lights");
+            changed |= ui.add(egui::DragValue::new(&mut config.led_count).range(3..=20)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Show gear");
+            changed |= ui.add(toggle(&mut config.show_gear)).changed();
+        });
+
+        ui.label("Custom shift points (RPM), used instead of the car's own");
+        let mut remove = None;
+        for (car, points) in config.custom_shift_points.iter_mut() {
+            ui.horizontal(|ui| {
+                ui.label(car);
+                ui.label("First");
+                changed |= ui.add(egui::DragValue::new(&mut points.first_rpm).range(0.0..=points.shift_rpm).speed(10.)).changed();
+                ui.label("Shift");
+                changed |= ui.add(egui::DragValue::new(&mut points.shift_rpm).range(points.first_rpm..=25000.0).speed(10.)).changed();
+                ui.label("Blink");
+                changed |= ui.add(egui::DragValue::new(&mut points.blink_rpm).range(0.0..=25000.0).speed(10.)).changed();
+                if ui.button("Remove").clicked() {
+                    remove = Some(car.clone());
+                }
+            });
+        }
+        if let Some(car) = remove {
+            config.custom_shift_points.remove(&car);
+            changed = true;
+        }
+        ui.horizontal(|ui| {
+            ui.label("Car");
+            // The car name being typed in, kept between frames until it's added
+            let id = ui.id().with("new_shift_car");
+            let mut new_car: String = ui.data_mut(|data| data.get_temp(id)).unwrap_or_default();
+            ui.text_edit_singleline(&mut new_car);
+            let car = new_car.trim();
+            if ui.add_enabled(!car.is_empty(), egui::Button::new("Add")).clicked() {
+                config.custom_shift_points.entry(car.to_string()).or_default();
+                new_car.clear();
+                changed = true;
+            }
+            ui.data_mut(|data| data.insert_temp(id, new_car));
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick light colours");
+            changed |= ui.color_edit_button_srgba(&mut config.low_colour).changed();
+            changed |= ui.color_edit_button_srgba(&mut config.mid_colour).changed();
+            changed |= ui.color_edit_button_srgba(&mut config.high_colour).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick shift colour");
+            changed |= ui.color_edit_button_srgba(&mut config.shift_colour).changed();
+        });
+
+        changed
+    }
+}
@@ -0,0 +1,52 @@
+use super::{overlay_frame, Overlay};
+use crate::telemetry::{TelemetrySnapshot, Variable, VariableType};
+use crate::util::{SpeedConfig, SpeedUnits};
+use eframe::egui;
+
+const VARIABLES: &[Variable] = &[Variable::new("Speed", VariableType::Float)];
+
+pub struct SpeedWidget;
+
+impl Overlay for SpeedWidget {
+    type Config = SpeedConfig;
+    const TITLE: &'static str = "Speed";
+    const BINARY: &'static str = "speed";
+
+    fn variables() -> Vec<Variable> {
+        VARIABLES.to_vec()
+    }
+
+    fn new(_config: &SpeedConfig) -> Self {
+        SpeedWidget
+    }
+
+    fn show(&self, ui: &mut egui::Ui, config: &SpeedConfig, snapshot: &TelemetrySnapshot) {
+        overlay_frame(ui, config.font_size, config.overlay_bgcolour, config.overlay_fontcolour, |ui| {
+            if !snapshot.state.has_data() {
+                ui.label("Waiting for iRacing...");
+                return;
+            }
+            let Some(speed) = snapshot.telemetry.float("Speed") else {
+                ui.label("Speed unavailable");
+                return;
+            };
+            let (unitstring, multiplier) = config.units.label_and_multiplier();
+            ui.label(format!(
+                "{:.0} {}",
+                speed * multiplier, unitstring
+            ));
+        });
+    }
+
+    fn settings(ui: &mut egui::Ui, config: &mut SpeedConfig) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Units:");
+            changed |= ui.radio_value(&mut config.units, SpeedUnits::Metrespersecond, "metres per second").changed();
+            changed |= ui.radio_value(&mut config.units, SpeedUnits::Milesperhour, "miles per hour").changed();
+            changed |= ui.radio_value(&mut config.units, SpeedUnits::Kilometresperhour, "kilometres per hour").changed();
+        });
+
+        changed
+    }
+}
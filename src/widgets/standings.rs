@@ -0,0 +1,129 @@
+use super::{colour, overlay_frame, Overlay};
+use crate::leaderboard::{self, ClassGroup, Standing};
+use crate::session::SessionDetails;
+use crate::telemetry::{TelemetrySnapshot, Variable};
+use crate::time_format;
+use crate::util::{toggle, StandingsConfig};
+use eframe::egui::{self, RichText};
+
+fn gap(standing: &Standing, seconds: f32) -> String {
+    if standing.position <= 0 {
+        time_format::PLACEHOLDER.to_string()
+    } else if standing.laps_down > 0 {
+        format!("+{}L", standing.laps_down)
+    } else {
+        format!("{:.1}", seconds)
+    }
+}
+
+pub struct StandingsWidget;
+
+impl StandingsWidget {
+    fn render_group(ui: &mut egui::Ui, config: &StandingsConfig, session: &SessionDetails, group: ClassGroup, player_idx: Option<usize>) {
+        let always_show = player_idx.filter(|_| config.always_show_player);
+        let standings = leaderboard::limit_rows(group.standings, config.max_rows, always_show);
+        for standing in &standings {
+            let text = |text: String| {
+                let text = RichText::new(text);
+                // Make the player's own row stand out
+                if Some(standing.car_idx) == player_idx {
+                    text.strong()
+                } else {
+                    text
+                }
+            };
+            let driver = session.driver(standing.car_idx);
+            let class_colour = driver.map_or(group.colour, |driver| driver.car_class_color);
+            ui.label(text(if standing.position > 0 { standing.position.to_string() } else { "-".to_string() }));
+            ui.label(text(if standing.class_position > 0 {
+                standing.class_position.to_string()
+            } else {
+                "-".to_string()
+            }).background_color(colour(class_colour)).color(egui::Color32::BLACK));
+            ui.label(text(driver.map_or(String::new(), |driver| driver.user_name.clone())));
+            ui.label(text(time_format::lap_time(standing.last_lap, 3)));
+            ui.label(text(time_format::lap_time(standing.best_lap, 3)));
+            ui.label(text(gap(standing, standing.gap_to_leader)));
+            ui.label(text(gap(standing, standing.interval)));
+            ui.label(text(if standing.on_pit_road { "PIT".to_string() } else { String::new() }));
+            ui.end_row();
+        }
+    }
+}
+
+impl Overlay for StandingsWidget {
+    type Config = StandingsConfig;
+    const TITLE: &'static str = "Standings";
+    const BINARY: &'static str = "standings";
+
+    fn variables() -> Vec<Variable> {
+        leaderboard::VARIABLES.to_vec()
+    }
+
+    fn new(_config: &StandingsConfig) -> Self {
+        StandingsWidget
+    }
+
+    fn show(&self, ui: &mut egui::Ui, config: &StandingsConfig, snapshot: &TelemetrySnapshot) {
+        overlay_frame(ui, config.font_size, config.overlay_bgcolour, config.overlay_fontcolour, |ui| {
+            if !snapshot.state.has_data() {
+                ui.label("Waiting for iRacing...");
+                return;
+            }
+            let Some(session) = snapshot.session.as_deref() else {
+                ui.label("Waiting for session info...");
+                return;
+            };
+            let player_idx = snapshot
+                .telemetry
+                .int("PlayerCarIdx")
+                .and_then(|car_idx| usize::try_from(car_idx).ok());
+            let standings = leaderboard::standings(&snapshot.telemetry, session);
+            let groups = if config.group_by_class {
+                leaderboard::group_by_class(standings, session)
+            } else {
+                vec![ClassGroup {
+                    class_id: 0,
+                    name: String::new(),
+                    colour: 0xffffff,
+                    standings,
+                }]
+            };
+            let grouped = groups.len() > 1;
+            egui::Grid::new("standings").striped(true).show(ui, |ui| {
+                for header in ["Pos", "Class", "Driver", "Last", "Best", "Gap", "Int", "Pit"] {
+                    ui.label(RichText::new(header).strong());
+                }
+                ui.end_row();
+                for group in groups {
+                    if grouped {
+                        ui.label("");
+                        ui.label(RichText::new(&group.name).strong().color(colour(group.colour)));
+                        ui.end_row();
+                    }
+                    Self::render_group(ui, config, session, group, player_idx);
+                }
+            });
+        });
+    }
+
+    fn settings(ui: &mut egui::Ui, config: &mut StandingsConfig) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Group by class");
+            changed |= ui.add(toggle(&mut config.group_by_class)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Maximum rows");
+            changed |= ui.add(egui::DragValue::new(&mut config.max_rows).range(1..=64)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Always show player");
+            changed |= ui.add(toggle(&mut config.always_show_player)).changed();
+        });
+
+        changed
+    }
+}
@@ -0,0 +1,141 @@
+use super::{colour, overlay_frame, Overlay, Recorder};
+use crate::session::SessionDetails;
+use crate::telemetry::{IRacingTelemetry, TelemetrySnapshot, Variable};
+use crate::track_outline::{self, TrackMapper, TrackOutline};
+use crate::util::TrackMapConfig;
+use eframe::egui;
+use std::sync::{Arc, Mutex};
+
+pub struct TrackMapWidget {
+    mapper: Arc<Mutex<TrackMapper>>,
+}
+
+impl TrackMapWidget {
+    fn render_map(
+        ui: &mut egui::Ui,
+        config: &TrackMapConfig,
+        outline: &TrackOutline,
+        telemetry: &IRacingTelemetry,
+        session: &SessionDetails,
+    ) {
+        let (rect, _) = ui.allocate_exact_size(ui.available_size(), egui::Sense::hover());
+        let Some((x0, y0, x1, y1)) = outline.bounds() else {
+            return;
+        };
+        let rect = rect.shrink(config.dot_size * 2.);
+        let scale = (rect.width() / (x1 - x0).max(1.)).min(rect.height() / (y1 - y0).max(1.));
+        let centre = ((x0 + x1) / 2., (y0 + y1) / 2.);
+        // North is up, so y has to be flipped for the screen
+        let to_screen = |(x, y): (f32, f32)| {
+            rect.center() + egui::vec2((x - centre.0) * scale, (centre.1 - y) * scale)
+        };
+
+        let painter = ui.painter();
+        let track: Vec<egui::Pos2> = outline.points.iter().copied().map(to_screen).collect();
+        painter.add(egui::Shape::closed_line(
+            track,
+            egui::Stroke::new(config.dot_size / 2., config.track_colour),
+        ));
+
+        let player_idx = telemetry
+            .int("PlayerCarIdx")
+            .and_then(|car_idx| usize::try_from(car_idx).ok());
+        let mut player = None;
+        for (car_idx, pct) in telemetry
+            .float_array("CarIdxLapDistPct")
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+        {
+            // Cars not in the world have a negative distance
+            let Some(position) = outline.position(*pct).filter(|_| *pct >= 0.) else {
+                continue;
+            };
+            if Some(car_idx) == player_idx {
+                player = Some(position);
+                continue;
+            }
+            let Some(driver) = session.driver(car_idx).filter(|driver| driver.is_racing()) else {
+                continue;
+            };
+            painter.circle_filled(to_screen(position), config.dot_size / 2., colour(driver.car_class_color));
+        }
+        // Drawn last so it's never hidden under another car
+        if let Some(position) = player {
+            painter.circle(
+                to_screen(position),
+                config.dot_size * 0.75,
+                config.player_colour,
+                egui::Stroke::new(1., config.overlay_fontcolour),
+            );
+        }
+    }
+}
+
+impl Overlay for TrackMapWidget {
+    type Config = TrackMapConfig;
+    const TITLE: &'static str = "Track Map";
+    const BINARY: &'static str = "trackmap";
+
+    fn variables() -> Vec<Variable> {
+        track_outline::VARIABLES.to_vec()
+    }
+
+    fn new(_config: &TrackMapConfig) -> Self {
+        Self {
+            mapper: Arc::new(Mutex::new(TrackMapper::new(TrackMapper::default_cache_dir()))),
+        }
+    }
+
+    /// Recording the outline needs every sample, not just the ones that get drawn
+    fn recorder(&self) -> Option<Recorder> {
+        let mapper = self.mapper.clone();
+        Some(Box::new(move |snapshot: &TelemetrySnapshot| {
+            if let Ok(mut mapper) = mapper.lock() {
+                mapper.update(&snapshot.telemetry, snapshot.session.as_deref());
+            }
+        }))
+    }
+
+    fn show(&self, ui: &mut egui::Ui, config: &TrackMapConfig, snapshot: &TelemetrySnapshot) {
+        let (outline, progress) = match self.mapper.lock() {
+            Ok(mapper) => (mapper.outline().cloned(), mapper.recording_progress()),
+            Err(_) => (None, 0.),
+        };
+        overlay_frame(ui, config.font_size, config.overlay_bgcolour, config.overlay_fontcolour, |ui| {
+            if !snapshot.state.has_data() {
+                ui.label("Waiting for iRacing...");
+                return;
+            }
+            let Some(session) = snapshot.session.as_deref() else {
+                ui.label("Waiting for session info...");
+                return;
+            };
+            let Some(outline) = outline else {
+                ui.label(format!("Recording track outline... {:.0}%", progress * 100.));
+                return;
+            };
+            Self::render_map(ui, config, &outline, &snapshot.telemetry, session);
+        });
+    }
+
+    fn settings(ui: &mut egui::Ui, config: &mut TrackMapConfig) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Car size");
+            changed |= ui.add(egui::DragValue::new(&mut config.dot_size).range(2.0..=20.0).speed(0.5)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick track colour");
+            changed |= ui.color_edit_button_srgba(&mut config.track_colour).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick player colour");
+            changed |= ui.color_edit_button_srgba(&mut config.player_colour).changed();
+        });
+
+        changed
+    }
+}
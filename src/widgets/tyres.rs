@@ -0,0 +1,56 @@
+use super::{overlay_frame, Overlay};
+use crate::telemetry::{TelemetrySnapshot, Variable};
+use crate::tyre_data::{self, TyreReading};
+use crate::util::{car_outline, TyresConfig};
+use eframe::egui;
+
+pub struct TyresWidget;
+
+impl Overlay for TyresWidget {
+    type Config = TyresConfig;
+    const TITLE: &'static str = "Tyres";
+    const BINARY: &'static str = "tyres";
+
+    fn variables() -> Vec<Variable> {
+        tyre_data::variables()
+    }
+
+    fn new(_config: &TyresConfig) -> Self {
+        TyresWidget
+    }
+
+    fn show(&self, ui: &mut egui::Ui, config: &TyresConfig, snapshot: &TelemetrySnapshot) {
+        overlay_frame(ui, config.font_size, config.overlay_bgcolour, config.overlay_fontcolour, |ui| {
+            if !snapshot.state.has_data() {
+                ui.label("Waiting for iRacing...");
+                return;
+            }
+            let tyres = TyreReading::all(&snapshot.telemetry);
+            ui.add(car_outline(&tyres, config));
+        });
+    }
+
+    fn settings(ui: &mut egui::Ui, config: &mut TyresConfig) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Target temperature (°C)");
+            changed |= ui.add(egui::DragValue::new(&mut config.temp_min).range(0.0..=config.temp_max).speed(1.)).changed();
+            ui.label("to");
+            changed |= ui.add(egui::DragValue::new(&mut config.temp_max).range(config.temp_min..=200.0).speed(1.)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Target cold pressure (kPa)");
+            changed |= ui.add(egui::DragValue::new(&mut config.pressure_min).range(0.0..=config.pressure_max).speed(1.)).changed();
+            ui.label("to");
+            changed |= ui.add(egui::DragValue::new(&mut config.pressure_max).range(config.pressure_min..=400.0).speed(1.)).changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pick car colour");
+            changed |= ui.color_edit_button_srgba(&mut config.car_colour).changed();
+        });
+
+        changed
+    }
+}